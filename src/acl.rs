@@ -0,0 +1,398 @@
+pub const CLUSTER_NAME: &str = "kafka-cluster";
+pub const WILDCARD_RESOURCE: &str = "*";
+pub const WILDCARD_HOST: &str = "*";
+pub const WILDCARD_PRINCIPAL: &str = "User:*";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourceType {
+    Any,
+    Topic,
+    Group,
+    Cluster,
+    TransactionalId,
+    DelegationToken,
+    User,
+}
+
+impl ResourceType {
+    pub fn code(self) -> i8 {
+        match self {
+            ResourceType::Any => 1,
+            ResourceType::Topic => 2,
+            ResourceType::Group => 3,
+            ResourceType::Cluster => 4,
+            ResourceType::TransactionalId => 5,
+            ResourceType::DelegationToken => 6,
+            ResourceType::User => 7,
+        }
+    }
+
+    pub fn from_code(code: i8) -> Option<ResourceType> {
+        match code {
+            1 => Some(ResourceType::Any),
+            2 => Some(ResourceType::Topic),
+            3 => Some(ResourceType::Group),
+            4 => Some(ResourceType::Cluster),
+            5 => Some(ResourceType::TransactionalId),
+            6 => Some(ResourceType::DelegationToken),
+            7 => Some(ResourceType::User),
+            _ => None,
+        }
+    }
+
+    // Operations that apply to resources of this type
+    pub fn operations(self) -> &'static [AclOperation] {
+        match self {
+            ResourceType::Topic => &[
+                AclOperation::Read,
+                AclOperation::Write,
+                AclOperation::Create,
+                AclOperation::Delete,
+                AclOperation::Alter,
+                AclOperation::Describe,
+                AclOperation::DescribeConfigs,
+                AclOperation::AlterConfigs,
+            ],
+            ResourceType::Group => &[
+                AclOperation::Read,
+                AclOperation::Describe,
+                AclOperation::Delete,
+            ],
+            ResourceType::Cluster => &[
+                AclOperation::Create,
+                AclOperation::ClusterAction,
+                AclOperation::DescribeConfigs,
+                AclOperation::AlterConfigs,
+                AclOperation::IdempotentWrite,
+                AclOperation::Alter,
+                AclOperation::Describe,
+            ],
+            ResourceType::TransactionalId => &[AclOperation::Describe, AclOperation::Write],
+            ResourceType::DelegationToken => &[AclOperation::Describe],
+            ResourceType::User => &[AclOperation::CreateTokens, AclOperation::DescribeTokens],
+            ResourceType::Any => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatternType {
+    Any,
+    Match,
+    Literal,
+    Prefixed,
+}
+
+impl PatternType {
+    pub fn code(self) -> i8 {
+        match self {
+            PatternType::Any => 1,
+            PatternType::Match => 2,
+            PatternType::Literal => 3,
+            PatternType::Prefixed => 4,
+        }
+    }
+
+    pub fn from_code(code: i8) -> Option<PatternType> {
+        match code {
+            1 => Some(PatternType::Any),
+            2 => Some(PatternType::Match),
+            3 => Some(PatternType::Literal),
+            4 => Some(PatternType::Prefixed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AclOperation {
+    Any,
+    All,
+    Read,
+    Write,
+    Create,
+    Delete,
+    Alter,
+    Describe,
+    ClusterAction,
+    DescribeConfigs,
+    AlterConfigs,
+    IdempotentWrite,
+    CreateTokens,
+    DescribeTokens,
+}
+
+impl AclOperation {
+    pub fn code(self) -> i8 {
+        match self {
+            AclOperation::Any => 1,
+            AclOperation::All => 2,
+            AclOperation::Read => 3,
+            AclOperation::Write => 4,
+            AclOperation::Create => 5,
+            AclOperation::Delete => 6,
+            AclOperation::Alter => 7,
+            AclOperation::Describe => 8,
+            AclOperation::ClusterAction => 9,
+            AclOperation::DescribeConfigs => 10,
+            AclOperation::AlterConfigs => 11,
+            AclOperation::IdempotentWrite => 12,
+            AclOperation::CreateTokens => 13,
+            AclOperation::DescribeTokens => 14,
+        }
+    }
+
+    pub fn from_code(code: i8) -> Option<AclOperation> {
+        match code {
+            1 => Some(AclOperation::Any),
+            2 => Some(AclOperation::All),
+            3 => Some(AclOperation::Read),
+            4 => Some(AclOperation::Write),
+            5 => Some(AclOperation::Create),
+            6 => Some(AclOperation::Delete),
+            7 => Some(AclOperation::Alter),
+            8 => Some(AclOperation::Describe),
+            9 => Some(AclOperation::ClusterAction),
+            10 => Some(AclOperation::DescribeConfigs),
+            11 => Some(AclOperation::AlterConfigs),
+            12 => Some(AclOperation::IdempotentWrite),
+            13 => Some(AclOperation::CreateTokens),
+            14 => Some(AclOperation::DescribeTokens),
+            _ => None,
+        }
+    }
+
+    // Whether an ALLOW acl for this operation also allows `operation`
+    pub fn implies(self, operation: AclOperation) -> bool {
+        if self == AclOperation::All || self == operation {
+            return true;
+        }
+        match operation {
+            AclOperation::Describe => matches!(
+                self,
+                AclOperation::Read
+                    | AclOperation::Write
+                    | AclOperation::Delete
+                    | AclOperation::Alter
+            ),
+            AclOperation::DescribeConfigs => self == AclOperation::AlterConfigs,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PermissionType {
+    Any,
+    Deny,
+    Allow,
+}
+
+impl PermissionType {
+    pub fn code(self) -> i8 {
+        match self {
+            PermissionType::Any => 1,
+            PermissionType::Deny => 2,
+            PermissionType::Allow => 3,
+        }
+    }
+
+    pub fn from_code(code: i8) -> Option<PermissionType> {
+        match code {
+            1 => Some(PermissionType::Any),
+            2 => Some(PermissionType::Deny),
+            3 => Some(PermissionType::Allow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AclBinding {
+    pub resource_type: ResourceType,
+    pub resource_name: String,
+    pub pattern_type: PatternType,
+    pub principal: String,
+    pub host: String,
+    pub operation: AclOperation,
+    pub permission_type: PermissionType,
+}
+
+impl AclBinding {
+    pub fn from_codes(
+        resource_type: i8,
+        resource_name: String,
+        pattern_type: i8,
+        principal: String,
+        host: String,
+        operation: i8,
+        permission_type: i8,
+    ) -> Option<AclBinding> {
+        Some(AclBinding {
+            resource_type: ResourceType::from_code(resource_type)?,
+            resource_name,
+            pattern_type: PatternType::from_code(pattern_type)?,
+            principal,
+            host,
+            operation: AclOperation::from_code(operation)?,
+            permission_type: PermissionType::from_code(permission_type)?,
+        })
+    }
+
+    // Filter matching exactly this binding
+    pub fn to_filter(&self) -> AclBindingFilter {
+        AclBindingFilter {
+            resource_type: self.resource_type,
+            resource_name: Some(self.resource_name.clone()),
+            pattern_type: self.pattern_type,
+            principal: Some(self.principal.clone()),
+            host: Some(self.host.clone()),
+            operation: self.operation,
+            permission_type: self.permission_type,
+        }
+    }
+
+    // Bindings can only be stored with concrete values, never ANY or MATCH
+    pub fn validate(&self) -> Result<(), String> {
+        if self.resource_type == ResourceType::Any {
+            return Err("Invalid resource type ANY".to_string());
+        }
+        if !matches!(
+            self.pattern_type,
+            PatternType::Literal | PatternType::Prefixed
+        ) {
+            return Err(format!("Invalid pattern type {:?}", self.pattern_type));
+        }
+        if self.operation == AclOperation::Any || self.permission_type == PermissionType::Any {
+            return Err("Invalid operation or permission type ANY".to_string());
+        }
+        if self.resource_name.is_empty() {
+            return Err("Resource name must not be empty".to_string());
+        }
+        if self.resource_type == ResourceType::Cluster && self.resource_name != CLUSTER_NAME {
+            return Err(format!(
+                "The only valid name for the CLUSTER resource is {}",
+                CLUSTER_NAME
+            ));
+        }
+        if !self.principal.contains(':') {
+            return Err(format!("Could not parse principal {}", self.principal));
+        }
+        Ok(())
+    }
+
+    pub fn matches_resource(&self, resource_type: ResourceType, resource_name: &str) -> bool {
+        if self.resource_type != resource_type {
+            return false;
+        }
+        match self.pattern_type {
+            PatternType::Literal => {
+                self.resource_name == resource_name || self.resource_name == WILDCARD_RESOURCE
+            }
+            PatternType::Prefixed => resource_name.starts_with(&self.resource_name),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AclBindingFilter {
+    pub resource_type: ResourceType,
+    pub resource_name: Option<String>,
+    pub pattern_type: PatternType,
+    pub principal: Option<String>,
+    pub host: Option<String>,
+    pub operation: AclOperation,
+    pub permission_type: PermissionType,
+}
+
+impl AclBindingFilter {
+    pub fn from_codes(
+        resource_type: i8,
+        resource_name: Option<String>,
+        pattern_type: i8,
+        principal: Option<String>,
+        host: Option<String>,
+        operation: i8,
+        permission_type: i8,
+    ) -> Option<AclBindingFilter> {
+        Some(AclBindingFilter {
+            resource_type: ResourceType::from_code(resource_type)?,
+            resource_name,
+            pattern_type: PatternType::from_code(pattern_type)?,
+            principal,
+            host,
+            operation: AclOperation::from_code(operation)?,
+            permission_type: PermissionType::from_code(permission_type)?,
+        })
+    }
+
+    fn matches_pattern(&self, binding: &AclBinding) -> bool {
+        if self.resource_type != ResourceType::Any && self.resource_type != binding.resource_type {
+            return false;
+        }
+        if !matches!(self.pattern_type, PatternType::Any | PatternType::Match)
+            && self.pattern_type != binding.pattern_type
+        {
+            return false;
+        }
+        let name = match &self.resource_name {
+            Some(name) => name,
+            None => return true,
+        };
+        if self.pattern_type != PatternType::Match {
+            return *name == binding.resource_name;
+        }
+        // MATCH finds every binding that applies to the named resource
+        match binding.pattern_type {
+            PatternType::Literal => {
+                *name == binding.resource_name || binding.resource_name == WILDCARD_RESOURCE
+            }
+            PatternType::Prefixed => name.starts_with(&binding.resource_name),
+            _ => false,
+        }
+    }
+
+    pub fn matches(&self, binding: &AclBinding) -> bool {
+        self.matches_pattern(binding)
+            && self
+                .principal
+                .as_ref()
+                .map_or(true, |principal| *principal == binding.principal)
+            && self
+                .host
+                .as_ref()
+                .map_or(true, |host| *host == binding.host)
+            && (self.operation == AclOperation::Any || self.operation == binding.operation)
+            && (self.permission_type == PermissionType::Any
+                || self.permission_type == binding.permission_type)
+    }
+}
+
+#[test]
+fn test_filter_matches() {
+    let binding = AclBinding {
+        resource_type: ResourceType::Topic,
+        resource_name: "orders-".to_string(),
+        pattern_type: PatternType::Prefixed,
+        principal: "User:alice".to_string(),
+        host: WILDCARD_HOST.to_string(),
+        operation: AclOperation::Read,
+        permission_type: PermissionType::Allow,
+    };
+    let filter = |pattern_type, resource_name: &str| AclBindingFilter {
+        resource_type: ResourceType::Any,
+        resource_name: Some(resource_name.to_string()),
+        pattern_type,
+        principal: None,
+        host: None,
+        operation: AclOperation::Any,
+        permission_type: PermissionType::Any,
+    };
+
+    assert!(filter(PatternType::Match, "orders-eu").matches(&binding));
+    assert!(!filter(PatternType::Match, "payments").matches(&binding));
+    assert!(filter(PatternType::Prefixed, "orders-").matches(&binding));
+    assert!(!filter(PatternType::Literal, "orders-").matches(&binding));
+    assert!(!filter(PatternType::Any, "orders-eu").matches(&binding));
+}
@@ -0,0 +1,92 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::partition_log::PartitionLog;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let transactional_id = input.get_compact_string();
+    let producer_id = input.get_i64();
+    let producer_epoch = input.get_i16();
+    let topic_count = input.get_compact_array_length();
+    let mut partitions: Vec<(String, i32)> = vec![];
+    for _ in 0..topic_count {
+        let name = input.get_compact_string();
+        let partition_count = input.get_compact_array_length();
+        for _ in 0..partition_count {
+            partitions.push((name.clone(), input.get_i32()));
+        }
+        input.skip_tagged_fields();
+    }
+    input.skip_tagged_fields();
+
+    // Unauthorized or unknown partitions fail the request, the rest are not attempted
+    let log_dir = broker.config.metadata_log_dir();
+    let failures: Vec<Option<i16>> = partitions
+        .iter()
+        .map(|(topic, partition)| {
+            if !broker.authorize(session, AclOperation::Write, ResourceType::Topic, topic) {
+                Some(error_code::TOPIC_AUTHORIZATION_FAILED)
+            } else if PartitionLog::open(&log_dir, topic, *partition).is_none() {
+                Some(error_code::UNKNOWN_TOPIC_OR_PARTITION)
+            } else {
+                None
+            }
+        })
+        .collect();
+    let error_codes: Vec<i16> = if !broker.authorize(
+        session,
+        AclOperation::Write,
+        ResourceType::TransactionalId,
+        &transactional_id,
+    ) {
+        vec![error_code::TRANSACTIONAL_ID_AUTHORIZATION_FAILED; partitions.len()]
+    } else if failures.iter().any(Option::is_some) {
+        failures
+            .iter()
+            .map(|failure| failure.unwrap_or(error_code::OPERATION_NOT_ATTEMPTED))
+            .collect()
+    } else {
+        let result = broker.transactions.lock().unwrap().add_partitions(
+            &transactional_id,
+            producer_id,
+            producer_epoch,
+            &partitions,
+        );
+        vec![result.err().unwrap_or(error_code::NONE); partitions.len()]
+    };
+
+    // Serialize result
+    let mut results: Vec<(&str, Vec<(i32, i16)>)> = vec![];
+    for ((topic, partition), error_code) in partitions.iter().zip(error_codes) {
+        match results.last_mut() {
+            Some((name, topic_results)) if name == topic => {
+                topic_results.push((*partition, error_code))
+            }
+            _ => results.push((topic, vec![(*partition, error_code)])),
+        }
+    }
+
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(results.len());
+    for (name, topic_results) in results {
+        body.put_compact_string(name);
+        body.put_compact_array_length(topic_results.len());
+        for (partition, error_code) in topic_results {
+            body.put_i32(partition);
+            body.put_i16(error_code);
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
@@ -0,0 +1,120 @@
+use crate::acl::{AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::cluser_metadata::ClientQuotaRecord;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::quota;
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+#[derive(Debug)]
+struct AlterClientQuotasEntry {
+    entity: Vec<(String, Option<String>)>,
+    // Key, value and whether to remove it
+    ops: Vec<(String, f64, bool)>,
+}
+
+fn quota_records(entry: &AlterClientQuotasEntry) -> Result<Vec<ClientQuotaRecord>, String> {
+    let entity = quota::validate_entity(&entry.entity)?;
+    entry
+        .ops
+        .iter()
+        .map(|(key, value, remove)| {
+            if *remove {
+                // Removed values are ignored, but the key still has to exist
+                quota::validate_quota(&entity, key, 1.0)?;
+            } else {
+                quota::validate_quota(&entity, key, *value)?;
+            }
+            Ok(ClientQuotaRecord::new(&entity, key, *value, *remove))
+        })
+        .collect()
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let entries_length = input.get_compact_array_length();
+    let entries: Vec<AlterClientQuotasEntry> = (0..entries_length)
+        .map(|_| {
+            let entity_length = input.get_compact_array_length();
+            let entity = (0..entity_length)
+                .map(|_| {
+                    let entity_type = input.get_compact_string();
+                    let entity_name = input.get_compact_nullable_string();
+                    input.skip_tagged_fields();
+                    (entity_type, entity_name)
+                })
+                .collect();
+            let ops_length = input.get_compact_array_length();
+            let ops = (0..ops_length)
+                .map(|_| {
+                    let key = input.get_compact_string();
+                    let value = input.get_f64();
+                    let remove = input.get_u8() != 0;
+                    input.skip_tagged_fields();
+                    (key, value, remove)
+                })
+                .collect();
+            input.skip_tagged_fields();
+            AlterClientQuotasEntry { entity, ops }
+        })
+        .collect();
+    let validate_only = input.get_u8() != 0;
+    input.skip_tagged_fields();
+
+    let authorized = broker.authorize(
+        session,
+        AclOperation::AlterConfigs,
+        ResourceType::Cluster,
+        CLUSTER_NAME,
+    );
+    let results: Vec<Result<(), (i16, String)>> = entries
+        .iter()
+        .map(|entry| {
+            if !authorized {
+                return Err((
+                    error_code::CLUSTER_AUTHORIZATION_FAILED,
+                    "Cluster authorization failed".to_string(),
+                ));
+            }
+            let records =
+                quota_records(entry).map_err(|message| (error_code::INVALID_REQUEST, message))?;
+            if validate_only {
+                return Ok(());
+            }
+            let records = records.iter().map(|record| record.encode()).collect();
+            broker
+                .append_metadata(records)
+                .map_err(|e| (error_code::KAFKA_STORAGE_ERROR, e.to_string()))
+        })
+        .collect();
+    if !validate_only && results.iter().any(Result::is_ok) {
+        broker.reload_quotas();
+    }
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(entries.len());
+    for (entry, result) in entries.iter().zip(results) {
+        let (error_code, error_message) = match result {
+            Ok(()) => (error_code::NONE, None),
+            Err((error_code, error_message)) => (error_code, Some(error_message)),
+        };
+        body.put_i16(error_code);
+        body.put_compact_nullable_string(error_message.as_deref());
+        body.put_compact_array_length(entry.entity.len());
+        for (entity_type, entity_name) in &entry.entity {
+            body.put_compact_string(entity_type);
+            body.put_compact_nullable_string(entity_name.as_deref());
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
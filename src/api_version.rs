@@ -1,3 +1,11 @@
+use crate::broker::Broker;
+use crate::error_code;
+use crate::feature::SUPPORTED_FEATURES;
+use crate::protocol::CompactBufMut;
+use crate::varint::PutVarint;
+
+use bytes::BufMut;
+
 struct ApiKeyVerInfo {
     pub id: i16,
     pub min: i16,
@@ -11,6 +19,162 @@ const API_VERSIONS: &[ApiKeyVerInfo] = &[
         min: 0,
         max: 4,
     },
+    // Produce
+    ApiKeyVerInfo {
+        id: 0,
+        min: 9,
+        max: 11,
+    },
+    // Fetch
+    ApiKeyVerInfo {
+        id: 1,
+        min: 12,
+        max: 16,
+    },
+    // ListOffsets
+    ApiKeyVerInfo {
+        id: 2,
+        min: 6,
+        max: 8,
+    },
+    // Metadata
+    ApiKeyVerInfo {
+        id: 3,
+        min: 9,
+        max: 12,
+    },
+    // FindCoordinator
+    ApiKeyVerInfo {
+        id: 10,
+        min: 4,
+        max: 6,
+    },
+    // SaslHandshake
+    ApiKeyVerInfo {
+        id: 17,
+        min: 1,
+        max: 1,
+    },
+    // DeleteRecords
+    ApiKeyVerInfo {
+        id: 21,
+        min: 2,
+        max: 2,
+    },
+    // InitProducerId
+    ApiKeyVerInfo {
+        id: 22,
+        min: 2,
+        max: 5,
+    },
+    // OffsetForLeaderEpoch
+    ApiKeyVerInfo {
+        id: 23,
+        min: 4,
+        max: 4,
+    },
+    // AddPartitionsToTxn
+    ApiKeyVerInfo {
+        id: 24,
+        min: 3,
+        max: 3,
+    },
+    // AddOffsetsToTxn
+    ApiKeyVerInfo {
+        id: 25,
+        min: 3,
+        max: 4,
+    },
+    // EndTxn
+    ApiKeyVerInfo {
+        id: 26,
+        min: 3,
+        max: 4,
+    },
+    // WriteTxnMarkers
+    ApiKeyVerInfo {
+        id: 27,
+        min: 1,
+        max: 1,
+    },
+    // TxnOffsetCommit
+    ApiKeyVerInfo {
+        id: 28,
+        min: 3,
+        max: 4,
+    },
+    // DescribeAcls
+    ApiKeyVerInfo {
+        id: 29,
+        min: 2,
+        max: 3,
+    },
+    // CreateAcls
+    ApiKeyVerInfo {
+        id: 30,
+        min: 2,
+        max: 3,
+    },
+    // DeleteAcls
+    ApiKeyVerInfo {
+        id: 31,
+        min: 2,
+        max: 3,
+    },
+    // DescribeConfigs
+    ApiKeyVerInfo {
+        id: 32,
+        min: 4,
+        max: 4,
+    },
+    // AlterConfigs
+    ApiKeyVerInfo {
+        id: 33,
+        min: 2,
+        max: 2,
+    },
+    // DescribeLogDirs
+    ApiKeyVerInfo {
+        id: 35,
+        min: 2,
+        max: 4,
+    },
+    // SaslAuthenticate
+    ApiKeyVerInfo {
+        id: 36,
+        min: 2,
+        max: 2,
+    },
+    // IncrementalAlterConfigs
+    ApiKeyVerInfo {
+        id: 44,
+        min: 1,
+        max: 1,
+    },
+    // DescribeClientQuotas
+    ApiKeyVerInfo {
+        id: 48,
+        min: 1,
+        max: 1,
+    },
+    // AlterClientQuotas
+    ApiKeyVerInfo {
+        id: 49,
+        min: 1,
+        max: 1,
+    },
+    // UpdateFeatures
+    ApiKeyVerInfo {
+        id: 57,
+        min: 0,
+        max: 1,
+    },
+    // DescribeCluster
+    ApiKeyVerInfo {
+        id: 60,
+        min: 0,
+        max: 1,
+    },
     // DescribeTopicPartitions
     ApiKeyVerInfo {
         id: 75,
@@ -19,10 +183,51 @@ const API_VERSIONS: &[ApiKeyVerInfo] = &[
     },
 ];
 
-pub fn handle_request(input: &[u8]) -> Vec<u8> {
+// Supported features, finalized features epoch and finalized features tagged fields
+fn feature_tagged_fields(broker: &Broker) -> Vec<u8> {
+    let mut supported_features = vec![];
+    supported_features.put_compact_array_length(SUPPORTED_FEATURES.len());
+    for (name, min, max) in SUPPORTED_FEATURES {
+        supported_features.put_compact_string(name);
+        supported_features.put_i16(*min);
+        supported_features.put_i16(*max);
+        supported_features.put_empty_tagged_fields();
+    }
+    let mut tagged_fields = vec![(0, supported_features)];
+
+    let metadata = broker.metadata();
+    let features = metadata.finalized_features();
+    tagged_fields.push((1, metadata.last_offset.to_be_bytes().to_vec()));
+    if !features.is_empty() {
+        let mut finalized_features = vec![];
+        finalized_features.put_compact_array_length(features.len());
+        for (name, level) in features {
+            finalized_features.put_compact_string(name);
+            finalized_features.put_i16(*level); // Max version level
+            finalized_features.put_i16(*level); // Min version level
+            finalized_features.put_empty_tagged_fields();
+        }
+        tagged_fields.push((2, finalized_features));
+    }
+
+    let mut output = vec![];
+    output.put_unsigned_varint(tagged_fields.len() as u64);
+    for (tag, value) in tagged_fields {
+        output.put_unsigned_varint(tag);
+        output.put_unsigned_varint(value.len() as u64);
+        output.extend_from_slice(&value);
+    }
+    output
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker) -> Vec<u8> {
     let api_version = i16::from_be_bytes(input[6..8].try_into().unwrap());
     let correlation_id = &input[8..12];
-    let error_code: i16 = if api_version != 4 { 35 } else { 0 };
+    let error_code: i16 = if api_version != 4 {
+        error_code::UNSUPPORTED_VERSION
+    } else {
+        error_code::NONE
+    };
     let array_length: u8 = API_VERSIONS.len() as u8 + 1;
     let tag_buffer: u8 = 0;
     let throttle_time: i32 = 0;
@@ -44,7 +249,7 @@ pub fn handle_request(input: &[u8]) -> Vec<u8> {
     }
 
     body.extend_from_slice(&throttle_time.to_be_bytes());
-    body.extend_from_slice(&tag_buffer.to_be_bytes());
+    body.extend_from_slice(&feature_tagged_fields(broker));
 
     // Write to stream
     let mut result = vec![];
@@ -52,5 +257,5 @@ pub fn handle_request(input: &[u8]) -> Vec<u8> {
     result.extend_from_slice(&message_size.to_be_bytes());
     result.extend_from_slice(&header);
     result.extend_from_slice(&body);
-    return result;
+    result
 }
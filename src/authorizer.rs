@@ -0,0 +1,171 @@
+use crate::acl::{
+    AclBinding, AclBindingFilter, AclOperation, PermissionType, ResourceType, WILDCARD_HOST,
+    WILDCARD_PRINCIPAL,
+};
+use crate::config::BrokerConfig;
+use crate::metadata_image::MetadataImage;
+use crate::session::Session;
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::RwLock;
+
+pub trait Authorizer: Debug + Send + Sync {
+    // Rebuilds the authorizer's state from the metadata log
+    fn load(&self, metadata: &MetadataImage);
+
+    fn authorize(
+        &self,
+        session: &Session,
+        operation: AclOperation,
+        resource_type: ResourceType,
+        resource_name: &str,
+    ) -> bool;
+
+    // Bindings matching the filter, keyed by their id in the metadata log
+    fn acls(&self, filter: &AclBindingFilter) -> Vec<([u8; 16], AclBinding)>;
+}
+
+// Authorizes with the ACLs stored as AccessControlEntryRecords
+#[derive(Debug)]
+pub struct StandardAuthorizer {
+    super_users: Vec<String>,
+    allow_everyone_if_no_acl_found: bool,
+    acls: RwLock<BTreeMap<[u8; 16], AclBinding>>,
+}
+
+impl StandardAuthorizer {
+    pub fn new(config: &BrokerConfig) -> StandardAuthorizer {
+        let super_users = config
+            .get("super.users")
+            .map(|users| {
+                users
+                    .split(';')
+                    .map(|user| user.trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let allow_everyone_if_no_acl_found =
+            config.get("allow.everyone.if.no.acl.found") == Some("true");
+        StandardAuthorizer {
+            super_users,
+            allow_everyone_if_no_acl_found,
+            acls: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Authorizer for StandardAuthorizer {
+    fn load(&self, metadata: &MetadataImage) {
+        let acls = metadata
+            .acls()
+            .iter()
+            .filter_map(|(id, acl_record)| Some((*id, acl_record.binding()?)))
+            .collect();
+        *self.acls.write().unwrap() = acls;
+    }
+
+    fn authorize(
+        &self,
+        session: &Session,
+        operation: AclOperation,
+        resource_type: ResourceType,
+        resource_name: &str,
+    ) -> bool {
+        if self.super_users.contains(&session.principal) {
+            return true;
+        }
+
+        let mut resource_has_acls = false;
+        let mut allowed = false;
+        for acl in self.acls.read().unwrap().values() {
+            if !acl.matches_resource(resource_type, resource_name) {
+                continue;
+            }
+            resource_has_acls = true;
+            if acl.principal != session.principal && acl.principal != WILDCARD_PRINCIPAL {
+                continue;
+            }
+            if acl.host != session.client_host && acl.host != WILDCARD_HOST {
+                continue;
+            }
+            match acl.permission_type {
+                // DENY acls never imply other operations
+                PermissionType::Deny
+                    if acl.operation == AclOperation::All || acl.operation == operation =>
+                {
+                    return false
+                }
+                PermissionType::Allow if acl.operation.implies(operation) => allowed = true,
+                _ => {}
+            }
+        }
+
+        allowed || (!resource_has_acls && self.allow_everyone_if_no_acl_found)
+    }
+
+    fn acls(&self, filter: &AclBindingFilter) -> Vec<([u8; 16], AclBinding)> {
+        self.acls
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, acl)| filter.matches(acl))
+            .map(|(id, acl)| (*id, acl.clone()))
+            .collect()
+    }
+}
+
+#[test]
+fn test_authorize() {
+    use crate::acl::PatternType;
+
+    let authorizer = StandardAuthorizer::new(&BrokerConfig::parse("super.users=User:admin\n"));
+    let acl = |resource_name: &str, principal: &str, operation, permission_type| AclBinding {
+        resource_type: ResourceType::Topic,
+        resource_name: resource_name.to_string(),
+        pattern_type: PatternType::Literal,
+        principal: principal.to_string(),
+        host: WILDCARD_HOST.to_string(),
+        operation,
+        permission_type,
+    };
+    *authorizer.acls.write().unwrap() = BTreeMap::from([
+        (
+            [1; 16],
+            acl(
+                "orders",
+                WILDCARD_PRINCIPAL,
+                AclOperation::Read,
+                PermissionType::Allow,
+            ),
+        ),
+        (
+            [2; 16],
+            acl(
+                "orders",
+                "User:mallory",
+                AclOperation::All,
+                PermissionType::Deny,
+            ),
+        ),
+    ]);
+    let session = |principal: &str| Session {
+        principal: principal.to_string(),
+        ..Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false)
+    };
+    let authorize = |principal, operation, resource_name| {
+        authorizer.authorize(
+            &session(principal),
+            operation,
+            ResourceType::Topic,
+            resource_name,
+        )
+    };
+
+    assert!(authorize("User:alice", AclOperation::Read, "orders"));
+    assert!(authorize("User:alice", AclOperation::Describe, "orders"));
+    assert!(!authorize("User:alice", AclOperation::Write, "orders"));
+    assert!(!authorize("User:mallory", AclOperation::Read, "orders"));
+    assert!(!authorize("User:alice", AclOperation::Read, "payments"));
+    assert!(authorize("User:admin", AclOperation::Write, "orders"));
+}
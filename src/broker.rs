@@ -0,0 +1,509 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::authorizer::{Authorizer, StandardAuthorizer};
+use crate::cluser_metadata::{ProducerIdsRecord, METADATA_TOPIC};
+use crate::compression::Compression;
+use crate::config::BrokerConfig;
+use crate::dynamic_config::{self, BROKER_RESOURCE, TOPIC_RESOURCE};
+use crate::feature::IBP_3_5_IV2;
+use crate::jaas;
+use crate::meta_properties::MetaProperties;
+use crate::metadata_image::{self, MetadataImage, BOOTSTRAP_CHECKPOINT};
+use crate::partition_log::PartitionLog;
+use crate::quota::ClientQuotas;
+use crate::record_batch::{now_ms, Record, RecordBatch};
+use crate::sasl::{ScramCredential, ScramMechanism};
+use crate::session::Session;
+use crate::transaction_coordinator::TransactionCoordinator;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+// How often the tailer checks the metadata log for batches appended by
+// another process sharing the log dir. Only the segment sizes are read
+// while nothing changes, so polling is cheap, and metadata changes made
+// elsewhere show up within half a second.
+const METADATA_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// State shared by every connection
+#[derive(Debug)]
+pub struct Broker {
+    pub config: BrokerConfig,
+    pub transactions: Mutex<TransactionCoordinator>,
+    // Without an authorizer every request is allowed
+    pub authorizer: Option<Box<dyn Authorizer>>,
+    // SASL/PLAIN users and their passwords
+    pub plain_users: HashMap<String, String>,
+    pub quotas: ClientQuotas,
+    // Held while ACLs are looked up and changed, so concurrent CreateAcls
+    // and DeleteAcls requests act on each other's records
+    pub acl_changes: Mutex<()>,
+    metadata_log: Mutex<PartitionLog>,
+    // Replaced as a whole on each update, so readers keep a consistent image
+    metadata: RwLock<Arc<MetadataImage>>,
+}
+
+impl Broker {
+    pub fn new(config: BrokerConfig) -> io::Result<Broker> {
+        let log_dir = config.metadata_log_dir();
+        let metadata_log = PartitionLog::create(&log_dir, METADATA_TOPIC, 0)?;
+        recover_logs(&config)?;
+        let metadata = MetadataImage::load(&metadata_log)?;
+        // Segments below the latest snapshot may have been deleted
+        if metadata_log.log_end_offset()? <= metadata.last_offset {
+            metadata_log.roll(metadata.last_offset + 1)?;
+        }
+        let transactions = TransactionCoordinator::load(&log_dir, metadata.next_producer_id())?;
+        let authorizer: Option<Box<dyn Authorizer>> = match config.get("authorizer.class.name") {
+            None | Some("") => None,
+            Some(class_name) if class_name.ends_with("StandardAuthorizer") => {
+                Some(Box::new(StandardAuthorizer::new(&config)))
+            }
+            Some(class_name) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Unknown authorizer {}", class_name),
+                ))
+            }
+        };
+        // The JAAS file Kafka takes through -Djava.security.auth.login.config
+        let plain_users = match config.get("java.security.auth.login.config") {
+            Some(path) => jaas::plain_users(&fs::read_to_string(path)?),
+            None => HashMap::new(),
+        };
+        let broker = Broker {
+            quotas: ClientQuotas::new(&config),
+            config,
+            transactions: Mutex::new(transactions),
+            authorizer,
+            plain_users,
+            acl_changes: Mutex::new(()),
+            metadata_log: Mutex::new(metadata_log),
+            metadata: RwLock::new(Arc::new(metadata)),
+        };
+        broker.bootstrap_metadata()?;
+        broker.reload_authorizer();
+        broker.reload_quotas();
+        Ok(broker)
+    }
+
+    // A new cluster's metadata log starts with the bootstrap records, such as
+    // its metadata.version
+    fn bootstrap_metadata(&self) -> io::Result<()> {
+        let path = self.config.metadata_log_dir().join(BOOTSTRAP_CHECKPOINT);
+        if self.metadata().last_offset >= 0 || !path.exists() {
+            return Ok(());
+        }
+        let records = metadata_image::read_checkpoint(&path)?
+            .into_iter()
+            .filter(|record_batch| !record_batch.is_control())
+            .flat_map(|record_batch| record_batch.records)
+            .filter_map(|record| record.value)
+            .collect();
+        self.append_metadata(records)
+    }
+
+    pub fn reload_authorizer(&self) {
+        if let Some(authorizer) = &self.authorizer {
+            authorizer.load(&self.metadata());
+        }
+    }
+
+    pub fn reload_quotas(&self) {
+        self.quotas.load(&self.metadata());
+    }
+
+    pub fn authorize(
+        &self,
+        session: &Session,
+        operation: AclOperation,
+        resource_type: ResourceType,
+        resource_name: &str,
+    ) -> bool {
+        match &self.authorizer {
+            Some(authorizer) => {
+                authorizer.authorize(session, operation, resource_type, resource_name)
+            }
+            None => true,
+        }
+    }
+
+    // Bitfield of the operations the session may perform on the resource
+    pub fn authorized_operations(
+        &self,
+        session: &Session,
+        resource_type: ResourceType,
+        resource_name: &str,
+    ) -> i32 {
+        resource_type
+            .operations()
+            .iter()
+            .filter(|operation| self.authorize(session, **operation, resource_type, resource_name))
+            .fold(0, |operations, operation| {
+                operations | 1 << operation.code()
+            })
+    }
+
+    pub fn scram_credential(
+        &self,
+        username: &str,
+        mechanism: ScramMechanism,
+    ) -> Option<ScramCredential> {
+        let metadata = self.metadata();
+        if metadata.metadata_version() < IBP_3_5_IV2 {
+            return None;
+        }
+        let credential_record = metadata.scram_credential(username, mechanism.code())?;
+        Some(ScramCredential {
+            salt: credential_record.salt.clone(),
+            stored_key: credential_record.stored_key.clone(),
+            server_key: credential_record.server_key.clone(),
+            iterations: credential_record.iterations,
+        })
+    }
+
+    // The current metadata image, which later updates leave untouched
+    pub fn metadata(&self) -> Arc<MetadataImage> {
+        self.metadata.read().unwrap().clone()
+    }
+
+    // Compression a batch written to `topic` gets from the topic's
+    // compression.type, where "producer" keeps `producer`, the batch's own.
+    // Internal topics like __consumer_offsets aren't in the metadata, so
+    // they take the broker's compression.type, as a topic without an
+    // override would.
+    pub fn compression_for(&self, topic: &str, producer: Compression) -> Compression {
+        let keys = ["compression.type".to_string()];
+        let metadata = self.metadata();
+        let node_id = self.config.node_id().to_string();
+        let compression_type =
+            dynamic_config::describe(TOPIC_RESOURCE, topic, Some(&keys), &metadata, &self.config)
+                .or_else(|_| {
+                    dynamic_config::describe(
+                        BROKER_RESOURCE,
+                        &node_id,
+                        Some(&keys),
+                        &metadata,
+                        &self.config,
+                    )
+                })
+                .ok()
+                .and_then(|described| described.into_iter().next()?.value)
+                .unwrap_or_default();
+        Compression::for_topic(&compression_type, producer)
+    }
+
+    // Looks the partition up in whichever log dir holds it. The metadata log
+    // is only written through `metadata_log`, so clients never get to it.
+    pub fn partition_log(&self, topic: &str, partition: i32) -> Option<PartitionLog> {
+        if topic == METADATA_TOPIC {
+            return None;
+        }
+        self.config
+            .log_dirs()
+            .iter()
+            .find_map(|log_dir| PartitionLog::open(log_dir, topic, partition))
+    }
+
+    // Log to append a partition's records to, created in the first log dir
+    // on the first append. None when the partition isn't in the metadata.
+    pub fn partition_log_for_append(
+        &self,
+        topic: &str,
+        partition: i32,
+    ) -> io::Result<Option<PartitionLog>> {
+        let metadata = self.metadata();
+        let known = metadata
+            .topic_id(topic)
+            .is_some_and(|topic_id| metadata.partition(topic_id, partition).is_some());
+        if !known || topic == METADATA_TOPIC {
+            return Ok(None);
+        }
+        if let Some(log) = self.partition_log(topic, partition) {
+            return Ok(Some(log));
+        }
+        PartitionLog::create(&self.config.log_dirs()[0], topic, partition).map(Some)
+    }
+
+    pub fn cluster_id(&self) -> String {
+        MetaProperties::load(&self.config.metadata_log_dir())
+            .ok()
+            .and_then(|meta_properties| meta_properties.cluster_id)
+            .unwrap_or_default()
+    }
+
+    // Leader of the metadata quorum, as recorded in its quorum-state file or
+    // else by the last LeaderChange in the metadata log
+    pub fn controller_id(&self) -> i32 {
+        let quorum_state = self
+            .config
+            .metadata_log_dir()
+            .join(format!("{}-0", METADATA_TOPIC))
+            .join("quorum-state");
+        fs::read_to_string(quorum_state)
+            .ok()
+            .and_then(|contents| json_int_field(&contents, "leaderId"))
+            .unwrap_or_else(|| self.metadata().quorum_leader())
+    }
+
+    // Appends encoded metadata records to the metadata log as a single batch
+    pub fn append_metadata(&self, records: Vec<Vec<u8>>) -> io::Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        let records = records
+            .into_iter()
+            .enumerate()
+            .map(|(offset_delta, value)| Record::new(offset_delta as i32, None, Some(value)))
+            .collect();
+        let mut record_batch = RecordBatch::new(0, -1, -1, now_ms(), records);
+        let metadata_log = self.metadata_log.lock().unwrap();
+        record_batch.base_offset = metadata_log.append(record_batch.clone())?;
+        // Still under the log lock, so batches are applied in offset order
+        self.publish_metadata(&[record_batch]);
+        Ok(())
+    }
+
+    // Publishes a new image with `record_batches` applied on top of the
+    // current one, for callers holding the metadata log lock. Connections
+    // keep reading the image they were handed, so it is never changed in
+    // place: the new one is built on a copy and swapped in whole. Metadata
+    // changes are rare next to reads, so a copy per batch costs less than
+    // having readers lock the image.
+    fn publish_metadata(&self, record_batches: &[RecordBatch]) {
+        let mut image = MetadataImage::clone(&self.metadata());
+        for record_batch in record_batches {
+            image.apply_batch(record_batch);
+        }
+        *self.metadata.write().unwrap() = Arc::new(image);
+    }
+
+    // Applies the complete batches appended to the metadata log since the
+    // current image, by this broker or another process sharing the log dir.
+    // Returns whether a new image was published.
+    pub fn catch_up_metadata(&self) -> io::Result<bool> {
+        let metadata_log = self.metadata_log.lock().unwrap();
+        let last_offset = self.metadata().last_offset;
+        let record_batches = metadata_log.read_batches_from(last_offset + 1)?;
+        if record_batches.is_empty() {
+            return Ok(false);
+        }
+        self.publish_metadata(&record_batches);
+        drop(metadata_log);
+        self.reload_authorizer();
+        self.reload_quotas();
+        Ok(true)
+    }
+
+    // Polls the metadata log dir, catching up whenever its segments change size
+    pub fn tail_metadata(&self) {
+        let mut last_size = None;
+        loop {
+            thread::sleep(METADATA_POLL_INTERVAL);
+            let size = self.metadata_log.lock().unwrap().size();
+            match size {
+                Ok(size) if last_size == Some(size) => continue,
+                Ok(size) => last_size = Some(size),
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            }
+            match self.catch_up_metadata() {
+                // Corrupt batches stay corrupt, so wait for the log to change
+                Err(e) if e.kind() == ErrorKind::InvalidData => println!("Error: {}", e),
+                Err(e) => {
+                    println!("Error: {}", e);
+                    // Retry on the next poll
+                    last_size = None;
+                }
+                Ok(_) => {}
+            }
+        }
+    }
+
+    // Aborts timed out transactions every cleanup interval
+    pub fn expire_transactions(&self) {
+        let interval = Duration::from_millis(self.config.transaction_cleanup_interval_ms());
+        loop {
+            thread::sleep(interval);
+            let aborted = self.transactions.lock().unwrap().abort_timed_out(now_ms());
+            for transactional_id in aborted {
+                println!("Aborted timed out transaction {}", transactional_id);
+            }
+        }
+    }
+
+    // Producer ids come from blocks reserved with a ProducerIdsRecord, so
+    // they aren't handed out again after a restart
+    pub fn init_producer_id(
+        &self,
+        transactional_id: Option<&str>,
+        timeout_ms: i32,
+        producer_id: i64,
+        producer_epoch: i16,
+    ) -> Result<(i64, i16), i16> {
+        let mut reserve_block = |next_producer_id| {
+            // This broker doesn't register itself, so it has no broker epoch
+            let record = ProducerIdsRecord::new(self.config.node_id(), -1, next_producer_id);
+            self.append_metadata(vec![record.encode()])
+        };
+        self.transactions.lock().unwrap().init_producer_id(
+            transactional_id,
+            timeout_ms,
+            producer_id,
+            producer_epoch,
+            &mut reserve_block,
+        )
+    }
+}
+
+// Truncates every partition log after its last valid batch, so a batch left
+// corrupt or half written doesn't block reads and appends. Committed
+// metadata is never thrown away: the metadata log only loses a batch a
+// crash left half written at its end, and fails startup on other damage.
+fn recover_logs(config: &BrokerConfig) -> io::Result<()> {
+    let mut log_dirs = config.log_dirs();
+    log_dirs.push(config.metadata_log_dir());
+    log_dirs.sort();
+    log_dirs.dedup();
+    for log_dir in log_dirs.iter().filter(|log_dir| log_dir.is_dir()) {
+        for (topic, partition) in PartitionLog::list(log_dir)? {
+            let Some(log) = PartitionLog::open(log_dir, &topic, partition) else {
+                continue;
+            };
+            let Some(recovery) = log.recover(topic == METADATA_TOPIC)? else {
+                continue;
+            };
+            println!(
+                "Truncated {} to offset {}, dropping its last {} bytes: {}",
+                recovery.segment.display(),
+                recovery.offset,
+                recovery.truncated_bytes,
+                recovery.error
+            );
+            for segment in recovery.deleted_segments {
+                println!("Deleted {} after the corrupt batch", segment.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Integer field of a flat JSON object, enough for the quorum-state file
+fn json_int_field(contents: &str, field: &str) -> Option<i32> {
+    let key = format!("\"{}\"", field);
+    let value = contents[contents.find(&key)? + key.len()..]
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start();
+    let end = value
+        .find(|c: char| c != '-' && !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+#[test]
+fn test_json_int_field() {
+    let quorum_state =
+        r#"{"clusterId":"","leaderId":1,"leaderEpoch":3,"votedId":-1,"data_version":0}"#;
+    assert_eq!(json_int_field(quorum_state, "leaderId"), Some(1));
+    assert_eq!(json_int_field(quorum_state, "votedId"), Some(-1));
+    assert_eq!(json_int_field(quorum_state, "appliedOffset"), None);
+}
+
+#[test]
+fn test_producer_ids_survive_restart() {
+    use crate::test_util::{broker_config, TempDir};
+    use crate::transaction_coordinator::PRODUCER_ID_BLOCK_SIZE;
+
+    let log_dir = TempDir::new("producer-ids");
+    let config = || broker_config(&log_dir, "");
+    let broker = Broker::new(config()).unwrap();
+    assert_eq!(broker.init_producer_id(None, 0, -1, -1), Ok((0, 0)));
+    assert_eq!(broker.init_producer_id(None, 0, -1, -1), Ok((1, 0)));
+    assert_eq!(broker.metadata().next_producer_id(), PRODUCER_ID_BLOCK_SIZE);
+    drop(broker);
+
+    // The rest of the reserved block is skipped rather than reused
+    let broker = Broker::new(config()).unwrap();
+    assert_eq!(
+        broker.init_producer_id(None, 0, -1, -1),
+        Ok((PRODUCER_ID_BLOCK_SIZE, 0))
+    );
+    assert_eq!(
+        broker.metadata().next_producer_id(),
+        2 * PRODUCER_ID_BLOCK_SIZE
+    );
+}
+
+#[test]
+fn test_corrupt_metadata_batch_is_truncated_on_load() {
+    use crate::test_util::{broker_config, TempDir};
+
+    let log_dir = TempDir::new("recover-metadata");
+    let config = || broker_config(&log_dir, "");
+    let broker = Broker::new(config()).unwrap();
+    broker
+        .append_metadata(vec![b"\x01\x14\x00\x00".to_vec()])
+        .unwrap();
+    let log_end_offset = broker
+        .metadata_log
+        .lock()
+        .unwrap()
+        .log_end_offset()
+        .unwrap();
+    drop(broker);
+
+    // A crash left half a batch at the end of the metadata log
+    let metadata_log = PartitionLog::open(log_dir.path(), METADATA_TOPIC, 0).unwrap();
+    let segment = fs::read_dir(metadata_log.dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|extension| extension == "log"))
+        .unwrap();
+    let mut raw = fs::read(&segment).unwrap();
+    let half = raw[..raw.len() / 2].to_vec();
+    raw.extend_from_slice(&half);
+    fs::write(&segment, &raw).unwrap();
+
+    let broker = Broker::new(config()).unwrap();
+    assert_eq!(broker.metadata().last_offset, log_end_offset - 1);
+    broker
+        .append_metadata(vec![b"\x01\x14\x00\x00".to_vec()])
+        .unwrap();
+    assert!(broker.catch_up_metadata().is_ok());
+    assert_eq!(broker.metadata().last_offset, log_end_offset);
+    drop(broker);
+
+    // Committed metadata that went bad is not discarded
+    let mut raw = fs::read(&segment).unwrap();
+    raw[70] ^= 0xff;
+    fs::write(&segment, &raw).unwrap();
+    assert!(Broker::new(config()).is_err());
+    assert_eq!(fs::read(&segment).unwrap(), raw);
+}
+
+#[test]
+fn test_compression_for_topic() {
+    use crate::cluser_metadata::ConfigRecord;
+    use crate::test_util::broker_with_topics;
+    use crate::transaction_coordinator::CONSUMER_OFFSETS_TOPIC;
+
+    let (_log_dir, broker) = broker_with_topics("compression-for", "", &[]);
+    assert_eq!(
+        broker.compression_for(CONSUMER_OFFSETS_TOPIC, Compression::Lz4),
+        Compression::Lz4
+    );
+
+    // A cluster-wide default, as the offsets topic has no config of its own
+    let record = ConfigRecord::new(BROKER_RESOURCE, "", "compression.type", Some("zstd"));
+    broker.append_metadata(vec![record.encode()]).unwrap();
+    assert_eq!(
+        broker.compression_for(CONSUMER_OFFSETS_TOPIC, Compression::Lz4),
+        Compression::Zstd
+    );
+}
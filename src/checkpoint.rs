@@ -0,0 +1,67 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+const CHECKPOINT_VERSION: &str = "0";
+
+// Checkpoint files hold a version line, an entry count line and then one
+// space-separated entry per line. A missing file has no entries.
+pub fn read(path: &Path) -> io::Result<Vec<Vec<String>>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let malformed = || {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Malformed checkpoint {:?}", path),
+        )
+    };
+
+    let mut lines = contents.lines();
+    if lines.next() != Some(CHECKPOINT_VERSION) {
+        return Err(malformed());
+    }
+    let count: usize = lines
+        .next()
+        .and_then(|count| count.trim().parse().ok())
+        .ok_or_else(malformed)?;
+    let entries: Vec<Vec<String>> = lines
+        .take(count)
+        .map(|line| line.split_whitespace().map(str::to_string).collect())
+        .collect();
+    if entries.len() != count {
+        return Err(malformed());
+    }
+    Ok(entries)
+}
+
+// Writes to a temporary file first so a crash never leaves a torn checkpoint
+pub fn write(path: &Path, entries: &[String]) -> io::Result<()> {
+    let mut contents = format!("{}\n{}\n", CHECKPOINT_VERSION, entries.len());
+    for entry in entries {
+        contents.push_str(entry);
+        contents.push('\n');
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(tmp_path, path)
+}
+
+#[test]
+fn test_write_and_read() {
+    let dir = crate::test_util::TempDir::new("checkpoint");
+    let path = dir.path().join("checkpoint");
+    assert!(read(&path).unwrap().is_empty());
+
+    write(
+        &path,
+        &["events 0 42".to_string(), "events 1 7".to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+        read(&path).unwrap(),
+        vec![vec!["events", "0", "42"], vec!["events", "1", "7"]]
+    );
+}
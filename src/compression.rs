@@ -0,0 +1,226 @@
+use bytes::{Buf, BufMut};
+
+use std::io::{self, ErrorKind, Read, Write};
+
+// Low three bits of a batch's attributes
+pub const COMPRESSION_MASK: u16 = 0x07;
+
+// snappy-java's stream header, which Kafka frames snappy batches with
+const XERIAL_MAGIC: &[u8] = b"\x82SNAPPY\x00";
+const XERIAL_BLOCK_SIZE: usize = 32 * 1024;
+
+// Most a compressed batch's records may decompress to, so a small batch
+// can't expand into gigabytes. Far above what a batch within the default
+// max.message.bytes holds in practice.
+const MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_attributes(attributes: u16) -> Option<Compression> {
+        match attributes & COMPRESSION_MASK {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Gzip),
+            2 => Some(Compression::Snappy),
+            3 => Some(Compression::Lz4),
+            4 => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> u16 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Snappy => 2,
+            Compression::Lz4 => 3,
+            Compression::Zstd => 4,
+        }
+    }
+
+    // Compression of the batches written to a topic with the given
+    // compression.type, where "producer" keeps what the producer sent
+    pub fn for_topic(compression_type: &str, producer: Compression) -> Compression {
+        match compression_type {
+            "uncompressed" => Compression::None,
+            "gzip" => Compression::Gzip,
+            "snappy" => Compression::Snappy,
+            "lz4" => Compression::Lz4,
+            "zstd" => Compression::Zstd,
+            _ => producer,
+        }
+    }
+
+    pub fn compress(self, input: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(input.to_vec()),
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(input)?;
+                encoder.finish()
+            }
+            Compression::Snappy => {
+                let mut output = XERIAL_MAGIC.to_vec();
+                output.put_i32(1); // Version
+                output.put_i32(1); // Minimum compatible version
+                let mut encoder = snap::raw::Encoder::new();
+                for block in input.chunks(XERIAL_BLOCK_SIZE) {
+                    let compressed = encoder.compress_vec(block).map_err(io::Error::other)?;
+                    output.put_i32(compressed.len() as i32);
+                    output.extend_from_slice(&compressed);
+                }
+                Ok(output)
+            }
+            Compression::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(vec![]);
+                encoder.write_all(input)?;
+                encoder.finish().map_err(io::Error::other)
+            }
+            Compression::Zstd => zstd::encode_all(input, zstd::DEFAULT_COMPRESSION_LEVEL),
+        }
+    }
+
+    pub fn decompress(self, input: &[u8]) -> io::Result<Vec<u8>> {
+        self.decompress_within(input, MAX_DECOMPRESSED_SIZE)
+    }
+
+    // Fails once the output would exceed `limit` bytes, before decoding it
+    fn decompress_within(self, input: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(input.to_vec()),
+            Compression::Gzip => read_within(flate2::read::GzDecoder::new(input), limit),
+            Compression::Snappy => snappy_decompress(input, limit),
+            Compression::Lz4 => read_within(lz4_flex::frame::FrameDecoder::new(input), limit),
+            Compression::Zstd => read_within(zstd::stream::read::Decoder::new(input)?, limit),
+        }
+    }
+}
+
+fn too_large(limit: u64) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Records decompress to more than {} bytes", limit),
+    )
+}
+
+fn read_within(decoder: impl Read, limit: u64) -> io::Result<Vec<u8>> {
+    let mut output = vec![];
+    decoder.take(limit + 1).read_to_end(&mut output)?;
+    if output.len() as u64 > limit {
+        return Err(too_large(limit));
+    }
+    Ok(output)
+}
+
+// Kafka writes snappy in snappy-java's framing: its header, then length
+// prefixed blocks. Older clients may send a single raw block. Each block
+// starts with its decompressed length, which is checked before decoding.
+fn snappy_decompress(input: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    let mut decoder = snap::raw::Decoder::new();
+    let mut decompress_block = |block: &[u8], output: &mut Vec<u8>| -> io::Result<()> {
+        let length = snap::raw::decompress_len(block).map_err(io::Error::other)?;
+        if (output.len() + length) as u64 > limit {
+            return Err(too_large(limit));
+        }
+        output.extend(decoder.decompress_vec(block).map_err(io::Error::other)?);
+        Ok(())
+    };
+    let mut output = vec![];
+    let Some(mut blocks) = input.strip_prefix(XERIAL_MAGIC) else {
+        decompress_block(input, &mut output)?;
+        return Ok(output);
+    };
+    let truncated = || io::Error::new(ErrorKind::UnexpectedEof, "Truncated snappy block");
+    if blocks.len() < 8 {
+        return Err(truncated());
+    }
+    blocks.advance(8); // Version and minimum compatible version
+    while !blocks.is_empty() {
+        if blocks.len() < 4 {
+            return Err(truncated());
+        }
+        let length = usize::try_from(blocks.get_i32()).map_err(|_| truncated())?;
+        if length > blocks.len() {
+            return Err(truncated());
+        }
+        let (block, rest) = blocks.split_at(length);
+        decompress_block(block, &mut output)?;
+        blocks = rest;
+    }
+    Ok(output)
+}
+
+#[test]
+fn test_roundtrip() {
+    let input: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+    for compression in [
+        Compression::None,
+        Compression::Gzip,
+        Compression::Snappy,
+        Compression::Lz4,
+        Compression::Zstd,
+    ] {
+        let compressed = compression.compress(&input).unwrap();
+        assert_eq!(compression.decompress(&compressed).unwrap(), input);
+        assert_eq!(
+            Compression::from_attributes(compression.code() | 0x10),
+            Some(compression)
+        );
+    }
+    // Several snappy-java blocks, and a bare block
+    assert!(Compression::Snappy.compress(&input).unwrap().len() > 8 + 4 * 4);
+    let raw = snap::raw::Encoder::new().compress_vec(&input).unwrap();
+    assert_eq!(Compression::Snappy.decompress(&raw).unwrap(), input);
+    assert!(Compression::Gzip.decompress(&input).is_err());
+}
+
+#[test]
+fn test_for_topic() {
+    assert_eq!(
+        Compression::for_topic("producer", Compression::Lz4),
+        Compression::Lz4
+    );
+    assert_eq!(
+        Compression::for_topic("zstd", Compression::Lz4),
+        Compression::Zstd
+    );
+    assert_eq!(
+        Compression::for_topic("uncompressed", Compression::Gzip),
+        Compression::None
+    );
+}
+
+#[test]
+fn test_decompress_limit() {
+    let input = vec![0; 100_000];
+    for compression in [
+        Compression::Gzip,
+        Compression::Snappy,
+        Compression::Lz4,
+        Compression::Zstd,
+    ] {
+        let compressed = compression.compress(&input).unwrap();
+        assert!(compressed.len() < 10_000);
+        assert_eq!(
+            compression
+                .decompress_within(&compressed, 100_000)
+                .unwrap()
+                .len(),
+            100_000
+        );
+        let error = compression
+            .decompress_within(&compressed, 99_999)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData, "{:?}", compression);
+    }
+    let raw = snap::raw::Encoder::new().compress_vec(&input).unwrap();
+    assert!(Compression::Snappy.decompress_within(&raw, 99_999).is_err());
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_LOG_DIR: &str = "/tmp/kraft-combined-logs";
+// Only reachable from this host unless listeners says otherwise
+const DEFAULT_LISTENERS: &str = "PLAINTEXT://127.0.0.1:9092";
+const DEFAULT_PORT: i32 = 9092;
+const DEFAULT_SOCKET_REQUEST_MAX_BYTES: u32 = 100 * 1024 * 1024;
+const DEFAULT_TRANSACTION_CLEANUP_INTERVAL_MS: u64 = 10_000;
+
+// A `NAME://host:port` entry of listeners or advertised.listeners
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    pub listener_name: String,
+    pub host: String,
+    pub port: i32,
+}
+
+fn parse_endpoints(endpoints: &str) -> Vec<Endpoint> {
+    endpoints
+        .split(',')
+        .filter_map(|endpoint| {
+            let (listener_name, address) = endpoint.trim().split_once("://")?;
+            let (host, port) = address.rsplit_once(':')?;
+            Some(Endpoint {
+                listener_name: listener_name.to_string(),
+                host: host
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string(),
+                port: port.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+// Java properties subset: `key=value` lines, `#`/`!` comments
+pub fn parse_properties(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct BrokerConfig {
+    properties: HashMap<String, String>,
+}
+
+impl BrokerConfig {
+    pub fn load(path: &Path) -> io::Result<BrokerConfig> {
+        Ok(BrokerConfig::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn parse(contents: &str) -> BrokerConfig {
+        BrokerConfig {
+            properties: parse_properties(contents),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(String::as_str)
+    }
+
+    // A config with its `listener.name.<listener>.` override applied
+    pub fn listener_get(&self, listener_name: &str, key: &str) -> Option<&str> {
+        self.get(&format!(
+            "listener.name.{}.{}",
+            listener_name.to_lowercase(),
+            key
+        ))
+        .or(self.get(key))
+    }
+
+    pub fn node_id(&self) -> i32 {
+        self.get("node.id")
+            .or(self.get("broker.id"))
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(1)
+    }
+
+    pub fn log_dirs(&self) -> Vec<PathBuf> {
+        let log_dirs = self.get("log.dirs").or(self.get("log.dir"));
+        match log_dirs {
+            Some(log_dirs) => log_dirs
+                .split(',')
+                .map(|dir| PathBuf::from(dir.trim()))
+                .collect(),
+            None => vec![PathBuf::from(DEFAULT_LOG_DIR)],
+        }
+    }
+
+    // Largest request a client may send, socket.request.max.bytes
+    pub fn socket_request_max_bytes(&self) -> u32 {
+        self.get("socket.request.max.bytes")
+            .and_then(|bytes| bytes.parse().ok())
+            .unwrap_or(DEFAULT_SOCKET_REQUEST_MAX_BYTES)
+    }
+
+    // How often ongoing transactions are checked for having timed out,
+    // transaction.abort.timed.out.transaction.cleanup.interval.ms
+    pub fn transaction_cleanup_interval_ms(&self) -> u64 {
+        self.get("transaction.abort.timed.out.transaction.cleanup.interval.ms")
+            .and_then(|interval_ms| interval_ms.parse().ok())
+            .unwrap_or(DEFAULT_TRANSACTION_CLEANUP_INTERVAL_MS)
+    }
+
+    // Directory holding the metadata log and the internal topics
+    pub fn metadata_log_dir(&self) -> PathBuf {
+        match self.get("metadata.log.dir") {
+            Some(dir) => PathBuf::from(dir),
+            None => self.log_dirs().remove(0),
+        }
+    }
+
+    pub fn rack(&self) -> Option<&str> {
+        self.get("broker.rack")
+    }
+
+    // Listeners to accept clients on, leaving out the controller's
+    pub fn listeners(&self) -> Vec<Endpoint> {
+        let controller_listener_names: Vec<&str> = self
+            .get("controller.listener.names")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .collect();
+        parse_endpoints(self.get("listeners").unwrap_or(DEFAULT_LISTENERS))
+            .into_iter()
+            .filter(|listener| {
+                !controller_listener_names.contains(&listener.listener_name.as_str())
+            })
+            .collect()
+    }
+
+    // Security protocol of a listener, which defaults to its own name
+    pub fn security_protocol(&self, listener_name: &str) -> String {
+        self.get("listener.security.protocol.map")
+            .and_then(|map| {
+                map.split(',')
+                    .filter_map(|entry| entry.split_once(':'))
+                    .find(|(name, _)| name.trim() == listener_name)
+            })
+            .map_or(listener_name.to_string(), |(_, protocol)| {
+                protocol.trim().to_string()
+            })
+    }
+
+    // GSSAPI isn't available, so PLAIN is the default instead
+    pub fn sasl_enabled_mechanisms(&self) -> Vec<String> {
+        self.get("sasl.enabled.mechanisms")
+            .unwrap_or("PLAIN")
+            .split(',')
+            .map(|mechanism| mechanism.trim().to_string())
+            .filter(|mechanism| !mechanism.is_empty())
+            .collect()
+    }
+
+    // Host and port clients of a listener should use to reach this broker
+    pub fn advertised_endpoint(&self, listener_name: &str) -> (String, i32) {
+        let endpoint = [self.get("advertised.listeners"), self.get("listeners")]
+            .into_iter()
+            .flatten()
+            .flat_map(parse_endpoints)
+            .find(|endpoint| endpoint.listener_name == listener_name);
+
+        match endpoint {
+            Some(endpoint) if endpoint.host.is_empty() => ("localhost".to_string(), endpoint.port),
+            Some(endpoint) => (endpoint.host, endpoint.port),
+            None => ("localhost".to_string(), DEFAULT_PORT),
+        }
+    }
+}
+
+#[test]
+fn test_parse() {
+    let config = BrokerConfig::parse(
+        "# comment\nnode.id=3\nlisteners=PLAINTEXT://:9092,CONTROLLER://:9093\nlog.dirs=/a, /b\n",
+    );
+    assert_eq!(config.node_id(), 3);
+    assert_eq!(
+        config.log_dirs(),
+        vec![PathBuf::from("/a"), PathBuf::from("/b")]
+    );
+    assert_eq!(
+        config.advertised_endpoint("PLAINTEXT"),
+        ("localhost".to_string(), 9092)
+    );
+    assert_eq!(config.security_protocol("CONTROLLER"), "CONTROLLER");
+
+    let config = BrokerConfig::parse(
+        "listener.security.protocol.map=INTERNAL:PLAINTEXT, EXTERNAL:SASL_PLAINTEXT\n",
+    );
+    assert_eq!(config.security_protocol("EXTERNAL"), "SASL_PLAINTEXT");
+    assert_eq!(config.sasl_enabled_mechanisms(), vec!["PLAIN"]);
+}
+
+#[test]
+fn test_listeners() {
+    let config = BrokerConfig::parse(
+        "listeners=INTERNAL://0.0.0.0:9092,EXTERNAL://:29092,CONTROLLER://[::1]:9093\n\
+         advertised.listeners=INTERNAL://kafka:9092,EXTERNAL://localhost:29092\n\
+         controller.listener.names=CONTROLLER\n",
+    );
+    let endpoint = |listener_name: &str, host: &str, port| Endpoint {
+        listener_name: listener_name.to_string(),
+        host: host.to_string(),
+        port,
+    };
+    assert_eq!(
+        config.listeners(),
+        vec![
+            endpoint("INTERNAL", "0.0.0.0", 9092),
+            endpoint("EXTERNAL", "", 29092)
+        ]
+    );
+    assert_eq!(
+        config.advertised_endpoint("INTERNAL"),
+        ("kafka".to_string(), 9092)
+    );
+    assert_eq!(
+        config.advertised_endpoint("EXTERNAL"),
+        ("localhost".to_string(), 29092)
+    );
+    assert_eq!(
+        BrokerConfig::default().listeners(),
+        vec![endpoint("PLAINTEXT", "127.0.0.1", 9092)]
+    );
+    assert_eq!(
+        parse_endpoints("CONTROLLER://[::1]:9093"),
+        vec![endpoint("CONTROLLER", "::1", 9093)]
+    );
+}
@@ -0,0 +1,287 @@
+use crate::protocol::CheckedBuf;
+use crate::record_batch::{Record, ABORT_MARKER, COMMIT_MARKER};
+
+use std::fmt;
+
+// Control record types of the metadata log, after the transaction markers
+pub const LEADER_CHANGE: i16 = 2;
+pub const SNAPSHOT_HEADER: i16 = 3;
+pub const SNAPSHOT_FOOTER: i16 = 4;
+pub const KRAFT_VERSION: i16 = 5;
+pub const KRAFT_VOTERS: i16 = 6;
+
+// Endpoint a voter listens on for the rest of the quorum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoterEndpoint {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+}
+
+// Member of the metadata quorum as a VotersRecord lists it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Voter {
+    pub voter_id: i32,
+    pub directory_id: [u8; 16],
+    pub endpoints: Vec<VoterEndpoint>,
+    // Range of kraft.version the voter supports
+    pub min_supported_version: i16,
+    pub max_supported_version: i16,
+}
+
+impl fmt::Display for Voter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (directory ", self.voter_id)?;
+        for byte in self.directory_id {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ") at")?;
+        for endpoint in &self.endpoints {
+            write!(
+                f,
+                " {}://{}:{}",
+                endpoint.name, endpoint.host, endpoint.port
+            )?;
+        }
+        write!(
+            f,
+            ", kraft.version {}-{}",
+            self.min_supported_version, self.max_supported_version
+        )
+    }
+}
+
+// Compact array of voter ids, each followed by tagged fields
+fn parse_voter_ids(cursor: &mut &[u8]) -> Option<Vec<i32>> {
+    let voters_length = cursor.checked_get_compact_array_length()?;
+    let mut voter_ids = vec![];
+    for _ in 0..voters_length {
+        voter_ids.push(cursor.checked_get_i32()?);
+        cursor.checked_skip_tagged_fields()?;
+    }
+    Some(voter_ids)
+}
+
+fn parse_voters(cursor: &mut &[u8]) -> Option<Vec<Voter>> {
+    let voters_length = cursor.checked_get_compact_array_length()?;
+    let mut voters = vec![];
+    for _ in 0..voters_length {
+        let voter_id = cursor.checked_get_i32()?;
+        let directory_id = cursor.checked_get_uuid()?;
+        let endpoints_length = cursor.checked_get_compact_array_length()?;
+        let mut endpoints = vec![];
+        for _ in 0..endpoints_length {
+            let name = cursor.checked_get_compact_string()?;
+            let host = cursor.checked_get_compact_string()?;
+            let port = cursor.checked_get_u16()?;
+            cursor.checked_skip_tagged_fields()?;
+            endpoints.push(VoterEndpoint { name, host, port });
+        }
+        let min_supported_version = cursor.checked_get_i16()?;
+        let max_supported_version = cursor.checked_get_i16()?;
+        cursor.checked_skip_tagged_fields()?;
+        cursor.checked_skip_tagged_fields()?;
+        voters.push(Voter {
+            voter_id,
+            directory_id,
+            endpoints,
+            min_supported_version,
+            max_supported_version,
+        });
+    }
+    Some(voters)
+}
+
+// Record of a control batch, which carries transaction markers, or Raft's
+// own records in the metadata log, rather than data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlRecord {
+    Abort { coordinator_epoch: i32 },
+    Commit { coordinator_epoch: i32 },
+    // The new leader of the metadata quorum and the voters at the time
+    LeaderChange { leader_id: i32, voters: Vec<i32> },
+    SnapshotHeader { last_contained_log_timestamp: i64 },
+    SnapshotFooter,
+    KRaftVersion { kraft_version: i16 },
+    Voters { voters: Vec<Voter> },
+    Unknown,
+}
+
+impl ControlRecord {
+    // None when the key or value is cut short or otherwise malformed
+    pub fn parse(record: &Record) -> Option<ControlRecord> {
+        let mut key = record.key.as_deref()?;
+        let mut value = record.value.as_deref()?;
+        let _key_version = key.checked_get_i16()?;
+        let record_type = key.checked_get_i16()?;
+        let _version = value.checked_get_i16()?;
+        let cursor = &mut value;
+
+        let control_record = match record_type {
+            ABORT_MARKER => {
+                let coordinator_epoch = cursor.checked_get_i32()?;
+                return Some(ControlRecord::Abort { coordinator_epoch });
+            }
+            COMMIT_MARKER => {
+                let coordinator_epoch = cursor.checked_get_i32()?;
+                return Some(ControlRecord::Commit { coordinator_epoch });
+            }
+            LEADER_CHANGE => {
+                let leader_id = cursor.checked_get_i32()?;
+                let voters = parse_voter_ids(cursor)?;
+                let _granting_voters = parse_voter_ids(cursor)?;
+                ControlRecord::LeaderChange { leader_id, voters }
+            }
+            SNAPSHOT_HEADER => ControlRecord::SnapshotHeader {
+                last_contained_log_timestamp: cursor.checked_get_i64()?,
+            },
+            SNAPSHOT_FOOTER => ControlRecord::SnapshotFooter,
+            KRAFT_VERSION => ControlRecord::KRaftVersion {
+                kraft_version: cursor.checked_get_i16()?,
+            },
+            KRAFT_VOTERS => ControlRecord::Voters {
+                voters: parse_voters(cursor)?,
+            },
+            _ => return Some(ControlRecord::Unknown),
+        };
+        // Raft's records end with tagged fields, the markers don't
+        cursor.checked_skip_tagged_fields()?;
+        Some(control_record)
+    }
+}
+
+#[test]
+fn test_parse_control_records() {
+    use crate::protocol::CompactBufMut;
+    use crate::record_batch::{RecordBatch, CONTROL_FLAG};
+    use bytes::BufMut;
+
+    let record = |offset_delta, record_type: i16, value: Vec<u8>| {
+        let mut key = vec![];
+        key.put_i16(0);
+        key.put_i16(record_type);
+        Record::new(offset_delta, Some(key), Some(value))
+    };
+    let mut header = vec![];
+    header.put_i16(0);
+    header.put_i64(1_700_000_000_000);
+    header.put_empty_tagged_fields();
+    let mut leader_change = vec![];
+    leader_change.put_i16(0);
+    leader_change.put_i32(1);
+    for _ in 0..2 {
+        leader_change.put_compact_array_length(1);
+        leader_change.put_i32(1);
+        leader_change.put_empty_tagged_fields();
+    }
+    leader_change.put_empty_tagged_fields();
+    let mut voters = vec![];
+    voters.put_i16(0);
+    voters.put_compact_array_length(1);
+    voters.put_i32(1);
+    voters.put_slice(&[7; 16]);
+    voters.put_compact_array_length(1);
+    voters.put_compact_string("CONTROLLER");
+    voters.put_compact_string("localhost");
+    voters.put_u16(9093);
+    voters.put_empty_tagged_fields();
+    voters.put_i16(0);
+    voters.put_i16(1);
+    voters.put_empty_tagged_fields();
+    voters.put_empty_tagged_fields();
+    voters.put_empty_tagged_fields();
+    let mut kraft_version = vec![];
+    kraft_version.put_i16(0);
+    kraft_version.put_i16(1);
+    kraft_version.put_empty_tagged_fields();
+    let mut abort = vec![];
+    abort.put_i16(0);
+    abort.put_i32(3);
+
+    let batch = RecordBatch::new(
+        CONTROL_FLAG,
+        -1,
+        -1,
+        0,
+        vec![
+            record(0, SNAPSHOT_HEADER, header),
+            record(1, LEADER_CHANGE, leader_change),
+            record(2, KRAFT_VOTERS, voters),
+            record(3, KRAFT_VERSION, kraft_version),
+            record(4, SNAPSHOT_FOOTER, vec![0, 0, 0]),
+            record(5, ABORT_MARKER, abort),
+            record(6, 9, vec![0, 0]),
+        ],
+    );
+    let (parsed, _) = RecordBatch::parse(&batch.encode()).unwrap();
+    let voter = Voter {
+        voter_id: 1,
+        directory_id: [7; 16],
+        endpoints: vec![VoterEndpoint {
+            name: "CONTROLLER".to_string(),
+            host: "localhost".to_string(),
+            port: 9093,
+        }],
+        min_supported_version: 0,
+        max_supported_version: 1,
+    };
+    assert_eq!(
+        voter.to_string(),
+        format!(
+            "1 (directory {}) at CONTROLLER://localhost:9093, kraft.version 0-1",
+            "07".repeat(16)
+        )
+    );
+    assert_eq!(
+        parsed.control_records(),
+        vec![
+            ControlRecord::SnapshotHeader {
+                last_contained_log_timestamp: 1_700_000_000_000
+            },
+            ControlRecord::LeaderChange {
+                leader_id: 1,
+                voters: vec![1]
+            },
+            ControlRecord::Voters {
+                voters: vec![voter]
+            },
+            ControlRecord::KRaftVersion { kraft_version: 1 },
+            ControlRecord::SnapshotFooter,
+            ControlRecord::Abort {
+                coordinator_epoch: 3
+            },
+            ControlRecord::Unknown,
+        ]
+    );
+    assert_eq!(parsed.control_marker(), None);
+}
+
+#[test]
+fn test_malformed_control_records() {
+    use bytes::BufMut;
+
+    let record = |record_type: i16, value: &[u8]| {
+        let mut key = vec![];
+        key.put_i16(0);
+        key.put_i16(record_type);
+        Record::new(0, Some(key), Some(value.to_vec()))
+    };
+    // A COMMIT marker with its version but no coordinator epoch
+    assert_eq!(ControlRecord::parse(&record(COMMIT_MARKER, &[0, 0])), None);
+    assert_eq!(
+        ControlRecord::parse(&record(COMMIT_MARKER, &[0, 0, 0, 0, 0, 5])),
+        Some(ControlRecord::Commit {
+            coordinator_epoch: 5
+        })
+    );
+    // Voters claiming one voter, cut short in its directory id
+    assert_eq!(
+        ControlRecord::parse(&record(KRAFT_VOTERS, &[0, 0, 2, 0, 0, 0, 1, 7, 7])),
+        None
+    );
+    assert_eq!(ControlRecord::parse(&record(SNAPSHOT_HEADER, &[0])), None);
+    assert_eq!(
+        ControlRecord::parse(&Record::new(0, Some(vec![0, 0, 0]), Some(vec![0, 0]))),
+        None
+    );
+}
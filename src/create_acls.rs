@@ -0,0 +1,138 @@
+use crate::acl::{AclBinding, AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::cluser_metadata::{random_uuid, AccessControlEntryRecord};
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+use std::io;
+
+// Stores the new bindings, skipping the ones that already exist
+fn create_acls(broker: &Broker, bindings: &[&AclBinding]) -> io::Result<()> {
+    let authorizer = match &broker.authorizer {
+        Some(authorizer) => authorizer,
+        None => return Ok(()),
+    };
+    // Until the records are applied another request could add the same binding
+    let _acl_changes = broker.acl_changes.lock().unwrap();
+    let mut created: Vec<&AclBinding> = vec![];
+    let mut records = vec![];
+    for binding in bindings {
+        if created.contains(binding) || !authorizer.acls(&binding.to_filter()).is_empty() {
+            continue;
+        }
+        records.push(AccessControlEntryRecord::new(random_uuid()?, binding).encode());
+        created.push(binding);
+    }
+    broker.append_metadata(records)?;
+    broker.reload_authorizer();
+    Ok(())
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let creations_length = input.get_compact_array_length();
+    let creations: Vec<Result<AclBinding, String>> = (0..creations_length)
+        .map(|_| {
+            let resource_type = input.get_i8();
+            let resource_name = input.get_compact_string();
+            let pattern_type = input.get_i8();
+            let principal = input.get_compact_string();
+            let host = input.get_compact_string();
+            let operation = input.get_i8();
+            let permission_type = input.get_i8();
+            input.skip_tagged_fields();
+            let binding = AclBinding::from_codes(
+                resource_type,
+                resource_name,
+                pattern_type,
+                principal,
+                host,
+                operation,
+                permission_type,
+            )
+            .ok_or_else(|| "Unknown acl binding code".to_string())?;
+            binding.validate()?;
+            Ok(binding)
+        })
+        .collect();
+    input.skip_tagged_fields();
+
+    let error = if broker.authorizer.is_none() {
+        Some((
+            error_code::SECURITY_DISABLED,
+            "No Authorizer is configured".to_string(),
+        ))
+    } else if !broker.authorize(
+        session,
+        AclOperation::Alter,
+        ResourceType::Cluster,
+        CLUSTER_NAME,
+    ) {
+        Some((
+            error_code::CLUSTER_AUTHORIZATION_FAILED,
+            "Cluster authorization failed".to_string(),
+        ))
+    } else {
+        let bindings: Vec<&AclBinding> = creations.iter().flatten().collect();
+        create_acls(broker, &bindings)
+            .err()
+            .map(|e| (error_code::KAFKA_STORAGE_ERROR, e.to_string()))
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(creations.len());
+    for creation in creations {
+        let (error_code, error_message) = match (&error, creation) {
+            (Some((error_code, error_message)), _) => (*error_code, Some(error_message.clone())),
+            (None, Ok(_)) => (error_code::NONE, None),
+            (None, Err(error_message)) => (error_code::INVALID_REQUEST, Some(error_message)),
+        };
+        body.put_i16(error_code);
+        body.put_compact_nullable_string(error_message.as_deref());
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_concurrent_creates_store_one_binding() {
+    use crate::test_util::broker_with_topics;
+    use std::sync::Barrier;
+    use std::thread;
+
+    let (_log_dir, broker) = broker_with_topics(
+        "create-acls-concurrent",
+        "authorizer.class.name=org.apache.kafka.metadata.authorizer.StandardAuthorizer\n",
+        &[],
+    );
+    let binding = AclBinding::from_codes(
+        2,
+        "orders".to_string(),
+        3,
+        "User:alice".to_string(),
+        "*".to_string(),
+        3,
+        3,
+    )
+    .unwrap();
+
+    let start = Barrier::new(8);
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                start.wait();
+                create_acls(&broker, &[&binding]).unwrap()
+            });
+        }
+    });
+    let authorizer = broker.authorizer.as_ref().unwrap();
+    assert_eq!(authorizer.acls(&binding.to_filter()).len(), 1);
+}
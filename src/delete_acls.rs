@@ -0,0 +1,135 @@
+use crate::acl::{AclBinding, AclBindingFilter, AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::cluser_metadata::RemoveAccessControlEntryRecord;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+use std::io;
+
+type FilterResult = Result<Vec<AclBinding>, (i16, String)>;
+
+// Removes every binding matching one of the filters, returning the matches per filter
+fn delete_acls(
+    broker: &Broker,
+    filters: &[Option<AclBindingFilter>],
+) -> io::Result<Vec<FilterResult>> {
+    let authorizer = match &broker.authorizer {
+        Some(authorizer) => authorizer,
+        None => return Ok(vec![]),
+    };
+    let _acl_changes = broker.acl_changes.lock().unwrap();
+    let mut deleted: Vec<[u8; 16]> = vec![];
+    let results = filters
+        .iter()
+        .map(|filter| {
+            let filter = filter.as_ref().ok_or_else(|| {
+                (
+                    error_code::INVALID_REQUEST,
+                    "Unknown acl filter code".to_string(),
+                )
+            })?;
+            let matching = authorizer.acls(filter);
+            deleted.extend(matching.iter().map(|(id, _)| *id));
+            Ok(matching.into_iter().map(|(_, acl)| acl).collect())
+        })
+        .collect();
+
+    deleted.sort();
+    deleted.dedup();
+    let records = deleted
+        .into_iter()
+        .map(|id| RemoveAccessControlEntryRecord::new(id).encode())
+        .collect();
+    broker.append_metadata(records)?;
+    broker.reload_authorizer();
+    Ok(results)
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let filters_length = input.get_compact_array_length();
+    let filters: Vec<Option<AclBindingFilter>> = (0..filters_length)
+        .map(|_| {
+            let resource_type = input.get_i8();
+            let resource_name = input.get_compact_nullable_string();
+            let pattern_type = input.get_i8();
+            let principal = input.get_compact_nullable_string();
+            let host = input.get_compact_nullable_string();
+            let operation = input.get_i8();
+            let permission_type = input.get_i8();
+            input.skip_tagged_fields();
+            AclBindingFilter::from_codes(
+                resource_type,
+                resource_name,
+                pattern_type,
+                principal,
+                host,
+                operation,
+                permission_type,
+            )
+        })
+        .collect();
+    input.skip_tagged_fields();
+
+    let error = if broker.authorizer.is_none() {
+        Some((
+            error_code::SECURITY_DISABLED,
+            "No Authorizer is configured".to_string(),
+        ))
+    } else if !broker.authorize(
+        session,
+        AclOperation::Alter,
+        ResourceType::Cluster,
+        CLUSTER_NAME,
+    ) {
+        Some((
+            error_code::CLUSTER_AUTHORIZATION_FAILED,
+            "Cluster authorization failed".to_string(),
+        ))
+    } else {
+        None
+    };
+    let results: Vec<FilterResult> = match error {
+        Some(error) => filters.iter().map(|_| Err(error.clone())).collect(),
+        None => delete_acls(broker, &filters).unwrap_or_else(|e| {
+            filters
+                .iter()
+                .map(|_| Err((error_code::KAFKA_STORAGE_ERROR, e.to_string())))
+                .collect()
+        }),
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(results.len());
+    for result in results {
+        let (error_code, error_message, matching_acls) = match result {
+            Ok(matching_acls) => (error_code::NONE, None, matching_acls),
+            Err((error_code, error_message)) => (error_code, Some(error_message), vec![]),
+        };
+        body.put_i16(error_code);
+        body.put_compact_nullable_string(error_message.as_deref());
+        body.put_compact_array_length(matching_acls.len());
+        for acl in matching_acls {
+            body.put_i16(error_code::NONE);
+            body.put_compact_nullable_string(None);
+            body.put_i8(acl.resource_type.code());
+            body.put_compact_string(&acl.resource_name);
+            body.put_i8(acl.pattern_type.code());
+            body.put_compact_string(&acl.principal);
+            body.put_compact_string(&acl.host);
+            body.put_i8(acl.operation.code());
+            body.put_i8(acl.permission_type.code());
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
@@ -0,0 +1,114 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+// Deleting up to this offset means deleting up to the high watermark
+const HIGH_WATERMARK: i64 = -1;
+
+#[derive(Debug)]
+struct DeleteRecordsTopic {
+    name: String,
+    partitions: Vec<(i32, i64)>,
+}
+
+fn delete_records(
+    broker: &Broker,
+    session: &Session,
+    topic: &str,
+    partition: i32,
+    offset: i64,
+) -> Result<i64, i16> {
+    if !broker.authorize(session, AclOperation::Delete, ResourceType::Topic, topic) {
+        return Err(error_code::TOPIC_AUTHORIZATION_FAILED);
+    }
+    let log = broker
+        .partition_log(topic, partition)
+        .ok_or(error_code::UNKNOWN_TOPIC_OR_PARTITION)?;
+    let storage_error = |_| error_code::KAFKA_STORAGE_ERROR;
+
+    // Without replication the high watermark is the log end offset
+    let high_watermark = log.log_end_offset().map_err(storage_error)?;
+    let offset = if offset == HIGH_WATERMARK {
+        high_watermark
+    } else {
+        offset
+    };
+    if offset < 0 || offset > high_watermark {
+        return Err(error_code::OFFSET_OUT_OF_RANGE);
+    }
+
+    log.delete_records_before(offset).map_err(storage_error)
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let topics_length = input.get_compact_array_length();
+    let topics: Vec<DeleteRecordsTopic> = (0..topics_length)
+        .map(|_| {
+            let name = input.get_compact_string();
+            let partitions_length = input.get_compact_array_length();
+            let partitions = (0..partitions_length)
+                .map(|_| {
+                    let partition_index = input.get_i32();
+                    let offset = input.get_i64();
+                    input.skip_tagged_fields();
+                    (partition_index, offset)
+                })
+                .collect();
+            input.skip_tagged_fields();
+            DeleteRecordsTopic { name, partitions }
+        })
+        .collect();
+    let _timeout_ms = input.get_i32();
+    input.skip_tagged_fields();
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(topics.len());
+    for topic in topics {
+        body.put_compact_string(&topic.name);
+        body.put_compact_array_length(topic.partitions.len());
+        for (partition_index, offset) in topic.partitions {
+            let result = delete_records(broker, session, &topic.name, partition_index, offset);
+            body.put_i32(partition_index);
+            body.put_i64(*result.as_ref().unwrap_or(&-1)); // Low watermark
+            body.put_i16(result.err().unwrap_or(error_code::NONE));
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_metadata_log_is_not_deletable() {
+    use crate::cluser_metadata::METADATA_TOPIC;
+    use crate::partition_log::PartitionLog;
+    use crate::test_util::{broker_with_topics, data_batch};
+
+    let (log_dir, broker) =
+        broker_with_topics("delete-records-metadata", "", &[("events", [1; 16], 1)]);
+    let events = PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+    events.append(data_batch()).unwrap();
+    let session = Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false);
+
+    assert_eq!(
+        delete_records(&broker, &session, METADATA_TOPIC, 0, HIGH_WATERMARK),
+        Err(error_code::UNKNOWN_TOPIC_OR_PARTITION)
+    );
+    let metadata_log = PartitionLog::open(log_dir.path(), METADATA_TOPIC, 0).unwrap();
+    assert_eq!(metadata_log.log_start_offset().unwrap(), 0);
+    assert_eq!(
+        delete_records(&broker, &session, "events", 0, HIGH_WATERMARK),
+        Ok(1)
+    );
+}
@@ -0,0 +1,96 @@
+use crate::acl::{AclBinding, AclBindingFilter, AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+use std::collections::BTreeMap;
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let resource_type = input.get_i8();
+    let resource_name = input.get_compact_nullable_string();
+    let pattern_type = input.get_i8();
+    let principal = input.get_compact_nullable_string();
+    let host = input.get_compact_nullable_string();
+    let operation = input.get_i8();
+    let permission_type = input.get_i8();
+    input.skip_tagged_fields();
+    let filter = AclBindingFilter::from_codes(
+        resource_type,
+        resource_name,
+        pattern_type,
+        principal,
+        host,
+        operation,
+        permission_type,
+    );
+
+    let result = match (&broker.authorizer, filter) {
+        (None, _) => Err((
+            error_code::SECURITY_DISABLED,
+            "No Authorizer is configured".to_string(),
+        )),
+        _ if !broker.authorize(
+            session,
+            AclOperation::Describe,
+            ResourceType::Cluster,
+            CLUSTER_NAME,
+        ) =>
+        {
+            Err((
+                error_code::CLUSTER_AUTHORIZATION_FAILED,
+                "Cluster authorization failed".to_string(),
+            ))
+        }
+        (_, None) => Err((
+            error_code::INVALID_REQUEST,
+            "Unknown acl filter code".to_string(),
+        )),
+        (Some(authorizer), Some(filter)) => Ok(authorizer.acls(&filter)),
+    };
+    let (error_code, error_message, acls) = match result {
+        Ok(acls) => (error_code::NONE, None, acls),
+        Err((error_code, error_message)) => (error_code, Some(error_message), vec![]),
+    };
+
+    // Bindings are reported grouped by the resource pattern they apply to
+    let mut resources: BTreeMap<(i8, String, i8), Vec<AclBinding>> = BTreeMap::new();
+    for (_, acl) in acls {
+        resources
+            .entry((
+                acl.resource_type.code(),
+                acl.resource_name.clone(),
+                acl.pattern_type.code(),
+            ))
+            .or_default()
+            .push(acl);
+    }
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_i16(error_code);
+    body.put_compact_nullable_string(error_message.as_deref());
+    body.put_compact_array_length(resources.len());
+    for ((resource_type, resource_name, pattern_type), acls) in resources {
+        body.put_i8(resource_type);
+        body.put_compact_string(&resource_name);
+        body.put_i8(pattern_type);
+        body.put_compact_array_length(acls.len());
+        for acl in acls {
+            body.put_compact_string(&acl.principal);
+            body.put_compact_string(&acl.host);
+            body.put_i8(acl.operation.code());
+            body.put_i8(acl.permission_type.code());
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
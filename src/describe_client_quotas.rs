@@ -0,0 +1,142 @@
+use crate::acl::{AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::quota::{self, QuotaEntity};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+use std::collections::BTreeMap;
+
+const MATCH_EXACT: i8 = 0;
+const MATCH_DEFAULT: i8 = 1;
+// Any entity of the type, including the default one
+const MATCH_SPECIFIED: i8 = 2;
+
+#[derive(Debug)]
+struct ComponentFilter {
+    entity_type: String,
+    match_type: i8,
+    name: Option<String>,
+}
+
+impl ComponentFilter {
+    fn matches(&self, entity: &QuotaEntity) -> bool {
+        match (self.match_type, entity.get(&self.entity_type)) {
+            (MATCH_EXACT, Some(name)) => *name == self.name,
+            (MATCH_DEFAULT, Some(name)) => name.is_none(),
+            (MATCH_SPECIFIED, Some(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+type QuotaEntries = BTreeMap<QuotaEntity, BTreeMap<String, f64>>;
+
+fn describe(
+    components: &[ComponentFilter],
+    strict: bool,
+    broker: &Broker,
+    session: &Session,
+) -> Result<QuotaEntries, (i16, String)> {
+    if !broker.authorize(
+        session,
+        AclOperation::DescribeConfigs,
+        ResourceType::Cluster,
+        CLUSTER_NAME,
+    ) {
+        return Err((
+            error_code::CLUSTER_AUTHORIZATION_FAILED,
+            "Cluster authorization failed".to_string(),
+        ));
+    }
+    for (i, component) in components.iter().enumerate() {
+        if ![quota::USER, quota::CLIENT_ID, quota::IP].contains(&component.entity_type.as_str()) {
+            return Err((
+                error_code::INVALID_REQUEST,
+                format!("Custom entity type {} not supported", component.entity_type),
+            ));
+        }
+        if ![MATCH_EXACT, MATCH_DEFAULT, MATCH_SPECIFIED].contains(&component.match_type) {
+            return Err((
+                error_code::INVALID_REQUEST,
+                format!("Unknown match type {}", component.match_type),
+            ));
+        }
+        if components[..i]
+            .iter()
+            .any(|previous| previous.entity_type == component.entity_type)
+        {
+            return Err((
+                error_code::INVALID_REQUEST,
+                format!("Duplicate {} entity type in filter", component.entity_type),
+            ));
+        }
+    }
+
+    Ok(broker
+        .quotas
+        .describe()
+        .into_iter()
+        .filter(|(entity, _)| !strict || entity.len() == components.len())
+        .filter(|(entity, _)| components.iter().all(|component| component.matches(entity)))
+        .collect())
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let components_length = input.get_compact_array_length();
+    let components: Vec<ComponentFilter> = (0..components_length)
+        .map(|_| {
+            let entity_type = input.get_compact_string();
+            let match_type = input.get_i8();
+            let name = input.get_compact_nullable_string();
+            input.skip_tagged_fields();
+            ComponentFilter {
+                entity_type,
+                match_type,
+                name,
+            }
+        })
+        .collect();
+    let strict = input.get_u8() != 0;
+    input.skip_tagged_fields();
+
+    let (error_code, error_message, entries) = match describe(&components, strict, broker, session)
+    {
+        Ok(entries) => (error_code::NONE, None, Some(entries)),
+        Err((error_code, error_message)) => (error_code, Some(error_message), None),
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_i16(error_code);
+    body.put_compact_nullable_string(error_message.as_deref());
+    match entries {
+        Some(entries) => {
+            body.put_compact_array_length(entries.len());
+            for (entity, values) in entries {
+                body.put_compact_array_length(entity.len());
+                for (entity_type, entity_name) in entity {
+                    body.put_compact_string(&entity_type);
+                    body.put_compact_nullable_string(entity_name.as_deref());
+                    body.put_empty_tagged_fields();
+                }
+                body.put_compact_array_length(values.len());
+                for (key, value) in values {
+                    body.put_compact_string(&key);
+                    body.put_f64(value);
+                    body.put_empty_tagged_fields();
+                }
+                body.put_empty_tagged_fields();
+            }
+        }
+        None => body.put_u8(0), // Null entries
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
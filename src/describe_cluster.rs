@@ -0,0 +1,133 @@
+use crate::acl::{ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+const BROKER_ENDPOINT_TYPE: i8 = 1;
+
+const OPERATIONS_NOT_REQUESTED: i32 = i32::MIN;
+
+#[derive(Debug)]
+pub struct DescribeClusterBroker {
+    pub broker_id: i32,
+    pub host: String,
+    pub port: i32,
+    pub rack: Option<String>,
+}
+
+// Unfenced brokers as reached through the listener the client connected to
+pub fn describe_brokers(broker: &Broker, listener_name: &str) -> Vec<DescribeClusterBroker> {
+    let metadata = broker.metadata();
+    let mut brokers: Vec<DescribeClusterBroker> = metadata
+        .brokers()
+        .values()
+        .filter(|register_record| !register_record.fenced)
+        .filter_map(|register_record| {
+            let endpoint = register_record
+                .endpoints
+                .iter()
+                .find(|endpoint| endpoint.name == listener_name)?;
+            Some(DescribeClusterBroker {
+                broker_id: register_record.broker_id,
+                host: endpoint.host.clone(),
+                port: endpoint.port as i32,
+                rack: register_record.rack.clone(),
+            })
+        })
+        .collect();
+
+    // This broker may not have registered in the metadata log yet
+    let node_id = broker.config.node_id();
+    if !metadata.brokers().contains_key(&node_id) {
+        let (host, port) = broker.config.advertised_endpoint(listener_name);
+        brokers.push(DescribeClusterBroker {
+            broker_id: node_id,
+            host,
+            port,
+            rack: broker.config.rack().map(str::to_string),
+        });
+        brokers.sort_by_key(|described| described.broker_id);
+    }
+
+    brokers
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let include_cluster_authorized_operations = input.get_u8() != 0;
+    let endpoint_type = if header.api_version >= 1 {
+        input.get_i8()
+    } else {
+        BROKER_ENDPOINT_TYPE
+    };
+    input.skip_tagged_fields();
+
+    let result = if endpoint_type != BROKER_ENDPOINT_TYPE {
+        Err((
+            error_code::MISMATCHED_ENDPOINT_TYPE,
+            "The request was sent to a broker endpoint".to_string(),
+        ))
+    } else {
+        Ok(describe_brokers(broker, &session.listener_name))
+    };
+    let (brokers, error_code, error_message) = match result {
+        Ok(brokers) => (brokers, error_code::NONE, None),
+        Err((error_code, error_message)) => (vec![], error_code, Some(error_message)),
+    };
+    let cluster_authorized_operations = if include_cluster_authorized_operations {
+        broker.authorized_operations(session, ResourceType::Cluster, CLUSTER_NAME)
+    } else {
+        OPERATIONS_NOT_REQUESTED
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_i16(error_code);
+    body.put_compact_nullable_string(error_message.as_deref());
+    if header.api_version >= 1 {
+        body.put_i8(endpoint_type);
+    }
+    body.put_compact_string(&broker.cluster_id());
+    body.put_i32(broker.controller_id());
+    body.put_compact_array_length(brokers.len());
+    for described in brokers {
+        body.put_i32(described.broker_id);
+        body.put_compact_string(&described.host);
+        body.put_i32(described.port);
+        body.put_compact_nullable_string(described.rack.as_deref());
+        body.put_empty_tagged_fields();
+    }
+    body.put_i32(cluster_authorized_operations);
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_fenced_brokers_are_left_out() {
+    use crate::test_util::{broker_with_topics, fence_broker_record, register_broker_record};
+
+    let (_log_dir, broker) = broker_with_topics("describe-cluster-fenced", "", &[]);
+    let register = |broker_id| register_broker_record(broker_id, "PLAINTEXT");
+    broker
+        .append_metadata(vec![register(1), register(2), register(3)])
+        .unwrap();
+    let broker_ids = |broker: &Broker| {
+        describe_brokers(broker, "PLAINTEXT")
+            .iter()
+            .map(|described| described.broker_id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(broker_ids(&broker), vec![1, 2, 3]);
+
+    broker
+        .append_metadata(vec![fence_broker_record(2)])
+        .unwrap();
+    assert_eq!(broker_ids(&broker), vec![1, 3]);
+}
@@ -0,0 +1,102 @@
+use crate::acl::AclOperation;
+use crate::broker::Broker;
+use crate::dynamic_config;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+#[derive(Debug)]
+struct DescribeConfigsResource {
+    resource_type: i8,
+    resource_name: String,
+    configuration_keys: Option<Vec<String>>,
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let resources_length = input.get_compact_array_length();
+    let resources: Vec<DescribeConfigsResource> = (0..resources_length)
+        .map(|_| {
+            let resource_type = input.get_i8();
+            let resource_name = input.get_compact_string();
+            let configuration_keys = input
+                .get_compact_nullable_array_length()
+                .map(|length| (0..length).map(|_| input.get_compact_string()).collect());
+            input.skip_tagged_fields();
+            DescribeConfigsResource {
+                resource_type,
+                resource_name,
+                configuration_keys,
+            }
+        })
+        .collect();
+    let include_synonyms = input.get_u8() != 0;
+    let include_documentation = input.get_u8() != 0;
+    input.skip_tagged_fields();
+
+    let metadata = broker.metadata();
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(resources.len());
+    for resource in resources {
+        let result = dynamic_config::authorize(
+            broker,
+            session,
+            AclOperation::DescribeConfigs,
+            resource.resource_type,
+            &resource.resource_name,
+        )
+        .and_then(|()| {
+            dynamic_config::describe(
+                resource.resource_type,
+                &resource.resource_name,
+                resource.configuration_keys.as_deref(),
+                &metadata,
+                &broker.config,
+            )
+        });
+        let (configs, error_code, error_message) = match result {
+            Ok(configs) => (configs, error_code::NONE, None),
+            Err((error_code, error_message)) => (vec![], error_code, Some(error_message)),
+        };
+
+        body.put_i16(error_code);
+        body.put_compact_nullable_string(error_message.as_deref());
+        body.put_i8(resource.resource_type);
+        body.put_compact_string(&resource.resource_name);
+        body.put_compact_array_length(configs.len());
+        for config in configs {
+            body.put_compact_string(&config.name);
+            body.put_compact_nullable_string(config.value.as_deref());
+            body.put_u8(config.read_only as u8);
+            body.put_i8(config.source.code());
+            body.put_u8(0); // Is sensitive
+            let synonyms = if include_synonyms {
+                config.synonyms
+            } else {
+                vec![]
+            };
+            body.put_compact_array_length(synonyms.len());
+            for synonym in synonyms {
+                body.put_compact_string(&synonym.name);
+                body.put_compact_nullable_string(synonym.value.as_deref());
+                body.put_i8(synonym.source.code());
+                body.put_empty_tagged_fields();
+            }
+            body.put_i8(config.config_type.code());
+            let documentation = include_documentation.then_some(config.documentation);
+            body.put_compact_nullable_string(documentation);
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
@@ -0,0 +1,208 @@
+use crate::acl::{AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::cluser_metadata::METADATA_TOPIC;
+use crate::error_code;
+use crate::feature::IBP_3_7_IV2;
+use crate::meta_properties::MetaProperties;
+use crate::metadata_image::MetadataImage;
+use crate::partition_log::PartitionLog;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+const UNKNOWN_VOLUME_BYTES: i64 = -1;
+
+#[derive(Debug)]
+struct DescribableLogDirTopic {
+    topic: String,
+    partitions: Vec<i32>,
+}
+
+#[derive(Debug)]
+struct DescribeLogDirsResult {
+    error_code: i16,
+    log_dir: String,
+    // Topic name to (partition, size) pairs
+    topics: BTreeMap<String, Vec<(i32, i64)>>,
+    total_bytes: i64,
+    usable_bytes: i64,
+}
+
+// Total and usable bytes of the volume holding `path`
+fn volume_bytes(path: &Path) -> io::Result<(i64, i64)> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let block_size = stat.f_frsize as i64;
+    Ok((
+        stat.f_blocks as i64 * block_size,
+        stat.f_bavail as i64 * block_size,
+    ))
+}
+
+// Unassigned, lost and migrating replicas use reserved ids below 100
+fn is_reserved_directory(directory: &[u8; 16]) -> bool {
+    directory[..15].iter().all(|byte| *byte == 0) && directory[15] < 100
+}
+
+fn describe_log_dir(
+    broker: &Broker,
+    log_dir: &Path,
+    metadata: &MetadataImage,
+    requested: Option<&[DescribableLogDirTopic]>,
+) -> io::Result<BTreeMap<String, Vec<(i32, i64)>>> {
+    let directory_id = MetaProperties::load(log_dir)
+        .ok()
+        .and_then(|meta_properties| meta_properties.directory_id);
+    let assignments = if metadata.metadata_version() >= IBP_3_7_IV2 {
+        metadata.directory_assignments(broker.config.node_id())
+    } else {
+        HashMap::new()
+    };
+
+    let mut topics: BTreeMap<String, Vec<(i32, i64)>> = BTreeMap::new();
+    for (topic, partition) in PartitionLog::list(log_dir)? {
+        // The metadata log isn't a partition clients can produce to or move
+        if topic == METADATA_TOPIC {
+            continue;
+        }
+        let is_requested = requested.map_or(true, |requested| {
+            requested.iter().any(|requested| {
+                requested.topic == topic && requested.partitions.contains(&partition)
+            })
+        });
+        // A partition assigned to another dir only leaves a stale copy here
+        let assigned_elsewhere =
+            assignments
+                .get(&(topic.clone(), partition))
+                .is_some_and(|assigned| {
+                    !is_reserved_directory(assigned) && Some(*assigned) != directory_id
+                });
+        if !is_requested || assigned_elsewhere {
+            continue;
+        }
+
+        let log = match PartitionLog::open(log_dir, &topic, partition) {
+            Some(log) => log,
+            None => continue,
+        };
+        topics
+            .entry(topic)
+            .or_default()
+            .push((partition, log.size()? as i64));
+    }
+
+    Ok(topics)
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    // A null topic list describes every partition
+    let topics: Option<Vec<DescribableLogDirTopic>> = input
+        .get_compact_nullable_array_length()
+        .map(|topics_length| {
+            (0..topics_length)
+                .map(|_| {
+                    let topic = input.get_compact_string();
+                    let partitions_length = input.get_compact_array_length();
+                    let partitions = (0..partitions_length).map(|_| input.get_i32()).collect();
+                    input.skip_tagged_fields();
+                    DescribableLogDirTopic { topic, partitions }
+                })
+                .collect()
+        });
+    input.skip_tagged_fields();
+
+    let authorized = broker.authorize(
+        session,
+        AclOperation::Describe,
+        ResourceType::Cluster,
+        CLUSTER_NAME,
+    );
+    let (error_code, results) = if !authorized {
+        (error_code::CLUSTER_AUTHORIZATION_FAILED, vec![])
+    } else {
+        let metadata = broker.metadata();
+        let results = broker
+            .config
+            .log_dirs()
+            .iter()
+            .map(|log_dir| {
+                let described = describe_log_dir(broker, log_dir, &metadata, topics.as_deref())
+                    .and_then(|topics| Ok((topics, volume_bytes(log_dir)?)));
+                let (error_code, topics, (total_bytes, usable_bytes)) = match described {
+                    Ok((topics, volume_bytes)) => (error_code::NONE, topics, volume_bytes),
+                    Err(_) => (
+                        error_code::KAFKA_STORAGE_ERROR,
+                        BTreeMap::new(),
+                        (UNKNOWN_VOLUME_BYTES, UNKNOWN_VOLUME_BYTES),
+                    ),
+                };
+                DescribeLogDirsResult {
+                    error_code,
+                    log_dir: log_dir.to_string_lossy().to_string(),
+                    topics,
+                    total_bytes,
+                    usable_bytes,
+                }
+            })
+            .collect();
+        (error_code::NONE, results)
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    if header.api_version >= 3 {
+        body.put_i16(error_code);
+    }
+    body.put_compact_array_length(results.len());
+    for result in results {
+        body.put_i16(result.error_code);
+        body.put_compact_string(&result.log_dir);
+        body.put_compact_array_length(result.topics.len());
+        for (topic, partitions) in result.topics {
+            body.put_compact_string(&topic);
+            body.put_compact_array_length(partitions.len());
+            for (partition_index, partition_size) in partitions {
+                body.put_i32(partition_index);
+                body.put_i64(partition_size);
+                body.put_i64(0); // Offset lag, only future replicas lag behind
+                body.put_u8(0); // Is future key
+                body.put_empty_tagged_fields();
+            }
+            body.put_empty_tagged_fields();
+        }
+        if header.api_version >= 4 {
+            body.put_i64(result.total_bytes);
+            body.put_i64(result.usable_bytes);
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_metadata_log_is_not_described() {
+    use crate::test_util::{broker_with_topics, data_batch};
+
+    let (log_dir, broker) =
+        broker_with_topics("describe-log-dirs-metadata", "", &[("events", [1; 16], 1)]);
+    let events = PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+    events.append(data_batch()).unwrap();
+
+    let topics = describe_log_dir(&broker, log_dir.path(), &broker.metadata(), None).unwrap();
+    assert_eq!(topics.keys().collect::<Vec<_>>(), vec!["events"]);
+}
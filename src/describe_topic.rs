@@ -1,7 +1,16 @@
-use crate::cluser_metadata::{ClusterMetadata, PartitionRecord};
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::cluser_metadata::{PartitionRecord, RegisterBrokerRecord};
+use crate::error_code;
+use crate::metadata_image::MetadataImage;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
 
 use bytes::{Buf, BufMut};
-use std::fs;
+use std::collections::BTreeMap;
+
+// Partitions a response describes at most, whatever the request asks for
+const DEFAULT_PARTITION_SIZE_LIMIT: i32 = 2000;
 
 #[derive(Debug)]
 struct Partition {
@@ -9,17 +18,11 @@ struct Partition {
     partition_id: i32,
     leader_id: i32,
     leader_epoch: i32,
-    replicas_length: u8,
     replicas: Vec<i32>,
-    insync_replicas_length: u8,
     insync_replicas: Vec<i32>,
-    eligible_leader_length: u8,
     eligible_leader: Vec<i32>,
-    last_known_elr_length: u8,
     last_known_elr: Vec<i32>,
-    offline_replica_length: u8,
     offline_replica: Vec<i32>,
-    tag_buffer: u8,
 }
 
 #[derive(Debug)]
@@ -28,165 +31,479 @@ struct TopicDescription {
     error_code: i16,
     topic_id: [u8; 16],
     is_internal: bool,
-    partition_length: u8,
     partition_info: Vec<Partition>,
-    authorized_operations: u32,
+    authorized_operations: i32,
+}
+
+// Topic and partition a paginated response continues from
+#[derive(Debug, Clone, PartialEq)]
+struct Cursor {
+    topic_name: String,
+    partition_index: i32,
 }
 
 struct DescribeTopicResult {
-    correlation_id: u32,
-    throttle_time: u32,
     topic_descriptions: Vec<TopicDescription>,
-    next_cursor: u8,
+    next_cursor: Option<Cursor>,
+}
+
+// Replicas clients can't reach through their listener: brokers that aren't
+// registered, are fenced or have no endpoint for it
+pub fn is_offline(
+    replica: i32,
+    brokers: &BTreeMap<i32, RegisterBrokerRecord>,
+    listener_name: &str,
+    node_id: i32,
+) -> bool {
+    match brokers.get(&replica) {
+        Some(broker) => {
+            broker.fenced
+                || !broker
+                    .endpoints
+                    .iter()
+                    .any(|endpoint| endpoint.name == listener_name)
+        }
+        // This broker may not have registered in the metadata log yet
+        None => replica != node_id,
+    }
 }
 
-fn partition_record_to_partition(partition_record: PartitionRecord) -> Partition {
+fn partition_record_to_partition(
+    partition_record: &PartitionRecord,
+    brokers: &BTreeMap<i32, RegisterBrokerRecord>,
+    listener_name: &str,
+    node_id: i32,
+) -> Partition {
+    let error_code = if partition_record.leader_id == -1 {
+        error_code::LEADER_NOT_AVAILABLE
+    } else {
+        error_code::NONE
+    };
+    let offline_replica = partition_record
+        .replicas
+        .iter()
+        .copied()
+        .filter(|replica| is_offline(*replica, brokers, listener_name, node_id))
+        .collect();
     Partition {
-        error_code: 0,
+        error_code,
         partition_id: partition_record.partition_id,
         leader_id: partition_record.leader_id,
         leader_epoch: partition_record.leader_epoch,
-        replicas_length: partition_record.replicas_length,
         replicas: partition_record.replicas.clone(),
-        insync_replicas_length: partition_record.insync_replicas_length,
         insync_replicas: partition_record.insync_replicas.clone(),
-        eligible_leader_length: 0,
-        eligible_leader: vec![],
-        last_known_elr_length: 0,
-        last_known_elr: vec![],
-        offline_replica_length: 0,
-        offline_replica: vec![],
-        tag_buffer: 0,
+        eligible_leader: partition_record.eligible_leader_replicas.clone(),
+        last_known_elr: partition_record.last_known_elr.clone(),
+        offline_replica,
     }
 }
 
-fn describe_topics(correlation_id: u32, topics: Vec<String>) -> DescribeTopicResult {
-    let mut sorted_topics = topics.clone();
+fn error_description(name: String, error_code: i16) -> TopicDescription {
+    TopicDescription {
+        name,
+        error_code,
+        topic_id: [0; 16],
+        is_internal: false,
+        partition_info: vec![],
+        authorized_operations: 0,
+    }
+}
+
+// Describes the partitions of a topic from `first_partition` on, stopping
+// at the partition limit with a cursor to the next one
+fn describe_topic(
+    broker: &Broker,
+    session: &Session,
+    metadata: &MetadataImage,
+    topic_id: [u8; 16],
+    first_partition: i32,
+    remaining: &mut usize,
+) -> Option<(TopicDescription, Option<Cursor>)> {
+    let topic_name = metadata.topic_name(topic_id)?.to_string();
+    let mut partitions: Vec<&PartitionRecord> = metadata
+        .partitions(topic_id)
+        .filter(|partition| partition.partition_id >= first_partition)
+        .collect();
+    let mut next_cursor = None;
+    if partitions.len() > *remaining {
+        next_cursor = Some(Cursor {
+            topic_name: topic_name.clone(),
+            partition_index: partitions[*remaining].partition_id,
+        });
+        partitions.truncate(*remaining);
+    }
+    *remaining -= partitions.len();
+    let partition_info: Vec<Partition> = partitions
+        .into_iter()
+        .map(|partition| {
+            partition_record_to_partition(
+                partition,
+                metadata.brokers(),
+                &session.listener_name,
+                broker.config.node_id(),
+            )
+        })
+        .collect();
+    let authorized_operations =
+        broker.authorized_operations(session, ResourceType::Topic, &topic_name);
+
+    let description = TopicDescription {
+        name: topic_name,
+        error_code: 0,
+        topic_id,
+        is_internal: false,
+        partition_info,
+        authorized_operations,
+    };
+    Some((description, next_cursor))
+}
+
+// Describes topics in name order, starting from the cursor, until the
+// partition limit is reached. No topics at all describes every topic the
+// client may see.
+fn describe_topics(
+    broker: &Broker,
+    session: &Session,
+    topics: Vec<String>,
+    partition_limit: i32,
+    cursor: Option<Cursor>,
+) -> DescribeTopicResult {
+    let metadata = broker.metadata();
+    let topic_ids = metadata.topics();
+    let describe_all = topics.is_empty();
+    let mut sorted_topics = if describe_all {
+        topic_ids.keys().cloned().collect()
+    } else {
+        topics
+    };
     sorted_topics.sort();
-    let customer_metadata_raw =
-        fs::read("/tmp/kraft-combined-logs/__cluster_metadata-0/00000000000000000000.log").unwrap();
+    sorted_topics.dedup();
+
+    if let Some(cursor) = &cursor {
+        if !describe_all && !sorted_topics.contains(&cursor.topic_name) {
+            return DescribeTopicResult {
+                topic_descriptions: sorted_topics
+                    .into_iter()
+                    .map(|topic_name| error_description(topic_name, error_code::INVALID_REQUEST))
+                    .collect(),
+                next_cursor: None,
+            };
+        }
+    }
 
-    let cluster_metadata = ClusterMetadata::parse(&customer_metadata_raw);
+    let size_limit = broker
+        .config
+        .get("max.request.partition.size.limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_PARTITION_SIZE_LIMIT);
+    let mut remaining = partition_limit.min(size_limit).max(1) as usize;
 
     let mut topic_descriptions = vec![];
+    let mut next_cursor = None;
     for topic_name in sorted_topics {
-        let topic_id = match cluster_metadata.topic_id(&topic_name) {
-            Some(id) => id,
-            None => {
-                topic_descriptions.push(TopicDescription {
-                    name: topic_name,
-                    error_code: 3,
-                    topic_id: [0; 16],
-                    is_internal: false,
-                    partition_length: 0,
-                    partition_info: vec![],
-                    authorized_operations: 0,
-                });
-                continue;
-            }
+        let first_partition = match &cursor {
+            Some(cursor) if topic_name < cursor.topic_name => continue,
+            Some(cursor) if topic_name == cursor.topic_name => cursor.partition_index,
+            _ => 0,
         };
+        let authorized = broker.authorize(
+            session,
+            AclOperation::Describe,
+            ResourceType::Topic,
+            &topic_name,
+        );
+        // Listing every topic leaves out the ones the client can't see
+        if describe_all && !authorized {
+            continue;
+        }
+        if remaining == 0 {
+            next_cursor = Some(Cursor {
+                topic_name,
+                partition_index: 0,
+            });
+            break;
+        }
 
-        let partition_info: Vec<Partition> = cluster_metadata
-            .partitions(topic_id)
-            .into_iter()
-            .map(partition_record_to_partition)
-            .collect();
-
-        topic_descriptions.push(TopicDescription {
-            name: topic_name,
-            error_code: 0,
-            topic_id: topic_id,
-            is_internal: false,
-            partition_length: partition_info.len() as u8,
-            partition_info: partition_info,
-            authorized_operations: 0,
+        if !authorized {
+            topic_descriptions.push(error_description(
+                topic_name,
+                error_code::TOPIC_AUTHORIZATION_FAILED,
+            ));
+            continue;
+        }
+        let described = topic_ids.get(&topic_name).and_then(|topic_id| {
+            describe_topic(
+                broker,
+                session,
+                &metadata,
+                *topic_id,
+                first_partition,
+                &mut remaining,
+            )
         });
+        match described {
+            Some((description, topic_cursor)) => {
+                topic_descriptions.push(description);
+                if topic_cursor.is_some() {
+                    next_cursor = topic_cursor;
+                    break;
+                }
+            }
+            None => topic_descriptions.push(error_description(
+                topic_name,
+                error_code::UNKNOWN_TOPIC_OR_PARTITION,
+            )),
+        }
     }
 
     DescribeTopicResult {
-        correlation_id: correlation_id,
-        throttle_time: 0,
-        topic_descriptions: topic_descriptions,
-        next_cursor: 0xff,
+        topic_descriptions,
+        next_cursor,
     }
 }
 
-pub fn handle_request(mut input: &[u8]) -> Vec<u8> {
-    // Deserialize input
-    let _message_size = input.get_u32();
-    let _api_key = input.get_u16();
-    let _api_version = input.get_u16();
-    let correlation_id = input.get_u32();
-    let client_id_length = input.get_u16() as usize;
-    let _client_id: Vec<u8> = (0..client_id_length).map(|_| input.get_u8()).collect();
-    input.get_u8(); // Tag Buffer
-    let topic_array_length = input.get_u8() - 1;
-
-    let mut topic_names: Vec<String> = vec![];
-    for _ in 0..topic_array_length {
-        let topic_name_length = input.get_u8() - 1;
-        let topic_name_utf8: Vec<u8> = (0..topic_name_length).map(|_| input.get_u8()).collect();
-        let topic_name = String::from_utf8_lossy(&topic_name_utf8).to_string();
-        topic_names.push(topic_name);
-        input.get_u8(); // Tag Buffer
+fn put_replicas(body: &mut Vec<u8>, replicas: &[i32]) {
+    body.put_compact_array_length(replicas.len());
+    for replica in replicas {
+        body.put_i32(*replica);
     }
+}
 
-    let result = describe_topics(correlation_id, topic_names);
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let topics_length = input.get_compact_array_length();
+    let topic_names: Vec<String> = (0..topics_length)
+        .map(|_| {
+            let topic_name = input.get_compact_string();
+            input.skip_tagged_fields();
+            topic_name
+        })
+        .collect();
+    let partition_limit = input.get_i32();
+    // A nullable struct is prefixed with -1 when null
+    let cursor = if input.get_i8() < 0 {
+        None
+    } else {
+        let topic_name = input.get_compact_string();
+        let partition_index = input.get_i32();
+        input.skip_tagged_fields();
+        Some(Cursor {
+            topic_name,
+            partition_index,
+        })
+    };
+    input.skip_tagged_fields();
 
-    // Serialize result
-    let mut header = vec![];
-    header.put_u32(result.correlation_id);
-    header.put_u8(0); // Tag buffer
+    let result = describe_topics(broker, session, topic_names, partition_limit, cursor);
 
+    // Serialize result
     let mut body = vec![];
-    body.put_u32(result.throttle_time);
-    body.put_u8(result.topic_descriptions.len() as u8 + 1);
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(result.topic_descriptions.len());
     for topic_description in result.topic_descriptions {
         body.put_i16(topic_description.error_code);
-        let topic_name_utf8 = topic_description.name.as_bytes();
-        body.put_u8(topic_name_utf8.len() as u8 + 1);
-        body.extend_from_slice(topic_name_utf8);
+        body.put_compact_string(&topic_description.name);
         body.extend_from_slice(&topic_description.topic_id);
         body.put_u8(topic_description.is_internal as u8);
-        body.put_u8(topic_description.partition_length + 1);
+        body.put_compact_array_length(topic_description.partition_info.len());
         for partition in topic_description.partition_info {
             body.put_i16(partition.error_code);
             body.put_i32(partition.partition_id);
             body.put_i32(partition.leader_id);
             body.put_i32(partition.leader_epoch);
-            body.put_u8(partition.replicas_length + 1);
-            for replica in partition.replicas {
-                body.put_i32(replica);
-            }
-            body.put_u8(partition.insync_replicas_length + 1);
-            for replica in partition.insync_replicas {
-                body.put_i32(replica);
-            }
-            body.put_u8(partition.eligible_leader_length + 1);
-            for replica in partition.eligible_leader {
-                body.put_i32(replica);
-            }
-            body.put_u8(partition.last_known_elr_length + 1);
-            for replica in partition.last_known_elr {
-                body.put_i32(replica);
-            }
-            body.put_u8(partition.offline_replica_length + 1);
-            for replica in partition.offline_replica {
-                body.put_i32(replica);
-            }
-            body.put_u8(partition.tag_buffer);
+            put_replicas(&mut body, &partition.replicas);
+            put_replicas(&mut body, &partition.insync_replicas);
+            put_replicas(&mut body, &partition.eligible_leader);
+            put_replicas(&mut body, &partition.last_known_elr);
+            put_replicas(&mut body, &partition.offline_replica);
+            body.put_empty_tagged_fields();
+        }
+        body.put_i32(topic_description.authorized_operations);
+        body.put_empty_tagged_fields();
+    }
+    match result.next_cursor {
+        Some(cursor) => {
+            body.put_i8(1);
+            body.put_compact_string(&cursor.topic_name);
+            body.put_i32(cursor.partition_index);
+            body.put_empty_tagged_fields();
         }
-        body.put_u32(topic_description.authorized_operations);
-        body.put_u8(0); // Tag buffer
+        None => body.put_i8(-1),
     }
-    body.put_u8(result.next_cursor);
-    body.put_u8(0); // Tag buffer
-
-    // Write response buffer
-    let mut response = vec![];
-    let message_size: u32 = header.len() as u32 + body.len() as u32;
-    response.put_u32(message_size);
-    response.extend_from_slice(&header);
-    response.extend_from_slice(&body);
-    return response;
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_topics_are_only_looked_up_by_name() {
+    use crate::test_util::broker_with_topics;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    let (_log_dir, broker) =
+        broker_with_topics("describe-topic-by-name", "", &[("orders", [7; 16], 1)]);
+    let session = Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false);
+
+    // Ids are looked up through Metadata, a name that decodes to one is
+    // still just a name
+    let topic_id = URL_SAFE_NO_PAD.encode([7; 16]);
+    let topics = vec!["orders".to_string(), topic_id.clone()];
+    let result = describe_topics(&broker, &session, topics, 10, None);
+    let descriptions = &result.topic_descriptions;
+    assert_eq!(descriptions.len(), 2);
+    assert_eq!(descriptions[0].name, topic_id);
+    assert_eq!(
+        descriptions[0].error_code,
+        error_code::UNKNOWN_TOPIC_OR_PARTITION
+    );
+    assert_eq!(descriptions[1].name, "orders");
+    assert_eq!(descriptions[1].topic_id, [7; 16]);
+}
+
+#[test]
+fn test_pagination_resumes_from_cursor() {
+    use crate::test_util::broker_with_topics;
+
+    let (_log_dir, broker) = broker_with_topics(
+        "describe-topic-pagination",
+        "",
+        &[("alpha", [1; 16], 3), ("beta", [2; 16], 2)],
+    );
+    let session = Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false);
+    let topics = vec!["beta".to_string(), "alpha".to_string()];
+    let partition_ids = |result: &DescribeTopicResult| {
+        result
+            .topic_descriptions
+            .iter()
+            .map(|description| {
+                let partition_ids = description
+                    .partition_info
+                    .iter()
+                    .map(|partition| partition.partition_id);
+                (description.name.clone(), partition_ids.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>()
+    };
+    let cursor = |topic_name: &str, partition_index| Cursor {
+        topic_name: topic_name.to_string(),
+        partition_index,
+    };
+
+    // The limit runs out exactly at the end of alpha
+    let result = describe_topics(&broker, &session, topics.clone(), 3, None);
+    assert_eq!(
+        partition_ids(&result),
+        vec![("alpha".to_string(), vec![0, 1, 2])]
+    );
+    assert_eq!(result.next_cursor, Some(cursor("beta", 0)));
+
+    // Resuming in the middle of a topic
+    let result = describe_topics(&broker, &session, topics, 2, Some(cursor("alpha", 1)));
+    assert_eq!(
+        partition_ids(&result),
+        vec![("alpha".to_string(), vec![1, 2])]
+    );
+    assert_eq!(result.next_cursor, Some(cursor("beta", 0)));
+    let result = describe_topics(&broker, &session, vec![], 10, Some(cursor("alpha", 2)));
+    assert_eq!(
+        partition_ids(&result),
+        vec![
+            ("alpha".to_string(), vec![2]),
+            ("beta".to_string(), vec![0, 1])
+        ]
+    );
+    assert_eq!(result.next_cursor, None);
+}
+
+#[test]
+fn test_cursor_must_name_a_requested_topic() {
+    use crate::test_util::broker_with_topics;
+
+    let (_log_dir, broker) = broker_with_topics(
+        "describe-topic-invalid-cursor",
+        "",
+        &[("alpha", [1; 16], 1), ("beta", [2; 16], 1)],
+    );
+    let session = Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false);
+    let cursor = Cursor {
+        topic_name: "beta".to_string(),
+        partition_index: 0,
+    };
+
+    let result = describe_topics(
+        &broker,
+        &session,
+        vec!["alpha".to_string()],
+        10,
+        Some(cursor),
+    );
+    assert_eq!(result.topic_descriptions.len(), 1);
+    assert_eq!(result.topic_descriptions[0].name, "alpha");
+    assert_eq!(
+        result.topic_descriptions[0].error_code,
+        error_code::INVALID_REQUEST
+    );
+    assert!(result.topic_descriptions[0].partition_info.is_empty());
+    assert_eq!(result.next_cursor, None);
+}
+
+#[test]
+fn test_partition_size_limit_caps_the_request_limit() {
+    use crate::test_util::broker_with_topics;
+
+    let (_log_dir, broker) = broker_with_topics(
+        "describe-topic-size-limit",
+        "max.request.partition.size.limit=2\n",
+        &[("alpha", [1; 16], 3)],
+    );
+    let session = Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false);
+
+    let result = describe_topics(&broker, &session, vec!["alpha".to_string()], 10, None);
+    assert_eq!(result.topic_descriptions[0].partition_info.len(), 2);
+    assert_eq!(
+        result.next_cursor,
+        Some(Cursor {
+            topic_name: "alpha".to_string(),
+            partition_index: 2,
+        })
+    );
+}
+
+#[test]
+fn test_offline_replicas_and_leaderless_partitions() {
+    use crate::test_util::{
+        broker_with_topics, fence_broker_record, partition_record, register_broker_record,
+        topic_record,
+    };
+
+    // Broker 2 only listens on EXTERNAL, 3 is fenced and 4 never registered
+    let (_log_dir, broker) = broker_with_topics("describe-topic-offline", "", &[]);
+    broker
+        .append_metadata(vec![
+            register_broker_record(1, "PLAINTEXT"),
+            register_broker_record(2, "EXTERNAL"),
+            register_broker_record(3, "PLAINTEXT"),
+            fence_broker_record(3),
+            topic_record("events", [1; 16]),
+            partition_record([1; 16], 0, 1, &[1, 2, 3, 4]),
+            partition_record([1; 16], 1, -1, &[1]),
+        ])
+        .unwrap();
+    let session = Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false);
+
+    let result = describe_topics(&broker, &session, vec!["events".to_string()], 10, None);
+    let partitions = &result.topic_descriptions[0].partition_info;
+    assert_eq!(partitions[0].error_code, error_code::NONE);
+    assert_eq!(partitions[0].offline_replica, vec![2, 3, 4]);
+    assert_eq!(partitions[1].error_code, error_code::LEADER_NOT_AVAILABLE);
+    assert_eq!(partitions[1].leader_id, -1);
+    assert!(partitions[1].offline_replica.is_empty());
+
+    // This broker counts as online before it has registered itself
+    let no_brokers = BTreeMap::new();
+    assert!(!is_offline(1, &no_brokers, "PLAINTEXT", 1));
+    assert!(is_offline(2, &no_brokers, "PLAINTEXT", 1));
 }
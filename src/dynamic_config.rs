@@ -0,0 +1,782 @@
+use crate::acl::{AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::cluser_metadata::ConfigRecord;
+use crate::config::BrokerConfig;
+use crate::error_code;
+use crate::metadata_image::MetadataImage;
+use crate::session::Session;
+
+use std::collections::{BTreeMap, HashSet};
+
+pub const TOPIC_RESOURCE: i8 = 2;
+pub const BROKER_RESOURCE: i8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigType {
+    Boolean,
+    String,
+    Int,
+    Long,
+    Double,
+    List,
+}
+
+impl ConfigType {
+    pub fn code(self) -> i8 {
+        match self {
+            ConfigType::Boolean => 1,
+            ConfigType::String => 2,
+            ConfigType::Int => 3,
+            ConfigType::Long => 5,
+            ConfigType::Double => 6,
+            ConfigType::List => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigSource {
+    DynamicTopic,
+    DynamicBroker,
+    DynamicDefaultBroker,
+    StaticBroker,
+    Default,
+}
+
+impl ConfigSource {
+    pub fn code(self) -> i8 {
+        match self {
+            ConfigSource::DynamicTopic => 1,
+            ConfigSource::DynamicBroker => 2,
+            ConfigSource::DynamicDefaultBroker => 3,
+            ConfigSource::StaticBroker => 4,
+            ConfigSource::Default => 5,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigDef {
+    pub name: &'static str,
+    pub config_type: ConfigType,
+    pub default: &'static str,
+    min: f64,
+    max: f64,
+    valid_values: &'static [&'static str],
+    // Broker configs the topic config falls back to, with the factor that
+    // converts their unit into the topic config's
+    synonyms: &'static [(&'static str, i64)],
+    pub documentation: &'static str,
+}
+
+const NO_MIN: f64 = f64::NEG_INFINITY;
+const NO_MAX: f64 = f64::INFINITY;
+
+pub const CONFIG_DEFS: &[ConfigDef] = &[
+    ConfigDef {
+        name: "cleanup.policy",
+        config_type: ConfigType::List,
+        default: "delete",
+        min: NO_MIN,
+        max: NO_MAX,
+        valid_values: &["compact", "delete"],
+        synonyms: &[("log.cleanup.policy", 1)],
+        documentation: "The retention policy to use on log segments.",
+    },
+    ConfigDef {
+        name: "compression.type",
+        config_type: ConfigType::String,
+        default: "producer",
+        min: NO_MIN,
+        max: NO_MAX,
+        valid_values: &["uncompressed", "zstd", "lz4", "snappy", "gzip", "producer"],
+        synonyms: &[("compression.type", 1)],
+        documentation: "The final compression type for a given topic.",
+    },
+    ConfigDef {
+        name: "delete.retention.ms",
+        config_type: ConfigType::Long,
+        default: "86400000",
+        min: 0.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.cleaner.delete.retention.ms", 1)],
+        documentation:
+            "The amount of time to retain delete tombstone markers for log compacted topics.",
+    },
+    ConfigDef {
+        name: "file.delete.delay.ms",
+        config_type: ConfigType::Long,
+        default: "60000",
+        min: 0.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.segment.delete.delay.ms", 1)],
+        documentation: "The time to wait before deleting a file from the filesystem.",
+    },
+    ConfigDef {
+        name: "flush.messages",
+        config_type: ConfigType::Long,
+        default: "9223372036854775807",
+        min: 1.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.flush.interval.messages", 1)],
+        documentation:
+            "The number of messages accumulated on a partition before they are flushed to disk.",
+    },
+    ConfigDef {
+        name: "flush.ms",
+        config_type: ConfigType::Long,
+        default: "9223372036854775807",
+        min: 0.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.flush.interval.ms", 1)],
+        documentation: "The maximum time a message stays in memory before it is flushed to disk.",
+    },
+    ConfigDef {
+        name: "index.interval.bytes",
+        config_type: ConfigType::Int,
+        default: "4096",
+        min: 0.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.index.interval.bytes", 1)],
+        documentation: "How frequently an entry is added to the offset index.",
+    },
+    ConfigDef {
+        name: "max.compaction.lag.ms",
+        config_type: ConfigType::Long,
+        default: "9223372036854775807",
+        min: 1.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.cleaner.max.compaction.lag.ms", 1)],
+        documentation: "The maximum time a message will remain ineligible for compaction.",
+    },
+    ConfigDef {
+        name: "max.message.bytes",
+        config_type: ConfigType::Int,
+        default: "1048588",
+        min: 0.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("message.max.bytes", 1)],
+        documentation: "The largest record batch size allowed.",
+    },
+    ConfigDef {
+        name: "message.timestamp.type",
+        config_type: ConfigType::String,
+        default: "CreateTime",
+        min: NO_MIN,
+        max: NO_MAX,
+        valid_values: &["CreateTime", "LogAppendTime"],
+        synonyms: &[("log.message.timestamp.type", 1)],
+        documentation: "Whether record timestamps are the create time or the log append time.",
+    },
+    ConfigDef {
+        name: "min.cleanable.dirty.ratio",
+        config_type: ConfigType::Double,
+        default: "0.5",
+        min: 0.0,
+        max: 1.0,
+        valid_values: &[],
+        synonyms: &[("log.cleaner.min.cleanable.ratio", 1)],
+        documentation: "The minimum ratio of dirty log to total log for a log to be cleaned.",
+    },
+    ConfigDef {
+        name: "min.compaction.lag.ms",
+        config_type: ConfigType::Long,
+        default: "0",
+        min: 0.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.cleaner.min.compaction.lag.ms", 1)],
+        documentation: "The minimum time a message will remain uncompacted.",
+    },
+    ConfigDef {
+        name: "min.insync.replicas",
+        config_type: ConfigType::Int,
+        default: "1",
+        min: 1.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("min.insync.replicas", 1)],
+        documentation:
+            "The minimum number of replicas that must acknowledge a write with acks=all.",
+    },
+    ConfigDef {
+        name: "preallocate",
+        config_type: ConfigType::Boolean,
+        default: "false",
+        min: NO_MIN,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.preallocate", 1)],
+        documentation: "Whether to preallocate the file on disk when creating a new log segment.",
+    },
+    ConfigDef {
+        name: "retention.bytes",
+        config_type: ConfigType::Long,
+        default: "-1",
+        min: NO_MIN,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.retention.bytes", 1)],
+        documentation:
+            "The maximum size a partition can grow to before old segments are discarded.",
+    },
+    ConfigDef {
+        name: "retention.ms",
+        config_type: ConfigType::Long,
+        default: "604800000",
+        min: -1.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[
+            ("log.retention.ms", 1),
+            ("log.retention.minutes", 60_000),
+            ("log.retention.hours", 3_600_000),
+        ],
+        documentation: "The maximum time a log is retained before old segments are discarded.",
+    },
+    ConfigDef {
+        name: "segment.bytes",
+        config_type: ConfigType::Int,
+        default: "1073741824",
+        min: 14.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.segment.bytes", 1)],
+        documentation: "The segment file size for the log.",
+    },
+    ConfigDef {
+        name: "segment.ms",
+        config_type: ConfigType::Long,
+        default: "604800000",
+        min: 1.0,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("log.roll.ms", 1), ("log.roll.hours", 3_600_000)],
+        documentation: "The time after which a new log segment is rolled.",
+    },
+    ConfigDef {
+        name: "unclean.leader.election.enable",
+        config_type: ConfigType::Boolean,
+        default: "false",
+        min: NO_MIN,
+        max: NO_MAX,
+        valid_values: &[],
+        synonyms: &[("unclean.leader.election.enable", 1)],
+        documentation: "Whether out-of-sync replicas may be elected leader as a last resort.",
+    },
+];
+
+#[derive(Debug)]
+pub struct ConfigSynonym {
+    pub name: String,
+    pub value: Option<String>,
+    pub source: ConfigSource,
+}
+
+#[derive(Debug)]
+pub struct DescribedConfig {
+    pub name: String,
+    pub value: Option<String>,
+    pub source: ConfigSource,
+    pub read_only: bool,
+    pub config_type: ConfigType,
+    pub synonyms: Vec<ConfigSynonym>,
+    pub documentation: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlterOp {
+    Set,
+    Delete,
+    Append,
+    Subtract,
+}
+
+impl AlterOp {
+    pub fn from_code(code: i8) -> Option<AlterOp> {
+        match code {
+            0 => Some(AlterOp::Set),
+            1 => Some(AlterOp::Delete),
+            2 => Some(AlterOp::Append),
+            3 => Some(AlterOp::Subtract),
+            _ => None,
+        }
+    }
+}
+
+pub type ConfigError = (i16, String);
+
+pub fn topic_def(name: &str) -> Option<&'static ConfigDef> {
+    CONFIG_DEFS.iter().find(|def| def.name == name)
+}
+
+// Broker configs are the synonyms of the topic configs
+fn broker_def(name: &str) -> Option<(&'static ConfigDef, i64)> {
+    CONFIG_DEFS.iter().find_map(|def| {
+        def.synonyms
+            .iter()
+            .find(|(synonym, _)| *synonym == name)
+            .map(|(_, factor)| (def, *factor))
+    })
+}
+
+fn invalid_value(def_name: &str, value: &str, reason: &str) -> ConfigError {
+    (
+        error_code::INVALID_CONFIG,
+        format!(
+            "Invalid value {} for configuration {}: {}",
+            value, def_name, reason
+        ),
+    )
+}
+
+pub fn validate(
+    name: &str,
+    def: &ConfigDef,
+    value: &str,
+    check_range: bool,
+) -> Result<(), ConfigError> {
+    let number = match def.config_type {
+        ConfigType::Boolean => {
+            if !value.eq_ignore_ascii_case("true") && !value.eq_ignore_ascii_case("false") {
+                return Err(invalid_value(
+                    name,
+                    value,
+                    "Expected value to be either true or false",
+                ));
+            }
+            None
+        }
+        ConfigType::Int => match value.trim().parse::<i32>() {
+            Ok(number) => Some(number as f64),
+            Err(_) => return Err(invalid_value(name, value, "Not a number of type INT")),
+        },
+        ConfigType::Long => match value.trim().parse::<i64>() {
+            Ok(number) => Some(number as f64),
+            Err(_) => return Err(invalid_value(name, value, "Not a number of type LONG")),
+        },
+        ConfigType::Double => match value.trim().parse::<f64>() {
+            Ok(number) => Some(number),
+            Err(_) => return Err(invalid_value(name, value, "Not a number of type DOUBLE")),
+        },
+        ConfigType::String => {
+            if !def.valid_values.is_empty() && !def.valid_values.contains(&value) {
+                let reason = format!("String must be one of: {}", def.valid_values.join(", "));
+                return Err(invalid_value(name, value, &reason));
+            }
+            None
+        }
+        ConfigType::List => {
+            for item in value.split(',').map(str::trim) {
+                if !def.valid_values.is_empty() && !def.valid_values.contains(&item) {
+                    let reason =
+                        format!("List items must be one of: {}", def.valid_values.join(", "));
+                    return Err(invalid_value(name, value, &reason));
+                }
+            }
+            None
+        }
+    };
+
+    if let (Some(number), true) = (number, check_range) {
+        if number < def.min {
+            let reason = format!("Value must be at least {}", def.min);
+            return Err(invalid_value(name, value, &reason));
+        }
+        if number > def.max {
+            let reason = format!("Value must be no more than {}", def.max);
+            return Err(invalid_value(name, value, &reason));
+        }
+    }
+    Ok(())
+}
+
+// Broker-level values for a config, most specific source first
+struct BrokerSources<'a> {
+    dynamic: BTreeMap<String, String>,
+    dynamic_default: BTreeMap<String, String>,
+    config: &'a BrokerConfig,
+}
+
+impl BrokerSources<'_> {
+    fn load<'a>(metadata: &MetadataImage, config: &'a BrokerConfig) -> BrokerSources<'a> {
+        BrokerSources {
+            dynamic: metadata.configs(BROKER_RESOURCE, &config.node_id().to_string()),
+            dynamic_default: metadata.configs(BROKER_RESOURCE, ""),
+            config,
+        }
+    }
+
+    fn synonyms(&self, name: &str) -> Vec<ConfigSynonym> {
+        let candidates = [
+            (
+                self.dynamic.get(name).map(String::as_str),
+                ConfigSource::DynamicBroker,
+            ),
+            (
+                self.dynamic_default.get(name).map(String::as_str),
+                ConfigSource::DynamicDefaultBroker,
+            ),
+            (self.config.get(name), ConfigSource::StaticBroker),
+        ];
+        candidates
+            .into_iter()
+            .filter_map(|(value, source)| {
+                value.map(|value| ConfigSynonym {
+                    name: name.to_string(),
+                    value: Some(value.to_string()),
+                    source,
+                })
+            })
+            .collect()
+    }
+}
+
+// Topic configs are guarded by the topic's acls, broker configs by the cluster's
+pub fn authorize(
+    broker: &Broker,
+    session: &Session,
+    operation: AclOperation,
+    resource_type: i8,
+    resource_name: &str,
+) -> Result<(), ConfigError> {
+    let authorized = match resource_type {
+        TOPIC_RESOURCE => broker.authorize(session, operation, ResourceType::Topic, resource_name),
+        _ => broker.authorize(session, operation, ResourceType::Cluster, CLUSTER_NAME),
+    };
+    match (authorized, resource_type) {
+        (true, _) => Ok(()),
+        (false, TOPIC_RESOURCE) => Err((
+            error_code::TOPIC_AUTHORIZATION_FAILED,
+            format!("Topic authorization failed for {}", resource_name),
+        )),
+        (false, _) => Err((
+            error_code::CLUSTER_AUTHORIZATION_FAILED,
+            "Cluster authorization failed".to_string(),
+        )),
+    }
+}
+
+fn check_resource(
+    resource_type: i8,
+    resource_name: &str,
+    metadata: &MetadataImage,
+    config: &BrokerConfig,
+) -> Result<(), ConfigError> {
+    match resource_type {
+        TOPIC_RESOURCE => match metadata.topic_id(resource_name) {
+            Some(_) => Ok(()),
+            None => Err((
+                error_code::UNKNOWN_TOPIC_OR_PARTITION,
+                format!("Topic {} does not exist", resource_name),
+            )),
+        },
+        BROKER_RESOURCE => {
+            if resource_name.is_empty() || resource_name == config.node_id().to_string() {
+                Ok(())
+            } else {
+                Err((
+                    error_code::INVALID_REQUEST,
+                    format!(
+                        "Unexpected broker id, expected {} or empty string, but received {}",
+                        config.node_id(),
+                        resource_name
+                    ),
+                ))
+            }
+        }
+        _ => Err((
+            error_code::INVALID_REQUEST,
+            format!("Unsupported resource type {}", resource_type),
+        )),
+    }
+}
+
+pub fn describe(
+    resource_type: i8,
+    resource_name: &str,
+    keys: Option<&[String]>,
+    metadata: &MetadataImage,
+    config: &BrokerConfig,
+) -> Result<Vec<DescribedConfig>, ConfigError> {
+    check_resource(resource_type, resource_name, metadata, config)?;
+    let broker_sources = BrokerSources::load(metadata, config);
+    let mut described = vec![];
+
+    if resource_type == TOPIC_RESOURCE {
+        let overrides = metadata.configs(TOPIC_RESOURCE, resource_name);
+        for def in CONFIG_DEFS {
+            let mut synonyms = vec![];
+            let mut factors = vec![];
+            if let Some(value) = overrides.get(def.name) {
+                synonyms.push(ConfigSynonym {
+                    name: def.name.to_string(),
+                    value: Some(value.clone()),
+                    source: ConfigSource::DynamicTopic,
+                });
+                factors.push(1);
+            }
+            for (broker_name, factor) in def.synonyms {
+                let broker_synonyms = broker_sources.synonyms(broker_name);
+                factors.extend(broker_synonyms.iter().map(|_| *factor));
+                synonyms.extend(broker_synonyms);
+            }
+            synonyms.push(ConfigSynonym {
+                name: def.synonyms[0].0.to_string(),
+                value: Some(def.default.to_string()),
+                source: ConfigSource::Default,
+            });
+            factors.push(1);
+
+            // Broker synonyms may be in other units, e.g. log.retention.hours
+            let value = match (synonyms[0].value.as_deref(), factors[0]) {
+                (Some(value), 1) => Some(value.to_string()),
+                (Some(value), factor) => value
+                    .trim()
+                    .parse::<i64>()
+                    .ok()
+                    .map(|value| value.saturating_mul(factor).to_string()),
+                (None, _) => None,
+            };
+            described.push(DescribedConfig {
+                name: def.name.to_string(),
+                value,
+                source: synonyms[0].source,
+                read_only: false,
+                config_type: def.config_type,
+                synonyms,
+                documentation: def.documentation,
+            });
+        }
+    } else {
+        let mut seen = HashSet::new();
+        for def in CONFIG_DEFS {
+            for (index, (broker_name, _)) in def.synonyms.iter().enumerate() {
+                if !seen.insert(*broker_name) {
+                    continue;
+                }
+                let mut synonyms = broker_sources.synonyms(broker_name);
+                // The cluster-wide default resource only shows dynamic defaults
+                if resource_name.is_empty() {
+                    synonyms.retain(|synonym| synonym.source == ConfigSource::DynamicDefaultBroker);
+                    if synonyms.is_empty() {
+                        continue;
+                    }
+                } else if index == 0 {
+                    synonyms.push(ConfigSynonym {
+                        name: broker_name.to_string(),
+                        value: Some(def.default.to_string()),
+                        source: ConfigSource::Default,
+                    });
+                }
+                let (value, source) = match synonyms.first() {
+                    Some(synonym) => (synonym.value.clone(), synonym.source),
+                    None => (None, ConfigSource::Default),
+                };
+                described.push(DescribedConfig {
+                    name: broker_name.to_string(),
+                    value,
+                    source,
+                    read_only: false,
+                    config_type: def.config_type,
+                    synonyms,
+                    documentation: def.documentation,
+                });
+            }
+        }
+    }
+
+    if let Some(keys) = keys {
+        described.retain(|config| keys.contains(&config.name));
+    }
+    Ok(described)
+}
+
+fn def_for(resource_type: i8, name: &str) -> Result<(&'static ConfigDef, bool), ConfigError> {
+    let def = if resource_type == TOPIC_RESOURCE {
+        topic_def(name).map(|def| (def, true))
+    } else {
+        // Range limits are expressed in the topic config's unit
+        broker_def(name).map(|(def, factor)| (def, factor == 1))
+    };
+    def.ok_or((
+        error_code::INVALID_CONFIG,
+        format!("Unknown config name: {}", name),
+    ))
+}
+
+// Turns the difference between two config sets into ConfigRecords
+fn diff_records(
+    resource_type: i8,
+    resource_name: &str,
+    current: &BTreeMap<String, String>,
+    updated: &BTreeMap<String, String>,
+) -> Vec<ConfigRecord> {
+    let removed = current
+        .keys()
+        .filter(|name| !updated.contains_key(*name))
+        .map(|name| ConfigRecord::new(resource_type, resource_name, name, None));
+    let changed = updated
+        .iter()
+        .filter(|(name, value)| current.get(*name) != Some(*value))
+        .map(|(name, value)| ConfigRecord::new(resource_type, resource_name, name, Some(value)));
+    removed.chain(changed).collect()
+}
+
+// AlterConfigs replaces the whole dynamic config set of the resource
+pub fn alter(
+    resource_type: i8,
+    resource_name: &str,
+    configs: &[(String, Option<String>)],
+    metadata: &MetadataImage,
+    config: &BrokerConfig,
+) -> Result<Vec<ConfigRecord>, ConfigError> {
+    check_resource(resource_type, resource_name, metadata, config)?;
+    let current = metadata.configs(resource_type, resource_name);
+
+    let mut updated = BTreeMap::new();
+    for (name, value) in configs {
+        let (def, check_range) = def_for(resource_type, name)?;
+        if let Some(value) = value {
+            validate(name, def, value, check_range)?;
+            updated.insert(name.clone(), value.clone());
+        }
+    }
+
+    Ok(diff_records(
+        resource_type,
+        resource_name,
+        &current,
+        &updated,
+    ))
+}
+
+pub fn incremental_alter(
+    resource_type: i8,
+    resource_name: &str,
+    changes: &[(String, i8, Option<String>)],
+    metadata: &MetadataImage,
+    config: &BrokerConfig,
+) -> Result<Vec<ConfigRecord>, ConfigError> {
+    check_resource(resource_type, resource_name, metadata, config)?;
+    let current = metadata.configs(resource_type, resource_name);
+
+    let mut names = HashSet::new();
+    if !changes.iter().all(|(name, _, _)| names.insert(name)) {
+        return Err((
+            error_code::INVALID_REQUEST,
+            "Error due to duplicate config keys".to_string(),
+        ));
+    }
+
+    let mut updated = current.clone();
+    for (name, operation, value) in changes {
+        let (def, check_range) = def_for(resource_type, name)?;
+        let operation = match AlterOp::from_code(*operation) {
+            Some(operation) => operation,
+            None => {
+                return Err((
+                    error_code::INVALID_REQUEST,
+                    format!("Unknown config operation {}", operation),
+                ))
+            }
+        };
+        if operation != AlterOp::Delete && value.is_none() {
+            return Err((
+                error_code::INVALID_REQUEST,
+                format!("Null value not supported for {}", name),
+            ));
+        }
+        if matches!(operation, AlterOp::Append | AlterOp::Subtract)
+            && def.config_type != ConfigType::List
+        {
+            return Err((
+                error_code::INVALID_CONFIG,
+                format!(
+                    "Config value append is not allowed for config key: {}",
+                    name
+                ),
+            ));
+        }
+
+        let value = value.as_deref().unwrap_or_default();
+        let existing = updated.get(name).map(String::as_str).unwrap_or(def.default);
+        let mut items: Vec<&str> = existing
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect();
+        let new_value = match operation {
+            AlterOp::Set => Some(value.to_string()),
+            AlterOp::Delete => None,
+            AlterOp::Append => {
+                for item in value.split(',').map(str::trim) {
+                    if !items.contains(&item) {
+                        items.push(item);
+                    }
+                }
+                Some(items.join(","))
+            }
+            AlterOp::Subtract => {
+                let removed: Vec<&str> = value.split(',').map(str::trim).collect();
+                items.retain(|item| !removed.contains(item));
+                Some(items.join(","))
+            }
+        };
+
+        match new_value {
+            Some(new_value) => {
+                validate(name, def, &new_value, check_range)?;
+                updated.insert(name.clone(), new_value);
+            }
+            None => {
+                updated.remove(name);
+            }
+        }
+    }
+
+    Ok(diff_records(
+        resource_type,
+        resource_name,
+        &current,
+        &updated,
+    ))
+}
+
+#[test]
+fn test_validate() {
+    let retention = topic_def("retention.ms").unwrap();
+    assert!(validate("retention.ms", retention, "1000", true).is_ok());
+    assert!(validate("retention.ms", retention, "-2", true).is_err());
+    assert!(validate("retention.ms", retention, "soon", true).is_err());
+
+    let cleanup = topic_def("cleanup.policy").unwrap();
+    assert!(validate("cleanup.policy", cleanup, "compact,delete", true).is_ok());
+    assert!(validate("cleanup.policy", cleanup, "compact,forever", true).is_err());
+
+    let ratio = topic_def("min.cleanable.dirty.ratio").unwrap();
+    assert!(validate("min.cleanable.dirty.ratio", ratio, "1.5", true).is_err());
+}
+
+#[test]
+fn test_topic_value_falls_back_to_broker_synonyms() {
+    let metadata = MetadataImage::new();
+    let config = BrokerConfig::parse("log.retention.hours=24\n");
+    let broker_sources = BrokerSources::load(&metadata, &config);
+    assert!(broker_sources.synonyms("log.retention.ms").is_empty());
+
+    let described = describe(BROKER_RESOURCE, "1", None, &metadata, &config).unwrap();
+    let hours = described
+        .iter()
+        .find(|config| config.name == "log.retention.hours")
+        .unwrap();
+    assert_eq!(hours.value.as_deref(), Some("24"));
+    assert_eq!(hours.source, ConfigSource::StaticBroker);
+}
@@ -0,0 +1,42 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let transactional_id = input.get_compact_string();
+    let producer_id = input.get_i64();
+    let producer_epoch = input.get_i16();
+    let committed = input.get_u8() != 0;
+    input.skip_tagged_fields();
+
+    let result = if broker.authorize(
+        session,
+        AclOperation::Write,
+        ResourceType::TransactionalId,
+        &transactional_id,
+    ) {
+        broker.transactions.lock().unwrap().end_transaction(
+            &transactional_id,
+            producer_id,
+            producer_epoch,
+            committed,
+        )
+    } else {
+        Err(error_code::TRANSACTIONAL_ID_AUTHORIZATION_FAILED)
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_i16(result.err().unwrap_or(error_code::NONE));
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
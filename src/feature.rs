@@ -0,0 +1,130 @@
+use crate::error_code;
+
+use std::collections::BTreeMap;
+
+pub const METADATA_VERSION: &str = "metadata.version";
+// Finalized by the metadata quorum's own KRaftVersion records, never by
+// UpdateFeatures
+pub const KRAFT_VERSION: &str = "kraft.version";
+
+// metadata.version feature levels behavior changes with. Logs without a
+// FeatureLevelRecord for it were written by the first KRaft version.
+pub const IBP_3_0_IV1: i16 = 1;
+pub const IBP_3_3_IV0: i16 = 4;
+// SCRAM credentials
+pub const IBP_3_5_IV2: i16 = 11;
+// Partition replicas assigned to log directories
+pub const IBP_3_7_IV2: i16 = 17;
+pub const IBP_3_9_IV0: i16 = 21;
+
+// Features this broker supports, with their minimum and maximum levels
+pub const SUPPORTED_FEATURES: &[(&str, i16, i16)] = &[(METADATA_VERSION, IBP_3_0_IV1, IBP_3_9_IV0)];
+
+pub const UPGRADE: i8 = 1;
+pub const SAFE_DOWNGRADE: i8 = 2;
+pub const UNSAFE_DOWNGRADE: i8 = 3;
+
+// Checks a request to finalize `feature` at `level`, where level 0 disables it
+pub fn validate_update(
+    finalized: &BTreeMap<String, i16>,
+    feature: &str,
+    level: i16,
+    upgrade_type: i8,
+) -> Result<(), (i16, String)> {
+    let invalid = |message: String| Err((error_code::INVALID_UPDATE_VERSION, message));
+    if ![UPGRADE, SAFE_DOWNGRADE, UNSAFE_DOWNGRADE].contains(&upgrade_type) {
+        return Err((
+            error_code::INVALID_REQUEST,
+            format!("Invalid upgrade type {}", upgrade_type),
+        ));
+    }
+    if level < 0 {
+        return invalid(
+            "The user requested to set the feature level to a negative value.".to_string(),
+        );
+    }
+    let current = finalized.get(feature).copied().unwrap_or(0);
+    if level < current && upgrade_type == UPGRADE {
+        return invalid(
+            "Can't downgrade the version of this feature without setting the upgrade type to \
+             either safe or unsafe downgrade."
+                .to_string(),
+        );
+    }
+    match SUPPORTED_FEATURES
+        .iter()
+        .find(|(name, _, _)| *name == feature)
+    {
+        Some((_, min, max)) if level != 0 && (level < *min || level > *max) => {
+            return invalid(format!(
+                "Invalid update version {} for feature {}. Broker only supports versions {}-{}",
+                level, feature, min, max
+            ));
+        }
+        None if level != 0 => {
+            return invalid(format!(
+                "Invalid update version {} for feature {}. The broker does not support the \
+                 given feature.",
+                level, feature
+            ));
+        }
+        _ => {}
+    }
+
+    if feature == METADATA_VERSION {
+        if level < IBP_3_3_IV0 {
+            return invalid(format!(
+                "Invalid metadata.version {}. Unable to set a metadata.version less than 3.3-IV0",
+                level
+            ));
+        }
+        // Every metadata.version since 3.3-IV0 changed the records it can write
+        if level < current && upgrade_type == UNSAFE_DOWNGRADE {
+            return invalid(
+                "Unsafe metadata downgrade is not supported in this version.".to_string(),
+            );
+        }
+        if level < current {
+            return invalid(format!(
+                "Invalid metadata.version {}. Refusing to perform the requested downgrade \
+                 because it might delete metadata information.",
+                level
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_validate_update() {
+    let finalized = BTreeMap::from([(METADATA_VERSION.to_string(), 14)]);
+    let error = |feature, level, upgrade_type| {
+        validate_update(&finalized, feature, level, upgrade_type)
+            .err()
+            .map(|(error_code, _)| error_code)
+    };
+
+    assert_eq!(error(METADATA_VERSION, 17, UPGRADE), None);
+    assert_eq!(error(METADATA_VERSION, 14, UPGRADE), None);
+    assert_eq!(
+        error(METADATA_VERSION, 12, UPGRADE),
+        Some(error_code::INVALID_UPDATE_VERSION)
+    );
+    assert_eq!(
+        error(METADATA_VERSION, 12, SAFE_DOWNGRADE),
+        Some(error_code::INVALID_UPDATE_VERSION)
+    );
+    assert_eq!(
+        error(METADATA_VERSION, IBP_3_9_IV0 + 1, UPGRADE),
+        Some(error_code::INVALID_UPDATE_VERSION)
+    );
+    assert_eq!(
+        error("group.version", 1, UPGRADE),
+        Some(error_code::INVALID_UPDATE_VERSION)
+    );
+    assert_eq!(error("group.version", 0, UPGRADE), None);
+    assert_eq!(
+        error(METADATA_VERSION, 17, 4),
+        Some(error_code::INVALID_REQUEST)
+    );
+}
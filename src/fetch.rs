@@ -0,0 +1,348 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::partition_log::AbortedTransaction;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Fetch sessions are not kept, so every fetch is a full one
+const NO_SESSION: i32 = 0;
+const READ_COMMITTED: i8 = 1;
+// How often a fetch waiting for min_bytes reads the logs again
+const FETCH_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+struct FetchPartition {
+    partition: i32,
+    fetch_offset: i64,
+    partition_max_bytes: i32,
+}
+
+#[derive(Debug)]
+struct FetchTopic {
+    topic_id: [u8; 16],
+    // None when the topic id is unknown, from v13 on topics are named by id
+    name: Option<String>,
+    partitions: Vec<FetchPartition>,
+}
+
+#[derive(Debug)]
+struct PartitionData {
+    high_watermark: i64,
+    last_stable_offset: i64,
+    log_start_offset: i64,
+    // None for read-uncommitted fetches
+    aborted_transactions: Option<Vec<AbortedTransaction>>,
+    records: Vec<u8>,
+}
+
+// Reads whole batches from the fetch offset up to the high watermark, or up
+// to the last stable offset for read-committed fetches. A first batch larger
+// than `max_bytes` is still returned when `min_one`, so consumers make progress.
+fn read_partition(
+    broker: &Broker,
+    topic: &str,
+    request: &FetchPartition,
+    read_committed: bool,
+    max_bytes: usize,
+    min_one: bool,
+) -> Result<PartitionData, i16> {
+    let log = broker
+        .partition_log(topic, request.partition)
+        .ok_or(error_code::UNKNOWN_TOPIC_OR_PARTITION)?;
+    let storage_error = |_| error_code::KAFKA_STORAGE_ERROR;
+
+    // Without replication the high watermark is the log end offset
+    let high_watermark = log.log_end_offset().map_err(storage_error)?;
+    let last_stable_offset = log.last_stable_offset().map_err(storage_error)?;
+    let log_start_offset = log.log_start_offset().map_err(storage_error)?;
+    if request.fetch_offset < log_start_offset || request.fetch_offset > high_watermark {
+        return Err(error_code::OFFSET_OUT_OF_RANGE);
+    }
+
+    let upper_bound = if read_committed {
+        last_stable_offset
+    } else {
+        high_watermark
+    };
+    let max_bytes = max_bytes.min(request.partition_max_bytes.max(0) as usize);
+    let mut records = vec![];
+    let mut next_offset = request.fetch_offset;
+    for record_batch in log
+        .read_batches_from(request.fetch_offset)
+        .map_err(storage_error)?
+    {
+        if record_batch.last_offset() >= upper_bound {
+            break;
+        }
+        let encoded = record_batch.encode();
+        if (!min_one || !records.is_empty()) && records.len() + encoded.len() > max_bytes {
+            break;
+        }
+        records.extend(encoded);
+        next_offset = record_batch.last_offset() + 1;
+    }
+
+    let aborted_transactions = if read_committed {
+        Some(
+            log.aborted_transactions(request.fetch_offset, next_offset)
+                .map_err(storage_error)?,
+        )
+    } else {
+        None
+    };
+    Ok(PartitionData {
+        high_watermark,
+        last_stable_offset,
+        log_start_offset,
+        aborted_transactions,
+        records,
+    })
+}
+
+fn fetch(
+    broker: &Broker,
+    session: &Session,
+    topics: &[FetchTopic],
+    read_committed: bool,
+    max_bytes: usize,
+) -> Vec<Vec<Result<PartitionData, i16>>> {
+    let mut bytes = 0;
+    let mut responses = vec![];
+    for topic in topics {
+        let authorized = topic
+            .name
+            .as_ref()
+            .map(|name| broker.authorize(session, AclOperation::Read, ResourceType::Topic, name));
+        let mut partitions = vec![];
+        for partition in &topic.partitions {
+            let result = match (&topic.name, authorized) {
+                (Some(name), Some(true)) => read_partition(
+                    broker,
+                    name,
+                    partition,
+                    read_committed,
+                    max_bytes.saturating_sub(bytes),
+                    bytes == 0,
+                ),
+                (Some(_), _) => Err(error_code::TOPIC_AUTHORIZATION_FAILED),
+                (None, _) => Err(error_code::UNKNOWN_TOPIC_ID),
+            };
+            if let Ok(data) = &result {
+                bytes += data.records.len();
+            }
+            partitions.push(result);
+        }
+        responses.push(partitions);
+    }
+    responses
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    if header.api_version < 15 {
+        let _replica_id = input.get_i32();
+    }
+    let max_wait_ms = input.get_i32();
+    let min_bytes = input.get_i32();
+    let max_bytes = input.get_i32();
+    let isolation_level = input.get_i8();
+    let session_id = input.get_i32();
+    let _session_epoch = input.get_i32();
+    let metadata = broker.metadata();
+    let topics_length = input.get_compact_array_length();
+    let topics: Vec<FetchTopic> = (0..topics_length)
+        .map(|_| {
+            let (topic_id, name) = if header.api_version >= 13 {
+                let mut topic_id = [0; 16];
+                input.copy_to_slice(&mut topic_id);
+                (topic_id, metadata.topic_name(topic_id).map(str::to_string))
+            } else {
+                let name = input.get_compact_string();
+                (metadata.topic_id(&name).unwrap_or_default(), Some(name))
+            };
+            let partitions_length = input.get_compact_array_length();
+            let partitions = (0..partitions_length)
+                .map(|_| {
+                    let partition = input.get_i32();
+                    let _current_leader_epoch = input.get_i32();
+                    let fetch_offset = input.get_i64();
+                    let _last_fetched_epoch = input.get_i32();
+                    let _log_start_offset = input.get_i64();
+                    let partition_max_bytes = input.get_i32();
+                    input.skip_tagged_fields();
+                    FetchPartition {
+                        partition,
+                        fetch_offset,
+                        partition_max_bytes,
+                    }
+                })
+                .collect();
+            input.skip_tagged_fields();
+            FetchTopic {
+                topic_id,
+                name,
+                partitions,
+            }
+        })
+        .collect();
+    // Forgotten topics only matter to fetch sessions
+    for _ in 0..input.get_compact_array_length() {
+        if header.api_version >= 13 {
+            input.advance(16);
+        } else {
+            input.get_compact_string();
+        }
+        for _ in 0..input.get_compact_array_length() {
+            input.get_i32();
+        }
+        input.skip_tagged_fields();
+    }
+    let _rack_id = input.get_compact_string();
+    input.skip_tagged_fields();
+    drop(metadata);
+
+    let error_code = if session_id == NO_SESSION {
+        error_code::NONE
+    } else {
+        error_code::FETCH_SESSION_ID_NOT_FOUND
+    };
+    let read_committed = isolation_level == READ_COMMITTED;
+    // Reads again until min_bytes are available or max_wait_ms is up
+    let deadline = Instant::now() + Duration::from_millis(max_wait_ms.max(0) as u64);
+    let responses = loop {
+        if error_code != error_code::NONE {
+            break vec![];
+        }
+        let responses = fetch(broker, session, &topics, read_committed, max_bytes as usize);
+        let bytes: usize = responses
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|data| data.records.len())
+            .sum();
+        if bytes >= min_bytes.max(0) as usize || Instant::now() >= deadline {
+            break responses;
+        }
+        thread::sleep(FETCH_POLL_INTERVAL);
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_i16(error_code);
+    body.put_i32(NO_SESSION);
+    body.put_compact_array_length(responses.len());
+    for (topic, partitions) in topics.iter().zip(responses) {
+        if header.api_version >= 13 {
+            body.extend_from_slice(&topic.topic_id);
+        } else {
+            body.put_compact_string(topic.name.as_deref().unwrap_or_default());
+        }
+        body.put_compact_array_length(partitions.len());
+        for (request, result) in topic.partitions.iter().zip(partitions) {
+            body.put_i32(request.partition);
+            match result {
+                Ok(data) => {
+                    body.put_i16(error_code::NONE);
+                    body.put_i64(data.high_watermark);
+                    body.put_i64(data.last_stable_offset);
+                    body.put_i64(data.log_start_offset);
+                    match data.aborted_transactions {
+                        Some(aborted_transactions) => {
+                            body.put_compact_array_length(aborted_transactions.len());
+                            for aborted in aborted_transactions {
+                                body.put_i64(aborted.producer_id);
+                                body.put_i64(aborted.first_offset);
+                                body.put_empty_tagged_fields();
+                            }
+                        }
+                        None => body.put_u8(0), // Null aborted transactions
+                    }
+                    body.put_i32(-1); // Preferred read replica
+                    body.put_compact_bytes(Some(&data.records));
+                }
+                Err(error_code) => {
+                    body.put_i16(error_code);
+                    body.put_i64(-1); // High watermark
+                    body.put_i64(-1); // Last stable offset
+                    body.put_i64(-1); // Log start offset
+                    body.put_u8(0); // Null aborted transactions
+                    body.put_i32(-1); // Preferred read replica
+                    body.put_compact_bytes(Some(&[]));
+                }
+            }
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_read_committed_stops_at_the_last_stable_offset() {
+    use crate::partition_log::PartitionLog;
+    use crate::record_batch::{RecordBatch, ABORT_MARKER};
+    use crate::test_util::{broker_with_topics, data_batch, transactional_batch};
+
+    let (log_dir, broker) = broker_with_topics("fetch-lso", "", &[("events", [1; 16], 1)]);
+    let log = PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+    log.append(transactional_batch(1)).unwrap();
+    log.append(RecordBatch::control(1, 0, ABORT_MARKER, 0))
+        .unwrap();
+    log.append(transactional_batch(2)).unwrap();
+    log.append(data_batch()).unwrap();
+
+    let base_offsets = |mut records: &[u8]| {
+        let mut base_offsets = vec![];
+        while let Ok((record_batch, rest)) = RecordBatch::parse(records) {
+            base_offsets.push(record_batch.base_offset);
+            records = rest;
+        }
+        base_offsets
+    };
+    let request = FetchPartition {
+        partition: 0,
+        fetch_offset: 0,
+        partition_max_bytes: i32::MAX,
+    };
+    let committed = read_partition(&broker, "events", &request, true, usize::MAX, true).unwrap();
+    assert_eq!(committed.high_watermark, 4);
+    assert_eq!(committed.last_stable_offset, 2);
+    assert_eq!(
+        committed.aborted_transactions,
+        Some(vec![AbortedTransaction {
+            producer_id: 1,
+            first_offset: 0,
+            last_offset: 1,
+        }])
+    );
+    assert_eq!(base_offsets(&committed.records), vec![0, 1]);
+
+    let uncommitted = read_partition(&broker, "events", &request, false, usize::MAX, true).unwrap();
+    assert_eq!(uncommitted.aborted_transactions, None);
+    assert_eq!(base_offsets(&uncommitted.records), vec![0, 1, 2, 3]);
+
+    // Only the first batch fits, and only because nothing else was read
+    let limited = read_partition(&broker, "events", &request, false, 1, true).unwrap();
+    assert_eq!(base_offsets(&limited.records), vec![0]);
+    let limited = read_partition(&broker, "events", &request, false, 1, false).unwrap();
+    assert!(limited.records.is_empty());
+
+    let out_of_range = FetchPartition {
+        fetch_offset: 5,
+        ..request
+    };
+    assert_eq!(
+        read_partition(&broker, "events", &out_of_range, false, usize::MAX, true).unwrap_err(),
+        error_code::OFFSET_OUT_OF_RANGE
+    );
+}
@@ -0,0 +1,60 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+const GROUP_KEY_TYPE: i8 = 0;
+const TRANSACTION_KEY_TYPE: i8 = 1;
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let key_type = input.get_i8();
+    let key_count = input.get_compact_array_length();
+    let keys: Vec<String> = (0..key_count).map(|_| input.get_compact_string()).collect();
+    input.skip_tagged_fields();
+
+    // This broker coordinates every group and transaction itself
+    let (host, port) = broker.config.advertised_endpoint(&session.listener_name);
+    let key_error_code = |key: &str| match key_type {
+        GROUP_KEY_TYPE
+            if !broker.authorize(session, AclOperation::Describe, ResourceType::Group, key) =>
+        {
+            error_code::GROUP_AUTHORIZATION_FAILED
+        }
+        TRANSACTION_KEY_TYPE
+            if !broker.authorize(
+                session,
+                AclOperation::Describe,
+                ResourceType::TransactionalId,
+                key,
+            ) =>
+        {
+            error_code::TRANSACTIONAL_ID_AUTHORIZATION_FAILED
+        }
+        GROUP_KEY_TYPE | TRANSACTION_KEY_TYPE => error_code::NONE,
+        _ => error_code::COORDINATOR_NOT_AVAILABLE,
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(keys.len());
+    for key in keys {
+        let error_code = key_error_code(&key);
+        body.put_compact_string(&key);
+        body.put_i32(broker.config.node_id());
+        body.put_compact_string(&host);
+        body.put_i32(port);
+        body.put_i16(error_code);
+        body.put_compact_nullable_string(None); // Error message
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
@@ -0,0 +1,92 @@
+use crate::acl::AclOperation;
+use crate::broker::Broker;
+use crate::dynamic_config;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+#[derive(Debug)]
+struct AlterConfigsResource {
+    resource_type: i8,
+    resource_name: String,
+    configs: Vec<(String, i8, Option<String>)>,
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let resources_length = input.get_compact_array_length();
+    let resources: Vec<AlterConfigsResource> = (0..resources_length)
+        .map(|_| {
+            let resource_type = input.get_i8();
+            let resource_name = input.get_compact_string();
+            let configs_length = input.get_compact_array_length();
+            let configs = (0..configs_length)
+                .map(|_| {
+                    let name = input.get_compact_string();
+                    let config_operation = input.get_i8();
+                    let value = input.get_compact_nullable_string();
+                    input.skip_tagged_fields();
+                    (name, config_operation, value)
+                })
+                .collect();
+            input.skip_tagged_fields();
+            AlterConfigsResource {
+                resource_type,
+                resource_name,
+                configs,
+            }
+        })
+        .collect();
+    let validate_only = input.get_u8() != 0;
+    input.skip_tagged_fields();
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(resources.len());
+    for resource in resources {
+        let result = dynamic_config::authorize(
+            broker,
+            session,
+            AclOperation::AlterConfigs,
+            resource.resource_type,
+            &resource.resource_name,
+        )
+        .and_then(|()| {
+            let metadata = broker.metadata();
+            dynamic_config::incremental_alter(
+                resource.resource_type,
+                &resource.resource_name,
+                &resource.configs,
+                &metadata,
+                &broker.config,
+            )
+        })
+        .and_then(|records| {
+            if validate_only {
+                return Ok(());
+            }
+            let records = records.iter().map(|record| record.encode()).collect();
+            broker
+                .append_metadata(records)
+                .map_err(|e| (error_code::KAFKA_STORAGE_ERROR, e.to_string()))
+        });
+        let (error_code, error_message) = match result {
+            Ok(()) => (error_code::NONE, None),
+            Err((error_code, error_message)) => (error_code, Some(error_message)),
+        };
+
+        body.put_i16(error_code);
+        body.put_compact_nullable_string(error_message.as_deref());
+        body.put_i8(resource.resource_type);
+        body.put_compact_string(&resource.resource_name);
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
@@ -0,0 +1,61 @@
+use crate::acl::{AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let transactional_id = input.get_compact_nullable_string();
+    let transaction_timeout_ms = input.get_i32();
+    let (producer_id, producer_epoch) = if header.api_version >= 3 {
+        (input.get_i64(), input.get_i16())
+    } else {
+        (-1, -1)
+    };
+    input.skip_tagged_fields();
+
+    // Transactional producers need WRITE on their id, idempotent ones on the cluster
+    let authorized = match &transactional_id {
+        Some(transactional_id) => broker.authorize(
+            session,
+            AclOperation::Write,
+            ResourceType::TransactionalId,
+            transactional_id,
+        ),
+        None => broker.authorize(
+            session,
+            AclOperation::IdempotentWrite,
+            ResourceType::Cluster,
+            CLUSTER_NAME,
+        ),
+    };
+    let result = match (&transactional_id, authorized) {
+        (Some(_), false) => Err(error_code::TRANSACTIONAL_ID_AUTHORIZATION_FAILED),
+        (None, false) => Err(error_code::CLUSTER_AUTHORIZATION_FAILED),
+        _ => broker.init_producer_id(
+            transactional_id.as_deref(),
+            transaction_timeout_ms,
+            producer_id,
+            producer_epoch,
+        ),
+    };
+    let (error_code, producer_id, producer_epoch) = match result {
+        Ok((producer_id, producer_epoch)) => (error_code::NONE, producer_id, producer_epoch),
+        Err(error_code) => (error_code, -1, -1),
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_i16(error_code);
+    body.put_i64(producer_id);
+    body.put_i16(producer_epoch);
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
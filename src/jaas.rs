@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+const SERVER_SECTION: &str = "KafkaServer";
+const PLAIN_LOGIN_MODULE: &str = "PlainLoginModule";
+
+// Splits a login module entry into whitespace separated tokens, keeping
+// quoted option values together and unquoting them
+fn tokens(entry: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in entry.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+// Users and passwords of the PLAIN login module in the KafkaServer section,
+// given as `user_<name>="<password>"` options
+pub fn plain_users(contents: &str) -> HashMap<String, String> {
+    let section = contents
+        .find(SERVER_SECTION)
+        .map(|start| &contents[start + SERVER_SECTION.len()..])
+        .and_then(|section| section.trim_start().strip_prefix('{'))
+        .map(|section| section.split_once('}').map_or(section, |(body, _)| body))
+        .unwrap_or_default();
+
+    section
+        .split(';')
+        .map(tokens)
+        .filter(|tokens| {
+            tokens
+                .first()
+                .is_some_and(|module| module.ends_with(PLAIN_LOGIN_MODULE))
+        })
+        .flat_map(|tokens| tokens.into_iter().skip(2))
+        .filter_map(|option| {
+            let (key, value) = option.split_once('=')?;
+            Some((key.strip_prefix("user_")?.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[test]
+fn test_plain_users() {
+    let users = plain_users(
+        "KafkaClient {\n  PlainLoginModule required user_client=\"nope\";\n};\n\
+         KafkaServer {\n  org.apache.kafka.common.security.plain.PlainLoginModule required\n  \
+         username=\"admin\"\n  password=\"admin-secret\"\n  user_admin=\"admin-secret\"\n  \
+         user_alice=\"alice secret\";\n};\n",
+    );
+    assert_eq!(
+        users,
+        HashMap::from([
+            ("admin".to_string(), "admin-secret".to_string()),
+            ("alice".to_string(), "alice secret".to_string()),
+        ])
+    );
+}
@@ -0,0 +1,179 @@
+use crate::checkpoint;
+
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+pub const UNDEFINED_EPOCH: i32 = -1;
+pub const UNDEFINED_EPOCH_OFFSET: i64 = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EpochEntry {
+    pub epoch: i32,
+    pub start_offset: i64,
+}
+
+// First offset written under each leader epoch of a partition, persisted
+// in the partition's leader-epoch-checkpoint file
+#[derive(Debug)]
+pub struct LeaderEpochCache {
+    path: PathBuf,
+    entries: Vec<EpochEntry>,
+}
+
+impl LeaderEpochCache {
+    pub fn new(path: PathBuf) -> LeaderEpochCache {
+        LeaderEpochCache {
+            path,
+            entries: vec![],
+        }
+    }
+
+    pub fn load(path: PathBuf) -> io::Result<LeaderEpochCache> {
+        let entries = checkpoint::read(&path)?
+            .into_iter()
+            .map(|entry| {
+                let malformed = || {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Malformed leader epoch entry {:?}", entry),
+                    )
+                };
+                match entry.as_slice() {
+                    [epoch, start_offset] => Ok(EpochEntry {
+                        epoch: epoch.parse().map_err(|_| malformed())?,
+                        start_offset: start_offset.parse().map_err(|_| malformed())?,
+                    }),
+                    _ => Err(malformed()),
+                }
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(LeaderEpochCache { path, entries })
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| format!("{} {}", entry.epoch, entry.start_offset))
+            .collect();
+        checkpoint::write(&self.path, &entries)
+    }
+
+    pub fn latest_epoch(&self) -> Option<i32> {
+        self.entries.last().map(|entry| entry.epoch)
+    }
+
+    // Records the start of a new epoch, returning whether the cache changed.
+    // Batches from older epochs or without an epoch don't start a new one.
+    pub fn assign(&mut self, epoch: i32, start_offset: i64) -> bool {
+        if epoch < 0 || self.latest_epoch().is_some_and(|latest| epoch <= latest) {
+            return false;
+        }
+        self.entries.push(EpochEntry {
+            epoch,
+            start_offset,
+        });
+        true
+    }
+
+    // The largest epoch up to `requested_epoch` and the offset where it ended,
+    // which is the start of the next epoch or the log end for the latest one
+    pub fn end_offset_for(&self, requested_epoch: i32, log_end_offset: i64) -> (i32, i64) {
+        if requested_epoch == UNDEFINED_EPOCH {
+            return (UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET);
+        }
+        if self.latest_epoch() == Some(requested_epoch) {
+            return (requested_epoch, log_end_offset);
+        }
+
+        let higher = self
+            .entries
+            .iter()
+            .find(|entry| entry.epoch > requested_epoch);
+        let floor = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.epoch <= requested_epoch);
+        match (floor, higher) {
+            (_, None) => (UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET),
+            (None, Some(higher)) => (requested_epoch, higher.start_offset),
+            (Some(floor), Some(higher)) => (floor.epoch, higher.start_offset),
+        }
+    }
+
+    // Drops the epochs that ended before the new log start offset, moving the
+    // start of the epoch that contains it up to that offset
+    pub fn truncate_from_start(&mut self, start_offset: i64) -> bool {
+        let below = self
+            .entries
+            .iter()
+            .take_while(|entry| entry.start_offset < start_offset)
+            .count();
+        if below == 0 {
+            return false;
+        }
+        let containing = self.entries[below - 1];
+        self.entries.drain(..below);
+        if self
+            .entries
+            .first()
+            .map_or(true, |entry| entry.start_offset > start_offset)
+        {
+            self.entries.insert(
+                0,
+                EpochEntry {
+                    epoch: containing.epoch,
+                    start_offset,
+                },
+            );
+        }
+        true
+    }
+
+    // Drops the epochs that start at or after the new log end offset
+    pub fn truncate_from_end(&mut self, end_offset: i64) -> bool {
+        let kept = self
+            .entries
+            .iter()
+            .take_while(|entry| entry.start_offset < end_offset)
+            .count();
+        let truncated = kept < self.entries.len();
+        self.entries.truncate(kept);
+        truncated
+    }
+}
+
+#[test]
+fn test_end_offset_for() {
+    let mut cache = LeaderEpochCache::new(PathBuf::new());
+    assert_eq!(cache.end_offset_for(0, 10), (-1, -1));
+
+    assert!(cache.assign(1, 0));
+    assert!(!cache.assign(1, 5));
+    assert!(cache.assign(3, 5));
+    assert!(cache.assign(4, 8));
+
+    assert_eq!(cache.end_offset_for(0, 10), (0, 0));
+    assert_eq!(cache.end_offset_for(1, 10), (1, 5));
+    assert_eq!(cache.end_offset_for(2, 10), (1, 5));
+    assert_eq!(cache.end_offset_for(3, 10), (3, 8));
+    assert_eq!(cache.end_offset_for(4, 10), (4, 10));
+    assert_eq!(cache.end_offset_for(5, 10), (-1, -1));
+
+    assert!(cache.truncate_from_start(6));
+    assert_eq!(
+        cache.entries,
+        vec![
+            EpochEntry {
+                epoch: 3,
+                start_offset: 6
+            },
+            EpochEntry {
+                epoch: 4,
+                start_offset: 8
+            }
+        ]
+    );
+    assert!(!cache.truncate_from_start(6));
+}
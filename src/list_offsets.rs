@@ -0,0 +1,228 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::leader_epoch_cache::UNDEFINED_EPOCH;
+use crate::offset_for_leader_epoch::check_current_leader_epoch;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+// Timestamps asking for a special offset rather than a lookup by time
+const LATEST_TIMESTAMP: i64 = -1;
+const EARLIEST_TIMESTAMP: i64 = -2;
+const MAX_TIMESTAMP: i64 = -3;
+// Without tiered storage the earliest local offset is the earliest one
+const EARLIEST_LOCAL_TIMESTAMP: i64 = -4;
+const READ_COMMITTED: i8 = 1;
+
+#[derive(Debug)]
+struct ListOffsetsPartition {
+    partition: i32,
+    current_leader_epoch: i32,
+    timestamp: i64,
+}
+
+#[derive(Debug)]
+struct ListOffsetsTopic {
+    name: String,
+    partitions: Vec<ListOffsetsPartition>,
+}
+
+#[derive(Debug)]
+struct ListedOffset {
+    timestamp: i64,
+    offset: i64,
+    leader_epoch: i32,
+}
+
+// Returned when no record has a timestamp at or after the one asked for
+const NO_OFFSET: ListedOffset = ListedOffset {
+    timestamp: -1,
+    offset: -1,
+    leader_epoch: UNDEFINED_EPOCH,
+};
+
+fn list_offset(
+    broker: &Broker,
+    topic: &str,
+    request: &ListOffsetsPartition,
+    read_committed: bool,
+) -> Result<ListedOffset, i16> {
+    let log = broker
+        .partition_log(topic, request.partition)
+        .ok_or(error_code::UNKNOWN_TOPIC_OR_PARTITION)?;
+    check_current_leader_epoch(
+        broker,
+        topic,
+        request.partition,
+        request.current_leader_epoch,
+    )?;
+    let storage_error = |_| error_code::KAFKA_STORAGE_ERROR;
+
+    let log_start_offset = log.log_start_offset().map_err(storage_error)?;
+    // Read-committed clients only see up to the last stable offset
+    let upper_bound = if read_committed {
+        log.last_stable_offset()
+    } else {
+        log.log_end_offset()
+    }
+    .map_err(storage_error)?;
+    let latest_epoch = log
+        .leader_epoch_cache()
+        .map_err(storage_error)?
+        .latest_epoch()
+        .unwrap_or(UNDEFINED_EPOCH);
+    if request.timestamp == LATEST_TIMESTAMP {
+        return Ok(ListedOffset {
+            timestamp: -1,
+            offset: upper_bound,
+            leader_epoch: latest_epoch,
+        });
+    }
+
+    let record_batches: Vec<_> = log
+        .read_batches_from(log_start_offset)
+        .map_err(storage_error)?
+        .into_iter()
+        .filter(|record_batch| record_batch.last_offset() < upper_bound)
+        .collect();
+    if matches!(
+        request.timestamp,
+        EARLIEST_TIMESTAMP | EARLIEST_LOCAL_TIMESTAMP
+    ) {
+        return Ok(ListedOffset {
+            timestamp: -1,
+            offset: log_start_offset,
+            leader_epoch: record_batches.first().map_or(latest_epoch, |record_batch| {
+                record_batch.partition_leader_epoch
+            }),
+        });
+    }
+
+    // Records deleted by DeleteRecords may still share a batch with live ones
+    let records = record_batches.iter().flat_map(|record_batch| {
+        record_batch
+            .records
+            .iter()
+            .map(move |record| ListedOffset {
+                timestamp: record.timestamp(record_batch),
+                offset: record.offset(record_batch),
+                leader_epoch: record_batch.partition_leader_epoch,
+            })
+            .filter(|record| record.offset >= log_start_offset)
+    });
+    let listed = if request.timestamp == MAX_TIMESTAMP {
+        records.fold(None, |max: Option<ListedOffset>, record| match max {
+            Some(max) if max.timestamp >= record.timestamp => Some(max),
+            _ => Some(record),
+        })
+    } else {
+        records
+            .filter(|record| record.timestamp >= request.timestamp)
+            .min_by_key(|record| record.offset)
+    };
+    Ok(listed.unwrap_or(NO_OFFSET))
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let _replica_id = input.get_i32();
+    let isolation_level = input.get_i8();
+    let topics_length = input.get_compact_array_length();
+    let topics: Vec<ListOffsetsTopic> = (0..topics_length)
+        .map(|_| {
+            let name = input.get_compact_string();
+            let partitions_length = input.get_compact_array_length();
+            let partitions = (0..partitions_length)
+                .map(|_| {
+                    let partition = input.get_i32();
+                    let current_leader_epoch = input.get_i32();
+                    let timestamp = input.get_i64();
+                    input.skip_tagged_fields();
+                    ListOffsetsPartition {
+                        partition,
+                        current_leader_epoch,
+                        timestamp,
+                    }
+                })
+                .collect();
+            input.skip_tagged_fields();
+            ListOffsetsTopic { name, partitions }
+        })
+        .collect();
+    input.skip_tagged_fields();
+
+    // Serialize result
+    let read_committed = isolation_level == READ_COMMITTED;
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(topics.len());
+    for topic in topics {
+        let authorized = broker.authorize(
+            session,
+            AclOperation::Describe,
+            ResourceType::Topic,
+            &topic.name,
+        );
+        body.put_compact_string(&topic.name);
+        body.put_compact_array_length(topic.partitions.len());
+        for partition in topic.partitions {
+            let result = if authorized {
+                list_offset(broker, &topic.name, &partition, read_committed)
+            } else {
+                Err(error_code::TOPIC_AUTHORIZATION_FAILED)
+            };
+            let listed = result.as_ref().unwrap_or(&NO_OFFSET);
+            body.put_i32(partition.partition);
+            body.put_i16(result.as_ref().err().copied().unwrap_or(error_code::NONE));
+            body.put_i64(listed.timestamp);
+            body.put_i64(listed.offset);
+            body.put_i32(listed.leader_epoch);
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_earliest_offset_follows_delete_records() {
+    use crate::partition_log::PartitionLog;
+    use crate::record_batch::{Record, RecordBatch};
+    use crate::test_util::{broker_with_topics, data_batch};
+
+    let (log_dir, broker) = broker_with_topics("list-offsets", "", &[("events", [1; 16], 1)]);
+    let log = PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+    for timestamp in [100, 300, 200] {
+        let records = vec![Record::new(0, None, Some(b"data".to_vec()))];
+        log.append(RecordBatch::new(0, -1, -1, timestamp, records))
+            .unwrap();
+    }
+    log.append(data_batch()).unwrap();
+    let list = |timestamp| {
+        let request = ListOffsetsPartition {
+            partition: 0,
+            current_leader_epoch: -1,
+            timestamp,
+        };
+        list_offset(&broker, "events", &request, false).map(|listed| listed.offset)
+    };
+
+    assert_eq!(list(EARLIEST_TIMESTAMP), Ok(0));
+    assert_eq!(list(LATEST_TIMESTAMP), Ok(4));
+    assert_eq!(list(MAX_TIMESTAMP), Ok(1));
+    assert_eq!(list(150), Ok(1));
+    assert_eq!(list(300), Ok(1));
+    assert_eq!(list(301), Ok(-1));
+
+    log.delete_records_before(2).unwrap();
+    assert_eq!(list(EARLIEST_TIMESTAMP), Ok(2));
+    assert_eq!(list(EARLIEST_LOCAL_TIMESTAMP), Ok(2));
+    assert_eq!(list(MAX_TIMESTAMP), Ok(2));
+    assert_eq!(list(150), Ok(2));
+}
@@ -1,56 +1,321 @@
+mod acl;
+mod add_offsets_to_txn;
+mod add_partitions_to_txn;
+mod alter_client_quotas;
+mod alter_configs;
 mod api_version;
+mod authorizer;
+mod broker;
+mod checkpoint;
 mod cluser_metadata;
+mod compression;
+mod config;
+mod control_record;
+mod create_acls;
+mod delete_acls;
+mod delete_records;
+mod describe_acls;
+mod describe_client_quotas;
+mod describe_cluster;
+mod describe_configs;
+mod describe_log_dirs;
 mod describe_topic;
+mod dynamic_config;
+mod end_txn;
+mod error_code;
+mod feature;
+mod fetch;
+mod find_coordinator;
+mod incremental_alter_configs;
+mod init_producer_id;
+mod jaas;
+mod leader_epoch_cache;
+mod list_offsets;
+mod meta_properties;
+mod metadata;
+mod metadata_image;
+mod offset_for_leader_epoch;
+mod partition_log;
+mod produce;
+mod protocol;
+mod quota;
+mod record_batch;
+mod sasl;
+mod sasl_authenticate;
+mod sasl_handshake;
+mod session;
+mod ssl_principal_mapper;
+#[cfg(test)]
+mod test_util;
+mod tls;
+mod transaction_coordinator;
+mod txn_offset_commit;
+mod update_features;
 mod varint;
+mod write_txn_markers;
 
-use std::io::{Read, Write};
+use broker::Broker;
+use config::BrokerConfig;
+use protocol::RequestHeader;
+use session::Session;
+use tls::TlsAcceptor;
+
+use std::io::{ErrorKind, Read, Write};
 use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
-fn handle_connection(mut stream: TcpStream) {
-    let mut input: [u8; 512] = [0; 512];
+const PRODUCE: i16 = 0;
+const FETCH: i16 = 1;
 
-    loop {
-        let result = stream.read(&mut input);
-        match result {
-            Ok(0) => break,
-            Ok(_) => {
-                // Parse data
-                let api_key = i16::from_be_bytes(input[4..6].try_into().unwrap());
-
-                let result = match api_key {
-                    18 => api_version::handle_request(&input),
-                    75 => describe_topic::handle_request(&input),
-                    _ => {
-                        println!("Error processing unknown API Key");
-                        break;
-                    }
-                };
-
-                match stream.write_all(&result) {
-                    Err(e) => {
-                        println!("Error writing to stream: {}", e);
-                        break;
-                    }
-                    Ok(_) => {}
-                }
+// Api key, api version, correlation id and client id length, which every
+// request header starts with
+const MIN_REQUEST_SIZE: u32 = 10;
+
+// Reads one size-prefixed request, keeping the size in front like it is on
+// the wire. A size past `max_bytes` is rejected before anything is allocated.
+fn read_request(stream: &mut impl Read, max_bytes: u32) -> std::io::Result<Vec<u8>> {
+    let mut message_size = [0; 4];
+    stream.read_exact(&mut message_size)?;
+    let size = u32::from_be_bytes(message_size);
+    if !(MIN_REQUEST_SIZE..=max_bytes).contains(&size) {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Invalid request size {}, socket.request.max.bytes is {}",
+                size, max_bytes
+            ),
+        ));
+    }
+    let mut input = vec![0; 4 + size as usize];
+    input[..4].copy_from_slice(&message_size);
+    stream.read_exact(&mut input[4..])?;
+    Ok(input)
+}
+
+// What connections accepted by one of the broker's listeners share
+struct Listener {
+    name: String,
+    sasl: bool,
+    tls: Option<TlsAcceptor>,
+}
+
+impl Listener {
+    fn new(config: &BrokerConfig, name: &str) -> std::io::Result<Listener> {
+        let security_protocol = config.security_protocol(name);
+        let tls = match security_protocol.as_str() {
+            "SSL" | "SASL_SSL" => Some(TlsAcceptor::new(config, name)?),
+            _ => None,
+        };
+        Ok(Listener {
+            name: name.to_string(),
+            sasl: security_protocol.starts_with("SASL_"),
+            tls,
+        })
+    }
+}
+
+fn handle_connection(stream: TcpStream, broker: Arc<Broker>, listener: Arc<Listener>) {
+    let client_host = stream
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default();
+    let mut session = Session::new(client_host, listener.name.clone(), listener.sasl);
+
+    match &listener.tls {
+        Some(tls) => match tls.accept(stream) {
+            Ok((stream, principal)) => {
+                session.principal = principal;
+                serve(stream, &broker, session);
             }
+            Err(e) => println!("Error in TLS handshake: {}", e),
+        },
+        None => serve(stream, &broker, session),
+    }
+}
+
+// How long to throttle a client for: the longest of what its request quota
+// and, for Produce and Fetch, its byte rate quota call for
+fn throttle_time_ms(broker: &Broker, api_key: i16, user: &str, client_id: &str) -> i32 {
+    let byte_rate = match api_key {
+        PRODUCE => Some(quota::PRODUCER_BYTE_RATE),
+        FETCH => Some(quota::CONSUMER_BYTE_RATE),
+        _ => None,
+    };
+    let request_throttle_time_ms =
+        broker
+            .quotas
+            .throttle_time_ms(quota::REQUEST_PERCENTAGE, user, client_id);
+    let byte_throttle_time_ms = byte_rate.map_or(0, |key| {
+        broker.quotas.throttle_time_ms(key, user, client_id)
+    });
+    request_throttle_time_ms.max(byte_throttle_time_ms)
+}
+
+fn serve(mut stream: impl Read + Write, broker: &Broker, mut session: Session) {
+    let max_bytes = broker.config.socket_request_max_bytes();
+    loop {
+        let input = match read_request(&mut stream, max_bytes) {
+            Ok(input) => input,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
             Err(e) => {
                 println!("Error reading from connection: {}", e);
+                break;
             }
+        };
+
+        // Parse data
+        let Some(header) = RequestHeader::try_parse_v1(&mut &input[..]) else {
+            println!("Error reading request header");
+            break;
+        };
+        let api_key = header.api_key;
+        if !session.allows(api_key) {
+            println!(
+                "Unexpected request with API Key {} before authentication",
+                api_key
+            );
+            break;
+        }
+
+        // ApiVersions and the SASL exchange aren't subject to request quotas
+        let quota_exempt = matches!(api_key, 17 | 18 | 36);
+        let client_id = header.client_id.unwrap_or_default();
+        // Produce bytes are known up front, so they already count towards
+        // the throttle time of this response
+        if api_key == PRODUCE {
+            broker.quotas.record(
+                quota::PRODUCER_BYTE_RATE,
+                session.user(),
+                &client_id,
+                input.len() as f64,
+            );
+        }
+        session.throttle_time_ms = if quota_exempt {
+            0
+        } else {
+            throttle_time_ms(broker, api_key, session.user(), &client_id)
+        };
+        let started = Instant::now();
+
+        let result = match api_key {
+            0 => produce::handle_request(&input, broker, &session),
+            1 => fetch::handle_request(&input, broker, &session),
+            2 => list_offsets::handle_request(&input, broker, &session),
+            3 => metadata::handle_request(&input, broker, &session),
+            10 => find_coordinator::handle_request(&input, broker, &session),
+            17 => sasl_handshake::handle_request(&input, broker, &mut session),
+            18 => api_version::handle_request(&input, broker),
+            21 => delete_records::handle_request(&input, broker, &session),
+            22 => init_producer_id::handle_request(&input, broker, &session),
+            23 => offset_for_leader_epoch::handle_request(&input, broker, &session),
+            24 => add_partitions_to_txn::handle_request(&input, broker, &session),
+            25 => add_offsets_to_txn::handle_request(&input, broker, &session),
+            26 => end_txn::handle_request(&input, broker, &session),
+            27 => write_txn_markers::handle_request(&input, broker, &session),
+            28 => txn_offset_commit::handle_request(&input, broker, &session),
+            29 => describe_acls::handle_request(&input, broker, &session),
+            30 => create_acls::handle_request(&input, broker, &session),
+            31 => delete_acls::handle_request(&input, broker, &session),
+            32 => describe_configs::handle_request(&input, broker, &session),
+            33 => alter_configs::handle_request(&input, broker, &session),
+            35 => describe_log_dirs::handle_request(&input, broker, &session),
+            36 => sasl_authenticate::handle_request(&input, broker, &mut session),
+            44 => incremental_alter_configs::handle_request(&input, broker, &session),
+            48 => describe_client_quotas::handle_request(&input, broker, &session),
+            49 => alter_client_quotas::handle_request(&input, broker, &session),
+            57 => update_features::handle_request(&input, broker, &session),
+            60 => describe_cluster::handle_request(&input, broker, &session),
+            75 => describe_topic::handle_request(&input, broker, &session),
+            _ => {
+                println!("Error processing unknown API Key");
+                break;
+            }
+        };
+
+        if !quota_exempt {
+            // Share of a second spent handling the request
+            let request_percentage = started.elapsed().as_secs_f64() * 100.0;
+            broker.quotas.record(
+                quota::REQUEST_PERCENTAGE,
+                session.user(),
+                &client_id,
+                request_percentage,
+            );
+        }
+
+        // Fetch bytes only count towards the throttle time of later responses
+        if api_key == FETCH {
+            broker.quotas.record(
+                quota::CONSUMER_BYTE_RATE,
+                session.user(),
+                &client_id,
+                result.len() as f64,
+            );
+        }
+
+        if let Err(e) = stream.write_all(&result) {
+            println!("Error writing to stream: {}", e);
+            break;
+        }
+
+        // Mute the connection until the client is back under its quota
+        if session.throttle_time_ms > 0 {
+            thread::sleep(Duration::from_millis(session.throttle_time_ms as u64));
         }
     }
 }
 
 fn main() {
     println!("Logs from your program will appear here!");
-    let listener = TcpListener::bind("127.0.0.1:9092").unwrap();
-    for stream in listener.incoming() {
+    let config = match std::env::args().nth(1) {
+        Some(path) => BrokerConfig::load(Path::new(&path)).unwrap(),
+        None => BrokerConfig::default(),
+    };
+    let broker = Arc::new(Broker::new(config).unwrap());
+    {
+        let broker = Arc::clone(&broker);
+        thread::spawn(move || broker.tail_metadata());
+    }
+    {
+        let broker = Arc::clone(&broker);
+        thread::spawn(move || broker.expire_transactions());
+    }
+
+    let accept_threads: Vec<_> = broker
+        .config
+        .listeners()
+        .into_iter()
+        .map(|endpoint| {
+            let listener =
+                Arc::new(Listener::new(&broker.config, &endpoint.listener_name).unwrap());
+            // An empty host binds every interface
+            let host = if endpoint.host.is_empty() {
+                "0.0.0.0"
+            } else {
+                endpoint.host.as_str()
+            };
+            let tcp_listener = TcpListener::bind((host, endpoint.port as u16)).unwrap();
+            let broker = Arc::clone(&broker);
+            thread::spawn(move || accept(tcp_listener, broker, listener))
+        })
+        .collect();
+    for accept_thread in accept_threads {
+        accept_thread.join().unwrap();
+    }
+}
+
+fn accept(tcp_listener: TcpListener, broker: Arc<Broker>, listener: Arc<Listener>) {
+    for stream in tcp_listener.incoming() {
         match stream {
             Ok(stream) => {
                 println!("Accepted new connection");
-                thread::spawn(|| {
-                    handle_connection(stream);
+                let broker = Arc::clone(&broker);
+                let listener = Arc::clone(&listener);
+                thread::spawn(move || {
+                    handle_connection(stream, broker, listener);
                 });
             }
             Err(e) => {
@@ -59,3 +324,43 @@ fn main() {
         }
     }
 }
+
+#[test]
+fn test_read_request() {
+    let frame = |size: u32, body: &[u8]| [&size.to_be_bytes()[..], body].concat();
+    let header = [0, 18, 0, 4, 0, 0, 0, 7, 0, 0];
+    let input = read_request(&mut &frame(10, &header)[..], 100).unwrap();
+    assert_eq!(&input[4..], &header);
+
+    // Too large for the limit, and too short for a header
+    let error = read_request(&mut &frame(101, &[0; 101])[..], 100).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    let error = read_request(&mut &frame(4, &[0, 18, 0, 4])[..], 100).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+    // A client id longer than the frame
+    let input = frame(10, &[0, 18, 0, 4, 0, 0, 0, 7, 0, 5]);
+    assert!(RequestHeader::try_parse_v1(&mut &input[..]).is_none());
+}
+
+#[test]
+fn test_byte_rate_throttle_time() {
+    use crate::cluser_metadata::ClientQuotaRecord;
+    use crate::quota::{QuotaEntity, USER};
+    use crate::test_util::broker_with_topics;
+
+    let (_log_dir, broker) = broker_with_topics("byte-rate-throttle", "", &[]);
+    let default_user = QuotaEntity::from([(USER.to_string(), None)]);
+    let record = ClientQuotaRecord::new(&default_user, quota::PRODUCER_BYTE_RATE, 1024.0, false);
+    broker.append_metadata(vec![record.encode()]).unwrap();
+    broker.reload_quotas();
+
+    assert_eq!(throttle_time_ms(&broker, PRODUCE, "alice", "app"), 0);
+    broker
+        .quotas
+        .record(quota::PRODUCER_BYTE_RATE, "alice", "app", 1_000_000.0);
+    assert!(throttle_time_ms(&broker, PRODUCE, "alice", "app") > 0);
+    // Only Produce is held to the producer quota
+    assert_eq!(throttle_time_ms(&broker, FETCH, "alice", "app"), 0);
+    assert_eq!(throttle_time_ms(&broker, 3, "alice", "app"), 0);
+}
@@ -0,0 +1,71 @@
+use crate::config::parse_properties;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Identity written into every log directory when the storage is formatted
+#[derive(Debug)]
+pub struct MetaProperties {
+    pub cluster_id: Option<String>,
+    pub directory_id: Option<[u8; 16]>,
+}
+
+// Uuids are written in unpadded url-safe base64, e.g. `6fJ2hZ-8QCWWZ3Cdh8LrTw`
+fn decode_uuid(encoded: &str) -> Option<[u8; 16]> {
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    let mut uuid = vec![];
+    for c in encoded.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            uuid.push((bits >> bit_count) as u8);
+        }
+    }
+    uuid.try_into().ok()
+}
+
+impl MetaProperties {
+    pub fn load(log_dir: &Path) -> io::Result<MetaProperties> {
+        let contents = fs::read_to_string(log_dir.join("meta.properties"))?;
+        Ok(MetaProperties::parse(&contents))
+    }
+
+    pub fn parse(contents: &str) -> MetaProperties {
+        let mut properties = parse_properties(contents);
+        MetaProperties {
+            cluster_id: properties.remove("cluster.id"),
+            directory_id: properties
+                .remove("directory.id")
+                .and_then(|directory_id| decode_uuid(&directory_id)),
+        }
+    }
+}
+
+#[test]
+fn test_parse() {
+    let meta_properties = MetaProperties::parse(
+        "#\n#Tue Oct 01 10:00:00 UTC 2024\nnode.id=1\ndirectory.id=6fJ2hZ-8QCWWZ3Cdh8LrTw\nversion=1\ncluster.id=MkU3OEVBNTcwNTJENDM2Qk\n",
+    );
+    assert_eq!(
+        meta_properties.cluster_id.as_deref(),
+        Some("MkU3OEVBNTcwNTJENDM2Qk")
+    );
+    assert_eq!(
+        meta_properties.directory_id,
+        Some([
+            0xe9, 0xf2, 0x76, 0x85, 0x9f, 0xbc, 0x40, 0x25, 0x96, 0x67, 0x70, 0x9d, 0x87, 0xc2,
+            0xeb, 0x4f
+        ])
+    );
+}
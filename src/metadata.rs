@@ -0,0 +1,274 @@
+use crate::acl::{AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::cluser_metadata::PartitionRecord;
+use crate::describe_cluster::describe_brokers;
+use crate::describe_topic::is_offline;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+const OPERATIONS_NOT_REQUESTED: i32 = i32::MIN;
+
+// A requested topic, which from v12 on may be named by its id alone
+#[derive(Debug)]
+struct MetadataRequestTopic {
+    topic_id: [u8; 16],
+    name: Option<String>,
+}
+
+#[derive(Debug)]
+struct MetadataTopic {
+    // None for an id that can't be resolved to a name the client may see
+    name: Option<String>,
+    error_code: i16,
+    topic_id: [u8; 16],
+    partitions: Vec<PartitionRecord>,
+    authorized_operations: i32,
+}
+
+fn error_topic(request: MetadataRequestTopic, error_code: i16) -> MetadataTopic {
+    MetadataTopic {
+        name: request.name,
+        error_code,
+        topic_id: request.topic_id,
+        partitions: vec![],
+        authorized_operations: OPERATIONS_NOT_REQUESTED,
+    }
+}
+
+// Describes the topics asked for, or every topic the client may see when
+// `topics` is None. Topics are never created on the fly.
+fn describe_topics(
+    broker: &Broker,
+    session: &Session,
+    topics: Option<Vec<MetadataRequestTopic>>,
+    include_authorized_operations: bool,
+) -> Vec<MetadataTopic> {
+    let metadata = broker.metadata();
+    let authorized =
+        |name: &str| broker.authorize(session, AclOperation::Describe, ResourceType::Topic, name);
+    let topics: Vec<MetadataRequestTopic> = match topics {
+        Some(topics) => topics,
+        None => metadata
+            .topics()
+            .iter()
+            .filter(|(name, _)| authorized(name))
+            .map(|(name, topic_id)| MetadataRequestTopic {
+                topic_id: *topic_id,
+                name: Some(name.clone()),
+            })
+            .collect(),
+    };
+
+    topics
+        .into_iter()
+        .map(|request| {
+            let (name, topic_id) = match &request.name {
+                Some(name) if !authorized(name) => {
+                    return error_topic(request, error_code::TOPIC_AUTHORIZATION_FAILED)
+                }
+                Some(name) => match metadata.topic_id(name) {
+                    Some(topic_id) => (name.clone(), topic_id),
+                    None => return error_topic(request, error_code::UNKNOWN_TOPIC_OR_PARTITION),
+                },
+                None => match metadata.topic_name(request.topic_id) {
+                    // Clients that may not see the topic don't learn its name either
+                    Some(name) if !authorized(name) => {
+                        return error_topic(request, error_code::TOPIC_AUTHORIZATION_FAILED)
+                    }
+                    Some(name) => (name.to_string(), request.topic_id),
+                    None => return error_topic(request, error_code::UNKNOWN_TOPIC_ID),
+                },
+            };
+            let authorized_operations = if include_authorized_operations {
+                broker.authorized_operations(session, ResourceType::Topic, &name)
+            } else {
+                OPERATIONS_NOT_REQUESTED
+            };
+            MetadataTopic {
+                name: Some(name),
+                error_code: error_code::NONE,
+                topic_id,
+                partitions: metadata.partitions(topic_id).cloned().collect(),
+                authorized_operations,
+            }
+        })
+        .collect()
+}
+
+fn put_replicas(body: &mut Vec<u8>, replicas: &[i32]) {
+    body.put_compact_array_length(replicas.len());
+    for replica in replicas {
+        body.put_i32(*replica);
+    }
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let topics = input.get_compact_nullable_array_length().map(|length| {
+        (0..length)
+            .map(|_| {
+                let mut topic_id = [0; 16];
+                if header.api_version >= 10 {
+                    input.copy_to_slice(&mut topic_id);
+                }
+                let name = input.get_compact_nullable_string();
+                input.skip_tagged_fields();
+                MetadataRequestTopic { topic_id, name }
+            })
+            .collect()
+    });
+    let _allow_auto_topic_creation = input.get_u8() != 0;
+    let include_cluster_authorized_operations = header.api_version <= 10 && input.get_u8() != 0;
+    let include_topic_authorized_operations = input.get_u8() != 0;
+    input.skip_tagged_fields();
+
+    // Brokers are advertised as reached through the client's listener
+    let brokers = describe_brokers(broker, &session.listener_name);
+    let topics = describe_topics(broker, session, topics, include_topic_authorized_operations);
+    let metadata = broker.metadata();
+    let brokers_by_id = metadata.brokers();
+    let node_id = broker.config.node_id();
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(brokers.len());
+    for described in brokers {
+        body.put_i32(described.broker_id);
+        body.put_compact_string(&described.host);
+        body.put_i32(described.port);
+        body.put_compact_nullable_string(described.rack.as_deref());
+        body.put_empty_tagged_fields();
+    }
+    body.put_compact_nullable_string(Some(&broker.cluster_id()));
+    body.put_i32(broker.controller_id());
+    body.put_compact_array_length(topics.len());
+    for topic in topics {
+        body.put_i16(topic.error_code);
+        body.put_compact_nullable_string(topic.name.as_deref());
+        if header.api_version >= 10 {
+            body.extend_from_slice(&topic.topic_id);
+        }
+        body.put_u8(0); // Is internal
+        body.put_compact_array_length(topic.partitions.len());
+        for partition in &topic.partitions {
+            let error_code = if partition.leader_id == -1 {
+                error_code::LEADER_NOT_AVAILABLE
+            } else {
+                error_code::NONE
+            };
+            let offline_replicas: Vec<i32> = partition
+                .replicas
+                .iter()
+                .copied()
+                .filter(|replica| {
+                    is_offline(*replica, brokers_by_id, &session.listener_name, node_id)
+                })
+                .collect();
+            body.put_i16(error_code);
+            body.put_i32(partition.partition_id);
+            body.put_i32(partition.leader_id);
+            body.put_i32(partition.leader_epoch);
+            put_replicas(&mut body, &partition.replicas);
+            put_replicas(&mut body, &partition.insync_replicas);
+            put_replicas(&mut body, &offline_replicas);
+            body.put_empty_tagged_fields();
+        }
+        body.put_i32(topic.authorized_operations);
+        body.put_empty_tagged_fields();
+    }
+    if header.api_version <= 10 {
+        let cluster_authorized_operations = if include_cluster_authorized_operations {
+            broker.authorized_operations(session, ResourceType::Cluster, CLUSTER_NAME)
+        } else {
+            OPERATIONS_NOT_REQUESTED
+        };
+        body.put_i32(cluster_authorized_operations);
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_brokers_are_advertised_per_listener() {
+    use crate::cluser_metadata::METADATA_TOPIC;
+    use crate::test_util::broker_with_topics;
+
+    let (_log_dir, broker) = broker_with_topics(
+        "metadata-listeners",
+        "listeners=INTERNAL://:9092,EXTERNAL://:29092\n\
+         advertised.listeners=INTERNAL://kafka:9092,EXTERNAL://localhost:29092\n",
+        &[("events", [1; 16], 2)],
+    );
+    let response = |listener_name: &str, topics: &[Option<&str>]| {
+        let mut request = vec![0, 0, 0, 0, 0, 3, 0, 12, 0, 0, 0, 7, 0, 0, 0];
+        request.put_compact_array_length(topics.len());
+        for topic in topics {
+            request.extend_from_slice(&[0; 16]);
+            request.put_compact_nullable_string(*topic);
+            request.put_empty_tagged_fields();
+        }
+        request.extend_from_slice(&[0, 0, 0]);
+        let session = Session::new("127.0.0.1".to_string(), listener_name.to_string(), false);
+        handle_request(&request, &broker, &session)
+    };
+
+    // Size, correlation id, header tags and throttle time, then the one broker
+    let mut internal = &response("INTERNAL", &[Some("events")])[13..];
+    assert_eq!(internal.get_compact_array_length(), 1);
+    assert_eq!(internal.get_i32(), 1);
+    assert_eq!(internal.get_compact_string(), "kafka");
+    assert_eq!(internal.get_i32(), 9092);
+    let mut external = &response("EXTERNAL", &[Some("events")])[13..];
+    assert_eq!(external.get_compact_array_length(), 1);
+    assert_eq!(external.get_i32(), 1);
+    assert_eq!(external.get_compact_string(), "localhost");
+    assert_eq!(external.get_i32(), 29092);
+
+    let session = Session::new("127.0.0.1".to_string(), "INTERNAL".to_string(), false);
+    let by_name = |name: &str| MetadataRequestTopic {
+        topic_id: [0; 16],
+        name: Some(name.to_string()),
+    };
+    let topics = describe_topics(
+        &broker,
+        &session,
+        Some(vec![by_name("events"), by_name(METADATA_TOPIC)]),
+        false,
+    );
+    assert_eq!(topics[0].error_code, error_code::NONE);
+    assert_eq!(topics[0].partitions.len(), 2);
+    assert_eq!(topics[1].error_code, error_code::UNKNOWN_TOPIC_OR_PARTITION);
+}
+
+#[test]
+fn test_describe_topic_by_id() {
+    use crate::test_util::broker_with_topics;
+
+    let (_log_dir, broker) = broker_with_topics("metadata-by-id", "", &[("orders", [7; 16], 1)]);
+    let session = Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false);
+    let by_id = |topic_id| MetadataRequestTopic {
+        topic_id,
+        name: None,
+    };
+
+    let topics = describe_topics(
+        &broker,
+        &session,
+        Some(vec![by_id([7; 16]), by_id([8; 16])]),
+        false,
+    );
+    assert_eq!(topics[0].name.as_deref(), Some("orders"));
+    assert_eq!(topics[0].topic_id, [7; 16]);
+    assert_eq!(topics[0].error_code, error_code::NONE);
+    assert_eq!(topics[0].partitions.len(), 1);
+    assert_eq!(topics[1].name, None);
+    assert_eq!(topics[1].topic_id, [8; 16]);
+    assert_eq!(topics[1].error_code, error_code::UNKNOWN_TOPIC_ID);
+}
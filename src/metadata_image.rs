@@ -0,0 +1,625 @@
+use crate::cluser_metadata::{
+    AccessControlEntryRecord, PartitionRecord, RecordValue, RegisterBrokerRecord,
+    UserScramCredentialRecord,
+};
+use crate::control_record::ControlRecord;
+use crate::dynamic_config::TOPIC_RESOURCE;
+use crate::feature::{IBP_3_0_IV1, KRAFT_VERSION, METADATA_VERSION};
+use crate::partition_log::PartitionLog;
+use crate::quota::QuotaEntity;
+use crate::record_batch::RecordBatch;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+// Records `kafka-storage format` writes to the metadata log dir, which start
+// the metadata log of a new cluster
+pub const BOOTSTRAP_CHECKPOINT: &str = "bootstrap.checkpoint";
+
+// End offset and path of each `<offset>-<epoch>.checkpoint` snapshot of the
+// metadata log, newest first
+fn snapshots(dir: &Path) -> io::Result<Vec<(i64, PathBuf)>> {
+    let mut snapshots = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let end_offset = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".checkpoint"))
+            .and_then(|name| name.split_once('-'))
+            .filter(|(_, epoch)| epoch.parse::<i32>().is_ok())
+            .and_then(|(offset, _)| offset.parse::<i64>().ok());
+        if let Some(end_offset) = end_offset {
+            snapshots.push((end_offset, path));
+        }
+    }
+    snapshots.sort_by(|a, b| b.cmp(a));
+    Ok(snapshots)
+}
+
+// Batches of a snapshot or bootstrap file, which was only renamed into
+// place once complete, so a batch cut short means it is corrupt
+pub fn read_checkpoint(path: &Path) -> io::Result<Vec<RecordBatch>> {
+    let raw = fs::read(path)?;
+    let mut cursor: &[u8] = &raw;
+    let mut record_batches = vec![];
+    while !cursor.is_empty() {
+        let (record_batch, rest) = RecordBatch::parse(cursor).map_err(|e| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("{} in {}", e, path.display()),
+            )
+        })?;
+        record_batches.push(record_batch);
+        cursor = rest;
+    }
+    Ok(record_batches)
+}
+
+// State of the cluster as of `last_offset`, built by replaying the metadata
+// log in order
+#[derive(Debug, Clone)]
+pub struct MetadataImage {
+    // Offset of the last record applied, or -1 for an empty log
+    pub last_offset: i64,
+    topic_ids: BTreeMap<String, [u8; 16]>,
+    topic_names: HashMap<[u8; 16], String>,
+    partitions: BTreeMap<([u8; 16], i32), PartitionRecord>,
+    brokers: BTreeMap<i32, RegisterBrokerRecord>,
+    // Dynamic configs by resource type and name
+    configs: HashMap<(i8, String), BTreeMap<String, String>>,
+    acls: BTreeMap<[u8; 16], AccessControlEntryRecord>,
+    scram_credentials: HashMap<(String, i8), UserScramCredentialRecord>,
+    client_quotas: BTreeMap<QuotaEntity, BTreeMap<String, f64>>,
+    features: BTreeMap<String, i16>,
+    // First producer id not yet handed out in a block
+    next_producer_id: i64,
+    // Leader of the metadata quorum as of its last LeaderChange, and the
+    // voters as of the last LeaderChange or Voters record
+    quorum_leader: i32,
+    quorum_voters: Vec<i32>,
+}
+
+impl MetadataImage {
+    pub fn new() -> MetadataImage {
+        MetadataImage {
+            last_offset: -1,
+            topic_ids: BTreeMap::new(),
+            topic_names: HashMap::new(),
+            partitions: BTreeMap::new(),
+            brokers: BTreeMap::new(),
+            configs: HashMap::new(),
+            acls: BTreeMap::new(),
+            scram_credentials: HashMap::new(),
+            client_quotas: BTreeMap::new(),
+            features: BTreeMap::new(),
+            next_producer_id: 0,
+            quorum_leader: -1,
+            quorum_voters: vec![],
+        }
+    }
+
+    // Loads the newest snapshot that reads back whole, then replays the log
+    // from the snapshot's end offset
+    pub fn load(metadata_log: &PartitionLog) -> io::Result<MetadataImage> {
+        let mut image = MetadataImage::new();
+        for (end_offset, path) in snapshots(metadata_log.dir())? {
+            match read_checkpoint(&path) {
+                Ok(record_batches) => {
+                    let header = record_batches
+                        .first()
+                        .and_then(|record_batch| record_batch.control_records().into_iter().next());
+                    if let Some(ControlRecord::SnapshotHeader {
+                        last_contained_log_timestamp,
+                    }) = header
+                    {
+                        println!(
+                            "Loading metadata snapshot {} of the log as of timestamp {}",
+                            path.display(),
+                            last_contained_log_timestamp
+                        );
+                    }
+                    for record_batch in &record_batches {
+                        image.apply_records(record_batch);
+                    }
+                    image.last_offset = end_offset - 1;
+                    break;
+                }
+                Err(e) => println!("Skipping metadata snapshot {}: {}", path.display(), e),
+            }
+        }
+        for record_batch in metadata_log.read_batches_from(image.last_offset + 1)? {
+            image.apply_batch(&record_batch);
+        }
+        Ok(image)
+    }
+
+    // Applies the records of a batch, unless the image already has them
+    pub fn apply_batch(&mut self, record_batch: &RecordBatch) {
+        if record_batch.last_offset() <= self.last_offset {
+            return;
+        }
+        self.apply_records(record_batch);
+        self.last_offset = record_batch.last_offset();
+    }
+
+    // Control batches hold Raft's own records, such as leader changes and
+    // snapshot headers, rather than metadata
+    fn apply_records(&mut self, record_batch: &RecordBatch) {
+        if record_batch.is_control() {
+            for control_record in record_batch.control_records() {
+                self.apply_control(control_record);
+            }
+            return;
+        }
+        for record in &record_batch.records {
+            if let Some(value) = &record.value {
+                self.apply(RecordValue::parse(value));
+            }
+        }
+    }
+
+    fn apply_control(&mut self, control_record: ControlRecord) {
+        match control_record {
+            ControlRecord::LeaderChange { leader_id, voters } => {
+                self.quorum_leader = leader_id;
+                self.quorum_voters = voters;
+            }
+            ControlRecord::Voters { voters } => {
+                for voter in &voters {
+                    println!("Metadata quorum voter {}", voter);
+                }
+                self.quorum_voters = voters.iter().map(|voter| voter.voter_id).collect();
+            }
+            // Level 0 leaves the feature disabled, as with FeatureLevelRecords
+            ControlRecord::KRaftVersion { kraft_version: 0 } => {
+                self.features.remove(KRAFT_VERSION);
+            }
+            ControlRecord::KRaftVersion { kraft_version } => {
+                self.features
+                    .insert(KRAFT_VERSION.to_string(), kraft_version);
+            }
+            _ => {}
+        }
+    }
+
+    fn apply(&mut self, record: RecordValue) {
+        match record {
+            RecordValue::Topic(topic_record) => {
+                let previous = self
+                    .topic_ids
+                    .insert(topic_record.name.clone(), topic_record.topic_id);
+                if let Some(previous) = previous.filter(|id| *id != topic_record.topic_id) {
+                    self.topic_names.remove(&previous);
+                }
+                self.topic_names
+                    .insert(topic_record.topic_id, topic_record.name);
+            }
+            RecordValue::RemoveTopic(remove_record) => {
+                let topic_id = remove_record.topic_id;
+                if let Some(name) = self.topic_names.remove(&topic_id) {
+                    if self.topic_ids.get(&name) == Some(&topic_id) {
+                        self.topic_ids.remove(&name);
+                    }
+                    self.configs.remove(&(TOPIC_RESOURCE, name));
+                }
+                self.partitions
+                    .retain(|(partition_topic_id, _), _| *partition_topic_id != topic_id);
+            }
+            RecordValue::Partition(partition_record) => {
+                let key = (partition_record.topic_id, partition_record.partition_id);
+                self.partitions.insert(key, partition_record);
+            }
+            RecordValue::PartitionChange(change_record) => {
+                let key = (change_record.topic_id, change_record.partition_id);
+                if let Some(partition) = self.partitions.get_mut(&key) {
+                    partition.merge(change_record);
+                }
+            }
+            RecordValue::Config(config_record) => {
+                let configs = self
+                    .configs
+                    .entry((config_record.resource_type, config_record.resource_name))
+                    .or_default();
+                match config_record.value {
+                    Some(value) => configs.insert(config_record.name, value),
+                    None => configs.remove(&config_record.name),
+                };
+            }
+            RecordValue::RegisterBroker(register_record) => {
+                self.brokers
+                    .insert(register_record.broker_id, register_record);
+            }
+            RecordValue::UnregisterBroker(unregister_record) => {
+                let registered = self.brokers.get(&unregister_record.broker_id);
+                if registered
+                    .is_some_and(|broker| broker.broker_epoch == unregister_record.broker_epoch)
+                {
+                    self.brokers.remove(&unregister_record.broker_id);
+                }
+            }
+            RecordValue::FenceBroker(fencing_record) => {
+                self.set_fenced(fencing_record.broker_id, fencing_record.broker_epoch, true);
+            }
+            RecordValue::UnfenceBroker(fencing_record) => {
+                self.set_fenced(fencing_record.broker_id, fencing_record.broker_epoch, false);
+            }
+            RecordValue::BrokerRegistrationChange(change_record) if change_record.fenced != 0 => {
+                self.set_fenced(
+                    change_record.broker_id,
+                    change_record.broker_epoch,
+                    change_record.fenced == 1,
+                );
+            }
+            RecordValue::AccessControlEntry(acl_record) => {
+                self.acls.insert(acl_record.id, acl_record);
+            }
+            RecordValue::RemoveAccessControlEntry(remove_record) => {
+                self.acls.remove(&remove_record.id);
+            }
+            RecordValue::UserScramCredential(credential_record) => {
+                let key = (credential_record.name.clone(), credential_record.mechanism);
+                self.scram_credentials.insert(key, credential_record);
+            }
+            RecordValue::RemoveUserScramCredential(remove_record) => {
+                self.scram_credentials
+                    .remove(&(remove_record.name, remove_record.mechanism));
+            }
+            RecordValue::ClientQuota(quota_record) => {
+                let entity: QuotaEntity = quota_record.entity.into_iter().collect();
+                let values = self.client_quotas.entry(entity.clone()).or_default();
+                if quota_record.remove {
+                    values.remove(&quota_record.key);
+                } else {
+                    values.insert(quota_record.key, quota_record.value);
+                }
+                if values.is_empty() {
+                    self.client_quotas.remove(&entity);
+                }
+            }
+            RecordValue::FeatureLevel(feature_record) => {
+                if feature_record.feature_level == 0 {
+                    self.features.remove(&feature_record.name);
+                } else {
+                    self.features
+                        .insert(feature_record.name, feature_record.feature_level);
+                }
+            }
+            RecordValue::ProducerIds(producer_ids_record) => {
+                self.next_producer_id = self
+                    .next_producer_id
+                    .max(producer_ids_record.next_producer_id);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn next_producer_id(&self) -> i64 {
+        self.next_producer_id
+    }
+
+    // Fencing changes only apply to the registration they were made for
+    fn set_fenced(&mut self, broker_id: i32, broker_epoch: i64, fenced: bool) {
+        if let Some(broker) = self.brokers.get_mut(&broker_id) {
+            if broker.broker_epoch == broker_epoch {
+                broker.fenced = fenced;
+            }
+        }
+    }
+
+    pub fn topic_id(&self, topic_name: &str) -> Option<[u8; 16]> {
+        self.topic_ids.get(topic_name).copied()
+    }
+
+    // Every topic's id by topic name
+    pub fn topics(&self) -> &BTreeMap<String, [u8; 16]> {
+        &self.topic_ids
+    }
+
+    pub fn topic_name(&self, topic_id: [u8; 16]) -> Option<&str> {
+        self.topic_names.get(&topic_id).map(String::as_str)
+    }
+
+    // Partitions of a topic in index order
+    pub fn partitions(&self, topic_id: [u8; 16]) -> impl Iterator<Item = &PartitionRecord> {
+        self.partitions
+            .range((topic_id, i32::MIN)..=(topic_id, i32::MAX))
+            .map(|(_, partition)| partition)
+    }
+
+    pub fn partition(&self, topic_id: [u8; 16], partition_id: i32) -> Option<&PartitionRecord> {
+        self.partitions.get(&(topic_id, partition_id))
+    }
+
+    // Dynamic configs of a resource
+    pub fn configs(&self, resource_type: i8, resource_name: &str) -> BTreeMap<String, String> {
+        self.configs
+            .get(&(resource_type, resource_name.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Registered brokers by id
+    pub fn brokers(&self) -> &BTreeMap<i32, RegisterBrokerRecord> {
+        &self.brokers
+    }
+
+    // Log dir each partition replica of `broker_id` was assigned to
+    pub fn directory_assignments(&self, broker_id: i32) -> HashMap<(String, i32), [u8; 16]> {
+        let mut assignments = HashMap::new();
+
+        for ((topic_id, partition_id), partition) in &self.partitions {
+            let topic_name = self.topic_names.get(topic_id);
+            let replica = partition
+                .replicas
+                .iter()
+                .position(|replica| *replica == broker_id);
+            if let (Some(topic_name), Some(replica)) = (topic_name, replica) {
+                if let Some(directory) = partition.directories.get(replica) {
+                    assignments.insert((topic_name.clone(), *partition_id), *directory);
+                }
+            }
+        }
+
+        assignments
+    }
+
+    // Access control entries by id
+    pub fn acls(&self) -> &BTreeMap<[u8; 16], AccessControlEntryRecord> {
+        &self.acls
+    }
+
+    // SCRAM credential of a user for one mechanism
+    pub fn scram_credential(
+        &self,
+        name: &str,
+        mechanism: i8,
+    ) -> Option<&UserScramCredentialRecord> {
+        self.scram_credentials.get(&(name.to_string(), mechanism))
+    }
+
+    // Quota values of each entity
+    pub fn client_quotas(&self) -> &BTreeMap<QuotaEntity, BTreeMap<String, f64>> {
+        &self.client_quotas
+    }
+
+    // Levels of the enabled features
+    pub fn finalized_features(&self) -> &BTreeMap<String, i16> {
+        &self.features
+    }
+
+    // Leader of the metadata quorum, or -1 when unknown or no longer a voter
+    pub fn quorum_leader(&self) -> i32 {
+        if self.quorum_voters.contains(&self.quorum_leader) {
+            self.quorum_leader
+        } else {
+            -1
+        }
+    }
+
+    pub fn metadata_version(&self) -> i16 {
+        self.features
+            .get(METADATA_VERSION)
+            .copied()
+            .unwrap_or(IBP_3_0_IV1)
+    }
+}
+
+#[test]
+fn test_apply_batch() {
+    use crate::cluser_metadata::{ConfigRecord, FeatureLevelRecord};
+    use crate::record_batch::Record;
+
+    let batch = |base_offset, values: Vec<Vec<u8>>| {
+        let records = values
+            .into_iter()
+            .enumerate()
+            .map(|(offset_delta, value)| Record::new(offset_delta as i32, None, Some(value)))
+            .collect();
+        let mut record_batch = RecordBatch::new(0, -1, -1, 0, records);
+        record_batch.base_offset = base_offset;
+        record_batch
+    };
+
+    let mut image = MetadataImage::new();
+    image.apply_batch(&batch(
+        0,
+        vec![
+            FeatureLevelRecord::new(METADATA_VERSION, 20).encode(),
+            ConfigRecord::new(2, "events", "retention.ms", Some("1000")).encode(),
+        ],
+    ));
+    image.apply_batch(&batch(
+        2,
+        vec![ConfigRecord::new(2, "events", "retention.ms", None).encode()],
+    ));
+    assert_eq!(image.last_offset, 2);
+    assert_eq!(image.metadata_version(), 20);
+    assert!(image.configs(2, "events").is_empty());
+
+    // Batches the image already has are skipped
+    image.apply_batch(&batch(
+        1,
+        vec![ConfigRecord::new(2, "events", "retention.ms", Some("1")).encode()],
+    ));
+    assert!(image.configs(2, "events").is_empty());
+    assert_eq!(MetadataImage::new().metadata_version(), IBP_3_0_IV1);
+}
+
+#[test]
+fn test_remove_and_recreate_topic() {
+    use crate::protocol::CompactBufMut;
+    use crate::record_batch::Record;
+    use crate::test_util::{partition_record, topic_record};
+    use bytes::BufMut;
+
+    let change = |topic_id: [u8; 16]| {
+        let mut output = vec![1, 5, 0];
+        output.put_i32(0);
+        output.extend_from_slice(&topic_id);
+        // Tag 0, the ISR, holding [2], and tag 1, the leader, set to 2
+        output.extend_from_slice(&[2, 0, 5, 2, 0, 0, 0, 2, 1, 4, 0, 0, 0, 2]);
+        output
+    };
+    let mut remove = vec![1, 9, 0];
+    remove.extend_from_slice(&[1; 16]);
+    remove.put_empty_tagged_fields();
+
+    let values = vec![
+        topic_record("events", [1; 16]),
+        partition_record([1; 16], 0, 1, &[1, 2]),
+        change([1; 16]),
+        remove,
+        topic_record("events", [2; 16]),
+        partition_record([2; 16], 0, 1, &[1, 2]),
+    ];
+    let records = values
+        .into_iter()
+        .enumerate()
+        .map(|(offset_delta, value)| Record::new(offset_delta as i32, None, Some(value)))
+        .collect();
+    let mut image = MetadataImage::new();
+    image.apply_batch(&RecordBatch::new(0, -1, -1, 0, records));
+
+    assert_eq!(image.topic_id("events"), Some([2; 16]));
+    assert_eq!(image.topic_name([1; 16]), None);
+    assert_eq!(image.partitions([1; 16]).count(), 0);
+    assert_eq!(image.partition([2; 16], 0).unwrap().leader_id, 1);
+
+    image.apply_batch(&{
+        let mut record_batch = RecordBatch::new(
+            0,
+            -1,
+            -1,
+            0,
+            vec![Record::new(0, None, Some(change([2; 16])))],
+        );
+        record_batch.base_offset = 6;
+        record_batch
+    });
+    let partition = image.partition([2; 16], 0).unwrap();
+    assert_eq!((partition.leader_id, partition.leader_epoch), (2, 1));
+    assert_eq!(partition.insync_replicas, vec![2]);
+}
+
+#[test]
+fn test_load_snapshot_and_log() {
+    use crate::cluser_metadata::{FeatureLevelRecord, METADATA_TOPIC};
+    use crate::record_batch::Record;
+    use crate::test_util::TempDir;
+
+    let batch = |feature_level| {
+        let value = FeatureLevelRecord::new(METADATA_VERSION, feature_level).encode();
+        RecordBatch::new(0, -1, -1, 0, vec![Record::new(0, None, Some(value))])
+    };
+    let log_dir = TempDir::new("metadata-image");
+    let log = PartitionLog::create(log_dir.path(), METADATA_TOPIC, 0).unwrap();
+    for feature_level in [7, 8, 9] {
+        log.append(batch(feature_level)).unwrap();
+    }
+    // A snapshot up to offset 2, and a newer one that was cut short
+    fs::write(
+        log.dir().join("00000000000000000002-0000000001.checkpoint"),
+        batch(14).encode(),
+    )
+    .unwrap();
+    let truncated = batch(15).encode();
+    fs::write(
+        log.dir().join("00000000000000000003-0000000001.checkpoint"),
+        &truncated[..truncated.len() - 1],
+    )
+    .unwrap();
+
+    let image = MetadataImage::load(&log).unwrap();
+    assert_eq!(image.last_offset, 2);
+    // Offset 2 is replayed on top of the snapshot, offsets 0 and 1 aren't
+    assert_eq!(image.metadata_version(), 9);
+
+    fs::remove_file(log.dir().join("00000000000000000002-0000000001.checkpoint")).unwrap();
+    fs::write(
+        log.dir().join("00000000000000000003-0000000001.checkpoint"),
+        batch(15).encode(),
+    )
+    .unwrap();
+    let image = MetadataImage::load(&log).unwrap();
+    assert_eq!((image.last_offset, image.metadata_version()), (2, 15));
+}
+
+#[test]
+fn test_apply_quorum_control_records() {
+    use crate::control_record::{
+        KRAFT_VERSION as KRAFT_VERSION_RECORD, KRAFT_VOTERS, LEADER_CHANGE,
+    };
+    use crate::protocol::CompactBufMut;
+    use crate::record_batch::{Record, CONTROL_FLAG};
+    use bytes::BufMut;
+
+    let control_batch = |base_offset, record_type: i16, value: Vec<u8>| {
+        let mut key = vec![];
+        key.put_i16(0);
+        key.put_i16(record_type);
+        let records = vec![Record::new(0, Some(key), Some(value))];
+        let mut record_batch = RecordBatch::new(CONTROL_FLAG, -1, -1, 0, records);
+        record_batch.base_offset = base_offset;
+        record_batch
+    };
+    let leader_change = |leader_id, voters: &[i32]| {
+        let mut value = vec![];
+        value.put_i16(0);
+        value.put_i32(leader_id);
+        for _ in 0..2 {
+            value.put_compact_array_length(voters.len());
+            for voter_id in voters {
+                value.put_i32(*voter_id);
+                value.put_empty_tagged_fields();
+            }
+        }
+        value.put_empty_tagged_fields();
+        value
+    };
+    let voters = |voter_ids: &[i32]| {
+        let mut value = vec![];
+        value.put_i16(0);
+        value.put_compact_array_length(voter_ids.len());
+        for voter_id in voter_ids {
+            value.put_i32(*voter_id);
+            value.put_slice(&[0; 16]);
+            value.put_compact_array_length(0);
+            value.put_i16(0);
+            value.put_i16(1);
+            value.put_empty_tagged_fields();
+            value.put_empty_tagged_fields();
+        }
+        value.put_empty_tagged_fields();
+        value
+    };
+    let kraft_version = |level: i16| {
+        let mut value = vec![];
+        value.put_i16(0);
+        value.put_i16(level);
+        value.put_empty_tagged_fields();
+        value
+    };
+
+    let mut image = MetadataImage::new();
+    assert_eq!(image.quorum_leader(), -1);
+    image.apply_batch(&control_batch(
+        0,
+        LEADER_CHANGE,
+        leader_change(2, &[1, 2, 3]),
+    ));
+    assert_eq!(image.quorum_leader(), 2);
+    image.apply_batch(&control_batch(1, KRAFT_VERSION_RECORD, kraft_version(1)));
+    assert_eq!(image.finalized_features().get(KRAFT_VERSION), Some(&1));
+    assert_eq!(image.last_offset, 1);
+
+    // A leader voted out of the quorum no longer leads it
+    image.apply_batch(&control_batch(2, KRAFT_VOTERS, voters(&[1, 3])));
+    assert_eq!(image.quorum_leader(), -1);
+    image.apply_batch(&control_batch(3, LEADER_CHANGE, leader_change(3, &[1, 3])));
+    assert_eq!(image.quorum_leader(), 3);
+    image.apply_batch(&control_batch(4, KRAFT_VERSION_RECORD, kraft_version(0)));
+    assert_eq!(image.finalized_features().get(KRAFT_VERSION), None);
+}
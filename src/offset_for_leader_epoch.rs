@@ -0,0 +1,131 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::leader_epoch_cache::{UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET};
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+#[derive(Debug)]
+struct OffsetForLeaderPartition {
+    partition: i32,
+    current_leader_epoch: i32,
+    leader_epoch: i32,
+}
+
+#[derive(Debug)]
+struct OffsetForLeaderTopic {
+    topic: String,
+    partitions: Vec<OffsetForLeaderPartition>,
+}
+
+// Checks the epoch the client believes is current against the partition's
+pub fn check_current_leader_epoch(
+    broker: &Broker,
+    topic: &str,
+    partition: i32,
+    current_leader_epoch: i32,
+) -> Result<(), i16> {
+    if current_leader_epoch < 0 {
+        return Ok(());
+    }
+    let metadata = broker.metadata();
+    let partition_record = metadata
+        .topic_id(topic)
+        .and_then(|topic_id| metadata.partition(topic_id, partition));
+    match partition_record {
+        Some(partition_record) if current_leader_epoch < partition_record.leader_epoch => {
+            Err(error_code::FENCED_LEADER_EPOCH)
+        }
+        Some(partition_record) if current_leader_epoch > partition_record.leader_epoch => {
+            Err(error_code::UNKNOWN_LEADER_EPOCH)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn end_offset_for(
+    broker: &Broker,
+    topic: &str,
+    request: &OffsetForLeaderPartition,
+) -> Result<(i32, i64), i16> {
+    let log = broker
+        .partition_log(topic, request.partition)
+        .ok_or(error_code::UNKNOWN_TOPIC_OR_PARTITION)?;
+    check_current_leader_epoch(
+        broker,
+        topic,
+        request.partition,
+        request.current_leader_epoch,
+    )?;
+
+    let storage_error = |_| error_code::KAFKA_STORAGE_ERROR;
+    let leader_epoch_cache = log.leader_epoch_cache().map_err(storage_error)?;
+    let log_end_offset = log.log_end_offset().map_err(storage_error)?;
+    Ok(leader_epoch_cache.end_offset_for(request.leader_epoch, log_end_offset))
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let _replica_id = input.get_i32();
+    let topics_length = input.get_compact_array_length();
+    let topics: Vec<OffsetForLeaderTopic> = (0..topics_length)
+        .map(|_| {
+            let topic = input.get_compact_string();
+            let partitions_length = input.get_compact_array_length();
+            let partitions = (0..partitions_length)
+                .map(|_| {
+                    let partition = input.get_i32();
+                    let current_leader_epoch = input.get_i32();
+                    let leader_epoch = input.get_i32();
+                    input.skip_tagged_fields();
+                    OffsetForLeaderPartition {
+                        partition,
+                        current_leader_epoch,
+                        leader_epoch,
+                    }
+                })
+                .collect();
+            input.skip_tagged_fields();
+            OffsetForLeaderTopic { topic, partitions }
+        })
+        .collect();
+    input.skip_tagged_fields();
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(topics.len());
+    for topic in topics {
+        let authorized = broker.authorize(
+            session,
+            AclOperation::Describe,
+            ResourceType::Topic,
+            &topic.topic,
+        );
+        body.put_compact_string(&topic.topic);
+        body.put_compact_array_length(topic.partitions.len());
+        for partition in topic.partitions {
+            let result = if authorized {
+                end_offset_for(broker, &topic.topic, &partition)
+            } else {
+                Err(error_code::TOPIC_AUTHORIZATION_FAILED)
+            };
+            let (leader_epoch, end_offset) = *result
+                .as_ref()
+                .unwrap_or(&(UNDEFINED_EPOCH, UNDEFINED_EPOCH_OFFSET));
+            body.put_i16(result.err().unwrap_or(error_code::NONE));
+            body.put_i32(partition.partition);
+            body.put_i32(leader_epoch);
+            body.put_i64(end_offset);
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
@@ -0,0 +1,663 @@
+use crate::checkpoint;
+use crate::control_record::ControlRecord;
+use crate::leader_epoch_cache::LeaderEpochCache;
+use crate::record_batch::{CorruptRecord, RecordBatch, ABORT_MARKER};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const LOG_START_OFFSET_CHECKPOINT: &str = "log-start-offset-checkpoint";
+const LEADER_EPOCH_CHECKPOINT: &str = "leader-epoch-checkpoint";
+
+// State shared by every handle on the same path, since connections each
+// open their own PartitionLog
+type Shared<T> = Mutex<BTreeMap<PathBuf, Arc<Mutex<T>>>>;
+
+// Every partition of a log dir shares one checkpoint file, so its updates
+// are serialized per log dir
+static LOG_START_OFFSETS: Shared<()> = Mutex::new(BTreeMap::new());
+// Log end of each partition dir, which appends are serialized on
+static LOG_ENDS: Shared<Option<LogEnd>> = Mutex::new(BTreeMap::new());
+
+fn shared<T: Default>(registry: &Shared<T>, path: &Path) -> Arc<Mutex<T>> {
+    let mut registry = registry.lock().unwrap();
+    Arc::clone(registry.entry(path.to_path_buf()).or_default())
+}
+
+// Where the complete batches of the active segment end, so appends don't
+// have to re-read the log
+#[derive(Debug)]
+struct LogEnd {
+    segment: PathBuf,
+    position: u64,
+    offset: i64,
+}
+
+fn segment_base_offset(segment: &Path) -> i64 {
+    segment
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbortedTransaction {
+    pub producer_id: i64,
+    pub first_offset: i64,
+    pub last_offset: i64,
+}
+
+// What recovery cut from a log
+#[derive(Debug)]
+pub struct Recovery {
+    // New log end offset
+    pub offset: i64,
+    // Segment truncated at the first bad batch, and by how many bytes
+    pub segment: PathBuf,
+    pub truncated_bytes: u64,
+    pub deleted_segments: Vec<PathBuf>,
+    // Why the first bad batch didn't parse
+    pub error: CorruptRecord,
+}
+
+#[derive(Debug)]
+pub struct PartitionLog {
+    log_dir: PathBuf,
+    topic: String,
+    partition: i32,
+    dir: PathBuf,
+    log_end: Arc<Mutex<Option<LogEnd>>>,
+    log_start_offsets: Arc<Mutex<()>>,
+}
+
+impl PartitionLog {
+    fn new(log_dir: &Path, topic: &str, partition: i32) -> PartitionLog {
+        let dir = log_dir.join(format!("{}-{}", topic, partition));
+        PartitionLog {
+            log_dir: log_dir.to_path_buf(),
+            topic: topic.to_string(),
+            partition,
+            log_end: shared(&LOG_ENDS, &dir),
+            log_start_offsets: shared(&LOG_START_OFFSETS, log_dir),
+            dir,
+        }
+    }
+
+    pub fn open(log_dir: &Path, topic: &str, partition: i32) -> Option<PartitionLog> {
+        let log = PartitionLog::new(log_dir, topic, partition);
+        if !log.dir.is_dir() {
+            return None;
+        }
+        Some(log)
+    }
+
+    pub fn create(log_dir: &Path, topic: &str, partition: i32) -> io::Result<PartitionLog> {
+        let log = PartitionLog::new(log_dir, topic, partition);
+        fs::create_dir_all(&log.dir)?;
+        Ok(log)
+    }
+
+    // Topic partitions with a log in `log_dir`
+    pub fn list(log_dir: &Path) -> io::Result<Vec<(String, i32)>> {
+        let mut partitions = vec![];
+        for entry in fs::read_dir(log_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Skips e.g. directories renamed for deletion, `events-0.<id>-delete`
+            if let Some((topic, partition)) = name.rsplit_once('-') {
+                if let Ok(partition) = partition.parse() {
+                    partitions.push((topic.to_string(), partition));
+                }
+            }
+        }
+        partitions.sort();
+        Ok(partitions)
+    }
+
+    // Bytes taken by the segment files
+    pub fn size(&self) -> io::Result<u64> {
+        let mut size = 0;
+        for segment in self.segments()? {
+            size += fs::metadata(segment)?.len();
+        }
+        Ok(size)
+    }
+
+    // Segment files ordered by base offset
+    fn segments(&self) -> io::Result<Vec<PathBuf>> {
+        let mut segments: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "log"))
+            .collect();
+        segments.sort();
+        Ok(segments)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn read_batches(&self) -> io::Result<Vec<RecordBatch>> {
+        self.read_batches_from(0)
+    }
+
+    // Batches holding offsets from `offset` on, skipping the segments that
+    // end before it. A batch still being appended at the tail is left out.
+    pub fn read_batches_from(&self, offset: i64) -> io::Result<Vec<RecordBatch>> {
+        let segments = self.segments()?;
+        let mut record_batches = vec![];
+        for (i, segment) in segments.iter().enumerate() {
+            if segments
+                .get(i + 1)
+                .is_some_and(|next| segment_base_offset(next) <= offset)
+            {
+                continue;
+            }
+            let raw = fs::read(segment)?;
+            let mut cursor: &[u8] = &raw;
+            while let Some(size) = RecordBatch::size(cursor) {
+                let (record_batch, _) = RecordBatch::parse(&cursor[..size])?;
+                cursor = &cursor[size..];
+                if record_batch.last_offset() >= offset {
+                    record_batches.push(record_batch);
+                }
+            }
+        }
+        Ok(record_batches)
+    }
+
+    // Truncates the log at the first batch that is corrupt or cut short and
+    // deletes the segments after it, as Kafka's log recovery does after an
+    // unclean shutdown. Returns what was cut, if anything. With `tail_only`
+    // only a batch cut short at the very end of the log is cut, as a crash
+    // mid-append leaves; any other damage is an error and the log is left as is.
+    pub fn recover(&self, tail_only: bool) -> io::Result<Option<Recovery>> {
+        let mut log_end = self.log_end.lock().unwrap();
+        let segments = self.segments()?;
+        for (i, segment) in segments.iter().enumerate() {
+            let raw = fs::read(segment)?;
+            let mut cursor: &[u8] = &raw;
+            let mut offset = segment_base_offset(segment);
+            let mut error = None;
+            while !cursor.is_empty() {
+                match RecordBatch::parse(cursor) {
+                    Ok((record_batch, rest)) => {
+                        offset = record_batch.last_offset() + 1;
+                        cursor = rest;
+                    }
+                    Err(e) => {
+                        error = Some(e);
+                        break;
+                    }
+                }
+            }
+            let Some(error) = error else {
+                continue;
+            };
+
+            let later_segments = &segments[i + 1..];
+            let torn_tail =
+                later_segments.is_empty() && matches!(error, CorruptRecord::Truncated { .. });
+            if tail_only && !torn_tail {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{} is corrupt after offset {}: {}",
+                        segment.display(),
+                        offset,
+                        error
+                    ),
+                ));
+            }
+
+            let valid = raw.len() - cursor.len();
+            OpenOptions::new()
+                .write(true)
+                .open(segment)?
+                .set_len(valid as u64)?;
+            for later in later_segments {
+                delete_segment(later)?;
+            }
+            let mut leader_epoch_cache = self.leader_epoch_cache()?;
+            if leader_epoch_cache.truncate_from_end(offset) {
+                leader_epoch_cache.flush()?;
+            }
+            *log_end = None;
+            return Ok(Some(Recovery {
+                offset,
+                segment: segment.clone(),
+                truncated_bytes: cursor.len() as u64,
+                deleted_segments: later_segments.to_vec(),
+                error,
+            }));
+        }
+        Ok(None)
+    }
+
+    pub fn log_end_offset(&self) -> io::Result<i64> {
+        let mut log_end = self.log_end.lock().unwrap();
+        self.update_log_end(&mut log_end)
+    }
+
+    // Brings the cached log end up to date with the active segment, reading
+    // only what was appended to it since, e.g. by another process. An empty
+    // active segment starts at the log end offset.
+    fn update_log_end(&self, log_end: &mut Option<LogEnd>) -> io::Result<i64> {
+        let segment = match self.segments()?.pop() {
+            Some(segment) => segment,
+            None => return Ok(0),
+        };
+        let (mut position, mut offset) = match log_end.take() {
+            Some(log_end) if log_end.segment == segment => (log_end.position, log_end.offset),
+            _ => (0, segment_base_offset(&segment)),
+        };
+        let mut file = File::open(&segment)?;
+        if file.metadata()?.len() > position {
+            let mut appended = vec![];
+            file.seek(SeekFrom::Start(position))?;
+            file.read_to_end(&mut appended)?;
+            let mut cursor: &[u8] = &appended;
+            while let Some(size) = RecordBatch::size(cursor) {
+                let (record_batch, rest) = RecordBatch::parse(cursor)?;
+                offset = record_batch.last_offset() + 1;
+                position += size as u64;
+                cursor = rest;
+            }
+        }
+        *log_end = Some(LogEnd {
+            segment,
+            position,
+            offset,
+        });
+        Ok(offset)
+    }
+
+    fn checkpointed_log_start_offsets(&self) -> io::Result<Vec<Vec<String>>> {
+        checkpoint::read(&self.log_dir.join(LOG_START_OFFSET_CHECKPOINT))
+    }
+
+    // First offset still readable from the log, as advanced by DeleteRecords
+    pub fn log_start_offset(&self) -> io::Result<i64> {
+        let partition = self.partition.to_string();
+        let checkpointed = self
+            .checkpointed_log_start_offsets()?
+            .into_iter()
+            .find(|entry| entry.len() == 3 && entry[0] == self.topic && entry[1] == partition)
+            .and_then(|entry| entry[2].parse().ok())
+            .unwrap_or(0);
+        let first_segment = self
+            .segments()?
+            .first()
+            .map_or(0, |segment| segment_base_offset(segment));
+        Ok(checkpointed.max(first_segment))
+    }
+
+    // Advances the log start offset to `offset`, which must not be past the
+    // log end offset, and deletes the segments that fall entirely below it
+    pub fn delete_records_before(&self, offset: i64) -> io::Result<i64> {
+        let _checkpoint_lock = self.log_start_offsets.lock().unwrap();
+        let mut log_end = self.log_end.lock().unwrap();
+        let log_start_offset = self.log_start_offset()?;
+        if offset <= log_start_offset {
+            return Ok(log_start_offset);
+        }
+
+        let partition = self.partition.to_string();
+        let mut entries: Vec<String> = self
+            .checkpointed_log_start_offsets()?
+            .into_iter()
+            .filter(|entry| entry.len() != 3 || entry[0] != self.topic || entry[1] != partition)
+            .map(|entry| entry.join(" "))
+            .collect();
+        entries.push(format!("{} {} {}", self.topic, self.partition, offset));
+        checkpoint::write(&self.log_dir.join(LOG_START_OFFSET_CHECKPOINT), &entries)?;
+        let mut leader_epoch_cache = self.leader_epoch_cache()?;
+        if leader_epoch_cache.truncate_from_start(offset) {
+            leader_epoch_cache.flush()?;
+        }
+
+        // Roll a new active segment when everything is deleted, so the old one can go
+        let mut segments = self.segments()?;
+        if offset == self.update_log_end(&mut log_end)?
+            && segments
+                .last()
+                .is_some_and(|segment| segment_base_offset(segment) < offset)
+        {
+            let segment = self.dir.join(format!("{:020}.log", offset));
+            File::create(&segment)?;
+            segments.push(segment);
+        }
+        for segments in segments.windows(2) {
+            if segment_base_offset(&segments[1]) <= offset {
+                delete_segment(&segments[0])?;
+            }
+        }
+
+        Ok(offset)
+    }
+
+    // Starts an empty active segment at `offset`, past the end of the log
+    pub fn roll(&self, offset: i64) -> io::Result<()> {
+        let _log_end = self.log_end.lock().unwrap();
+        File::create(self.dir.join(format!("{:020}.log", offset)))?;
+        Ok(())
+    }
+
+    // Assigns the next offset to the batch and appends it to the active
+    // segment. Appends to a partition are serialized across handles.
+    pub fn append(&self, mut record_batch: RecordBatch) -> io::Result<i64> {
+        let mut log_end = self.log_end.lock().unwrap();
+        let base_offset = self.update_log_end(&mut log_end)?;
+        record_batch.base_offset = base_offset;
+
+        let segment = match self.segments()?.pop() {
+            Some(segment) => segment,
+            None => self.dir.join(format!("{:020}.log", base_offset)),
+        };
+        let mut leader_epoch_cache = self.leader_epoch_cache()?;
+        let encoded = record_batch.encode();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&segment)?;
+        file.write_all(&encoded)?;
+        if leader_epoch_cache.assign(record_batch.partition_leader_epoch, base_offset) {
+            leader_epoch_cache.flush()?;
+        }
+        // Appending to the segment the cache is for only moves its end
+        if let Some(log_end) = log_end
+            .as_mut()
+            .filter(|log_end| log_end.segment == segment)
+        {
+            log_end.position += encoded.len() as u64;
+            log_end.offset = record_batch.last_offset() + 1;
+        }
+        Ok(base_offset)
+    }
+
+    // Logs written before the checkpoint existed get their cache rebuilt from the batches
+    pub fn leader_epoch_cache(&self) -> io::Result<LeaderEpochCache> {
+        let path = self.dir.join(LEADER_EPOCH_CHECKPOINT);
+        if path.exists() {
+            return LeaderEpochCache::load(path);
+        }
+        let mut leader_epoch_cache = LeaderEpochCache::new(path);
+        for record_batch in self.read_batches()? {
+            leader_epoch_cache.assign(
+                record_batch.partition_leader_epoch,
+                record_batch.base_offset,
+            );
+        }
+        Ok(leader_epoch_cache)
+    }
+
+    // Replays the transactional batches, returning the first offset of every
+    // still-open transaction and the ranges of the aborted ones
+    fn transaction_index(&self) -> io::Result<(HashMap<i64, i64>, Vec<AbortedTransaction>)> {
+        let mut ongoing: HashMap<i64, i64> = HashMap::new();
+        let mut aborted = vec![];
+
+        for record_batch in self.read_batches()? {
+            if !record_batch.is_transactional() {
+                continue;
+            }
+            if !record_batch.is_control() {
+                ongoing
+                    .entry(record_batch.producer_id)
+                    .or_insert(record_batch.base_offset);
+                continue;
+            }
+            let first_offset = ongoing.remove(&record_batch.producer_id);
+            if let (Some(first_offset), Some(ABORT_MARKER)) =
+                (first_offset, record_batch.control_marker())
+            {
+                aborted.push(AbortedTransaction {
+                    producer_id: record_batch.producer_id,
+                    first_offset,
+                    last_offset: record_batch.base_offset,
+                });
+            }
+        }
+
+        Ok((ongoing, aborted))
+    }
+
+    // Coordinator epoch of the last transaction marker written for
+    // `producer_id`, which fences markers from an older coordinator
+    pub fn coordinator_epoch(&self, producer_id: i64) -> io::Result<Option<i32>> {
+        let mut coordinator_epoch = None;
+        for record_batch in self.read_batches()? {
+            if record_batch.producer_id != producer_id {
+                continue;
+            }
+            if let Some(
+                ControlRecord::Abort {
+                    coordinator_epoch: epoch,
+                }
+                | ControlRecord::Commit {
+                    coordinator_epoch: epoch,
+                },
+            ) = record_batch.control_records().first()
+            {
+                coordinator_epoch = Some(*epoch);
+            }
+        }
+        Ok(coordinator_epoch)
+    }
+
+    // Read-committed consumers may only see offsets below the last stable offset
+    pub fn last_stable_offset(&self) -> io::Result<i64> {
+        let (ongoing, _) = self.transaction_index()?;
+        match ongoing.values().min() {
+            Some(first_offset) => Ok(*first_offset),
+            None => self.log_end_offset(),
+        }
+    }
+
+    // Aborted transactions overlapping [fetch_offset, upper_bound_offset)
+    pub fn aborted_transactions(
+        &self,
+        fetch_offset: i64,
+        upper_bound_offset: i64,
+    ) -> io::Result<Vec<AbortedTransaction>> {
+        let (_, aborted) = self.transaction_index()?;
+        Ok(aborted
+            .into_iter()
+            .filter(|aborted| {
+                aborted.last_offset >= fetch_offset && aborted.first_offset < upper_bound_offset
+            })
+            .collect())
+    }
+}
+
+// Removes a segment together with its index files
+fn delete_segment(segment: &Path) -> io::Result<()> {
+    for extension in ["log", "index", "timeindex", "txnindex"] {
+        match fs::remove_file(segment.with_extension(extension)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_last_stable_offset_and_aborted_transactions() {
+    use crate::record_batch::COMMIT_MARKER;
+    use crate::test_util::{transactional_batch, TempDir};
+
+    let log_dir = TempDir::new("partition-log");
+    let log = PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+
+    assert_eq!(log.append(transactional_batch(1)).unwrap(), 0);
+    assert_eq!(log.append(transactional_batch(2)).unwrap(), 1);
+    let abort = RecordBatch::control(1, 0, ABORT_MARKER, 0);
+    assert_eq!(log.append(abort).unwrap(), 2);
+    assert_eq!(log.last_stable_offset().unwrap(), 1);
+
+    let commit = RecordBatch::control(2, 0, COMMIT_MARKER, 0);
+    assert_eq!(log.append(commit).unwrap(), 3);
+    assert_eq!(log.last_stable_offset().unwrap(), 4);
+    assert_eq!(
+        log.aborted_transactions(0, 4).unwrap(),
+        vec![AbortedTransaction {
+            producer_id: 1,
+            first_offset: 0,
+            last_offset: 2,
+        }]
+    );
+    assert!(log.aborted_transactions(3, 4).unwrap().is_empty());
+}
+
+#[test]
+fn test_concurrent_appends_through_separate_handles() {
+    use crate::test_util::{data_batch, TempDir};
+
+    let log_dir = TempDir::new("concurrent-appends");
+    PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+    let appenders: Vec<_> = (0..4)
+        .map(|_| {
+            let log = PartitionLog::open(log_dir.path(), "events", 0).unwrap();
+            std::thread::spawn(move || {
+                (0..25)
+                    .map(|_| log.append(data_batch()).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let mut offsets: Vec<i64> = appenders
+        .into_iter()
+        .flat_map(|appender| appender.join().unwrap())
+        .collect();
+    offsets.sort();
+    assert_eq!(offsets, (0..100).collect::<Vec<_>>());
+
+    // A batch appended by another process is picked up from the segment
+    let log = PartitionLog::open(log_dir.path(), "events", 0).unwrap();
+    let mut external = data_batch();
+    external.base_offset = 100;
+    OpenOptions::new()
+        .append(true)
+        .open(&log.segments().unwrap()[0])
+        .unwrap()
+        .write_all(&external.encode())
+        .unwrap();
+    assert_eq!(log.append(data_batch()).unwrap(), 101);
+    assert_eq!(log.read_batches().unwrap().len(), 102);
+}
+
+#[test]
+fn test_delete_records_before() {
+    use crate::test_util::{data_batch, TempDir};
+
+    let log_dir = TempDir::new("delete-records");
+    let log = PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+    log.append(data_batch()).unwrap();
+    log.append(data_batch()).unwrap();
+    // Start a second segment at offset 2
+    log.roll(2).unwrap();
+    log.append(data_batch()).unwrap();
+
+    assert_eq!(log.delete_records_before(1).unwrap(), 1);
+    assert_eq!(log.segments().unwrap().len(), 2);
+    assert_eq!(log.delete_records_before(2).unwrap(), 2);
+    assert_eq!(log.segments().unwrap().len(), 1);
+    assert_eq!(log.delete_records_before(1).unwrap(), 2);
+
+    assert_eq!(log.delete_records_before(3).unwrap(), 3);
+    assert_eq!(log.log_start_offset().unwrap(), 3);
+    assert_eq!(log.log_end_offset().unwrap(), 3);
+    assert_eq!(log.append(data_batch()).unwrap(), 3);
+
+    let reopened = PartitionLog::open(log_dir.path(), "events", 0).unwrap();
+    assert_eq!(reopened.log_start_offset().unwrap(), 3);
+}
+
+#[test]
+fn test_read_batches_from_partial_tail() {
+    use crate::test_util::{data_batch, TempDir};
+
+    let log_dir = TempDir::new("partial-tail");
+    let log = PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+    log.append(data_batch()).unwrap();
+    log.append(data_batch()).unwrap();
+
+    // A writer is halfway through the third batch
+    let mut third = data_batch();
+    third.base_offset = 2;
+    let encoded = third.encode();
+    let mut segment = fs::OpenOptions::new()
+        .append(true)
+        .open(&log.segments().unwrap()[0])
+        .unwrap();
+    segment.write_all(&encoded[..encoded.len() / 2]).unwrap();
+    let offsets = |from| {
+        log.read_batches_from(from)
+            .unwrap()
+            .iter()
+            .map(|record_batch| record_batch.base_offset)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(offsets(1), vec![1]);
+
+    segment.write_all(&encoded[encoded.len() / 2..]).unwrap();
+    assert_eq!(offsets(1), vec![1, 2]);
+    assert_eq!(offsets(3), Vec::<i64>::new());
+}
+
+#[test]
+fn test_recover_truncates_at_corrupt_batch() {
+    use crate::test_util::{data_batch, TempDir};
+
+    let log_dir = TempDir::new("recover");
+    let log = PartitionLog::create(log_dir.path(), "events", 0).unwrap();
+    for _ in 0..3 {
+        log.append(data_batch()).unwrap();
+    }
+    log.roll(3).unwrap();
+    log.append(data_batch()).unwrap();
+    assert!(log.recover(false).unwrap().is_none());
+
+    // Flip a byte of the second batch, which its CRC covers
+    let segments = log.segments().unwrap();
+    let mut raw = fs::read(&segments[0]).unwrap();
+    let batch_size = raw.len() / 3;
+    raw[batch_size + 70] ^= 0xff;
+    fs::write(&segments[0], &raw).unwrap();
+    assert!(log.read_batches().is_err());
+
+    // Recovering only a torn tail leaves a corrupt batch alone
+    assert!(log.recover(true).is_err());
+    assert_eq!(log.segments().unwrap(), segments);
+    let recovery = log.recover(false).unwrap().unwrap();
+    assert_eq!(recovery.offset, 1);
+    assert_eq!(recovery.segment, segments[0]);
+    assert_eq!(recovery.truncated_bytes, 2 * batch_size as u64);
+    assert_eq!(recovery.deleted_segments, vec![segments[1].clone()]);
+    assert_eq!(log.segments().unwrap(), vec![segments[0].clone()]);
+    assert_eq!(log.read_batches().unwrap().len(), 1);
+    assert_eq!(log.append(data_batch()).unwrap(), 1);
+    assert_eq!(log.log_end_offset().unwrap(), 2);
+
+    // A batch cut short by a crash is dropped too
+    let encoded = data_batch().encode();
+    OpenOptions::new()
+        .append(true)
+        .open(&segments[0])
+        .unwrap()
+        .write_all(&encoded[..20])
+        .unwrap();
+    let recovery = log.recover(true).unwrap().unwrap();
+    assert_eq!(recovery.offset, 2);
+    assert_eq!(recovery.truncated_bytes, 20);
+    assert!(recovery.deleted_segments.is_empty());
+    assert_eq!(log.append(data_batch()).unwrap(), 2);
+    assert_eq!(log.read_batches().unwrap().len(), 3);
+}
@@ -0,0 +1,275 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::compression::Compression;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::record_batch::RecordBatch;
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+// Without replication, acks=all is the same as acks=1
+const NO_ACKS: i16 = 0;
+
+#[derive(Debug)]
+struct ProducePartition {
+    index: i32,
+    records: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct ProduceTopic {
+    name: String,
+    partitions: Vec<ProducePartition>,
+}
+
+// Batches a client may append: no control batches or delete horizons, which
+// only the broker writes, and one record per offset in the batch
+fn validate_batch(record_batch: &RecordBatch) -> Result<(), i16> {
+    let offsets_match =
+        record_batch.last_offset_delta as i64 + 1 == record_batch.records.len() as i64
+            && record_batch.records.iter().enumerate().all(|(i, record)| {
+                record.offset(record_batch) == record_batch.base_offset + i as i64
+            });
+    if record_batch.is_control() || record_batch.has_delete_horizon() || !offsets_match {
+        return Err(error_code::INVALID_RECORD);
+    }
+    Ok(())
+}
+
+// Base offset the batches were appended at and the log start offset after
+fn append_records(
+    broker: &Broker,
+    topic: &str,
+    partition: &ProducePartition,
+) -> Result<(i64, i64), i16> {
+    let storage_error = |_| error_code::KAFKA_STORAGE_ERROR;
+    let log = broker
+        .partition_log_for_append(topic, partition.index)
+        .map_err(storage_error)?
+        .ok_or(error_code::UNKNOWN_TOPIC_OR_PARTITION)?;
+
+    // Nothing is appended unless every batch parses
+    let mut record_batches = vec![];
+    let mut cursor: &[u8] = &partition.records;
+    while !cursor.is_empty() {
+        let (record_batch, rest) =
+            RecordBatch::parse(cursor).map_err(|_| error_code::CORRUPT_MESSAGE)?;
+        validate_batch(&record_batch)?;
+        record_batches.push(record_batch);
+        cursor = rest;
+    }
+    if record_batches.is_empty() {
+        return Err(error_code::CORRUPT_MESSAGE);
+    }
+
+    let mut base_offset = None;
+    for mut record_batch in record_batches {
+        // The topic's compression.type overrides what the producer used
+        let producer_compression = record_batch.compression().unwrap_or(Compression::None);
+        record_batch.set_compression(broker.compression_for(topic, producer_compression));
+        let offset = log.append(record_batch).map_err(storage_error)?;
+        base_offset.get_or_insert(offset);
+    }
+    let log_start_offset = log.log_start_offset().map_err(storage_error)?;
+    Ok((base_offset.unwrap_or(-1), log_start_offset))
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let transactional_id = input.get_compact_nullable_string();
+    let acks = input.get_i16();
+    let _timeout_ms = input.get_i32();
+    let topics_length = input.get_compact_array_length();
+    let topics: Vec<ProduceTopic> = (0..topics_length)
+        .map(|_| {
+            let name = input.get_compact_string();
+            let partitions_length = input.get_compact_array_length();
+            let partitions = (0..partitions_length)
+                .map(|_| {
+                    let index = input.get_i32();
+                    let records = input.get_compact_bytes().unwrap_or_default();
+                    input.skip_tagged_fields();
+                    ProducePartition { index, records }
+                })
+                .collect();
+            input.skip_tagged_fields();
+            ProduceTopic { name, partitions }
+        })
+        .collect();
+    input.skip_tagged_fields();
+
+    let transaction_authorized = transactional_id.as_ref().map_or(true, |transactional_id| {
+        broker.authorize(
+            session,
+            AclOperation::Write,
+            ResourceType::TransactionalId,
+            transactional_id,
+        )
+    });
+    let results: Vec<Vec<Result<(i64, i64), i16>>> = topics
+        .iter()
+        .map(|topic| {
+            let authorized = broker.authorize(
+                session,
+                AclOperation::Write,
+                ResourceType::Topic,
+                &topic.name,
+            );
+            topic
+                .partitions
+                .iter()
+                .map(|partition| {
+                    if !transaction_authorized {
+                        Err(error_code::TRANSACTIONAL_ID_AUTHORIZATION_FAILED)
+                    } else if !authorized {
+                        Err(error_code::TOPIC_AUTHORIZATION_FAILED)
+                    } else {
+                        append_records(broker, &topic.name, partition)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // Clients don't read a response to acks=0
+    if acks == NO_ACKS {
+        return vec![];
+    }
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_compact_array_length(topics.len());
+    for (topic, results) in topics.iter().zip(results) {
+        body.put_compact_string(&topic.name);
+        body.put_compact_array_length(topic.partitions.len());
+        for (partition, result) in topic.partitions.iter().zip(results) {
+            let (base_offset, log_start_offset) = *result.as_ref().unwrap_or(&(-1, -1));
+            body.put_i32(partition.index);
+            body.put_i16(result.err().unwrap_or(error_code::NONE));
+            body.put_i64(base_offset);
+            body.put_i64(-1); // Log append time, records keep their create time
+            body.put_i64(log_start_offset);
+            body.put_compact_array_length(0); // Record errors
+            body.put_compact_nullable_string(None);
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
+
+#[test]
+fn test_produce_appends_to_known_partitions() {
+    use crate::partition_log::PartitionLog;
+    use crate::test_util::{broker_with_topics, data_batch};
+
+    let (log_dir, broker) = broker_with_topics("produce", "", &[("events", [1; 16], 1)]);
+    let partition = |index, records| ProducePartition { index, records };
+
+    let records = [data_batch().encode(), data_batch().encode()].concat();
+    assert_eq!(
+        append_records(&broker, "events", &partition(0, records.clone())),
+        Ok((0, 0))
+    );
+    assert_eq!(
+        append_records(&broker, "events", &partition(0, records.clone())),
+        Ok((2, 0))
+    );
+    let log = PartitionLog::open(log_dir.path(), "events", 0).unwrap();
+    assert_eq!(log.log_end_offset().unwrap(), 4);
+
+    assert_eq!(
+        append_records(&broker, "events", &partition(1, records.clone())),
+        Err(error_code::UNKNOWN_TOPIC_OR_PARTITION)
+    );
+    assert_eq!(
+        append_records(&broker, "missing", &partition(0, records.clone())),
+        Err(error_code::UNKNOWN_TOPIC_OR_PARTITION)
+    );
+
+    // A batch cut short rejects the whole partition's records
+    let truncated = records[..records.len() - 1].to_vec();
+    assert_eq!(
+        append_records(&broker, "events", &partition(0, truncated)),
+        Err(error_code::CORRUPT_MESSAGE)
+    );
+    assert_eq!(log.log_end_offset().unwrap(), 4);
+}
+
+#[test]
+fn test_produce_rejects_batches_only_the_broker_writes() {
+    use crate::partition_log::PartitionLog;
+    use crate::record_batch::{Record, ABORT_MARKER, DELETE_HORIZON_FLAG};
+    use crate::test_util::{broker_with_topics, data_batch};
+
+    let (log_dir, broker) = broker_with_topics("produce-invalid", "", &[("events", [1; 16], 1)]);
+    let produce = |record_batch: RecordBatch| {
+        let partition = ProducePartition {
+            index: 0,
+            records: [data_batch().encode(), record_batch.encode()].concat(),
+        };
+        append_records(&broker, "events", &partition)
+    };
+
+    assert_eq!(
+        produce(RecordBatch::control(1, 0, ABORT_MARKER, 0)),
+        Err(error_code::INVALID_RECORD)
+    );
+    let records = || vec![Record::new(0, None, Some(b"data".to_vec()))];
+    assert_eq!(
+        produce(RecordBatch::new(DELETE_HORIZON_FLAG, -1, -1, 0, records())),
+        Err(error_code::INVALID_RECORD)
+    );
+    let mut gap = RecordBatch::new(0, -1, -1, 0, records());
+    gap.last_offset_delta = 1;
+    assert_eq!(produce(gap), Err(error_code::INVALID_RECORD));
+    let repeated = vec![
+        Record::new(0, None, Some(b"data".to_vec())),
+        Record::new(0, None, Some(b"data".to_vec())),
+    ];
+    let mut repeated = RecordBatch::new(0, -1, -1, 0, repeated);
+    repeated.last_offset_delta = 1;
+    assert_eq!(produce(repeated), Err(error_code::INVALID_RECORD));
+
+    // Nothing from a rejected request is appended
+    let log = PartitionLog::open(log_dir.path(), "events", 0).unwrap();
+    assert_eq!(log.log_end_offset().unwrap(), 0);
+    assert_eq!(produce(data_batch()), Ok((0, 0)));
+}
+
+#[test]
+fn test_produce_recompresses_to_the_topic_compression() {
+    use crate::cluser_metadata::ConfigRecord;
+    use crate::dynamic_config::TOPIC_RESOURCE;
+    use crate::partition_log::PartitionLog;
+    use crate::test_util::{broker_with_topics, data_batch};
+
+    let (log_dir, broker) = broker_with_topics(
+        "produce-compression",
+        "",
+        &[("events", [1; 16], 1), ("orders", [2; 16], 1)],
+    );
+    let record = ConfigRecord::new(TOPIC_RESOURCE, "events", "compression.type", Some("zstd"));
+    broker.append_metadata(vec![record.encode()]).unwrap();
+    let mut lz4_batch = data_batch();
+    lz4_batch.set_compression(Compression::Lz4);
+    let partition = ProducePartition {
+        index: 0,
+        records: lz4_batch.encode(),
+    };
+
+    // Topics without a compression.type keep the producer's
+    for (topic, compression) in [("events", Compression::Zstd), ("orders", Compression::Lz4)] {
+        assert_eq!(append_records(&broker, topic, &partition), Ok((0, 0)));
+        let log = PartitionLog::open(log_dir.path(), topic, 0).unwrap();
+        let stored = log.read_batches().unwrap();
+        assert_eq!(stored[0].compression(), Some(compression));
+        assert_eq!(stored[0].records[0].value.as_deref(), Some(&b"data"[..]));
+    }
+}
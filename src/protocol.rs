@@ -0,0 +1,302 @@
+use crate::varint::{PutVarint, Varint};
+use bytes::{Buf, BufMut};
+
+#[derive(Debug)]
+pub struct RequestHeader {
+    pub api_key: i16,
+    pub api_version: i16,
+    pub correlation_id: u32,
+    pub client_id: Option<String>,
+}
+
+impl RequestHeader {
+    // Reads the message size and a v2 request header, leaving the cursor at the body
+    pub fn parse(cursor: &mut &[u8]) -> RequestHeader {
+        let header = RequestHeader::parse_v1(cursor);
+        cursor.skip_tagged_fields();
+        header
+    }
+
+    // Same as `parse` for the v1 header of APIs without flexible versions.
+    // Requests only reach their handler once their header was checked.
+    pub fn parse_v1(cursor: &mut &[u8]) -> RequestHeader {
+        RequestHeader::try_parse_v1(cursor).expect("request header checked on read")
+    }
+
+    // Same as `parse_v1`, or None when the frame is too short for the header
+    pub fn try_parse_v1(cursor: &mut &[u8]) -> Option<RequestHeader> {
+        let _message_size = cursor.checked_get_i32()?;
+        let api_key = cursor.checked_get_i16()?;
+        let api_version = cursor.checked_get_i16()?;
+        let correlation_id = cursor.checked_get_i32()? as u32;
+        let client_id = cursor.checked_get_nullable_string()?;
+
+        Some(RequestHeader {
+            api_key,
+            api_version,
+            correlation_id,
+            client_id,
+        })
+    }
+}
+
+// Frames a flexible response body with a v1 response header
+pub fn encode_response(correlation_id: u32, body: &[u8]) -> Vec<u8> {
+    let mut header = vec![];
+    header.put_u32(correlation_id);
+    header.put_empty_tagged_fields();
+    frame_response(&header, body)
+}
+
+// Frames the body of an API without flexible versions with a v0 response header
+pub fn encode_response_v0(correlation_id: u32, body: &[u8]) -> Vec<u8> {
+    frame_response(&correlation_id.to_be_bytes(), body)
+}
+
+fn frame_response(header: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut response = vec![];
+    let message_size: u32 = header.len() as u32 + body.len() as u32;
+    response.put_u32(message_size);
+    response.extend_from_slice(header);
+    response.extend_from_slice(body);
+    response
+}
+
+pub trait CompactBuf {
+    fn get_nullable_string(&mut self) -> Option<String>;
+    fn get_compact_string(&mut self) -> String;
+    fn get_compact_nullable_string(&mut self) -> Option<String>;
+    fn get_compact_bytes(&mut self) -> Option<Vec<u8>>;
+    fn get_compact_array_length(&mut self) -> usize;
+    fn get_compact_nullable_array_length(&mut self) -> Option<usize>;
+    fn skip_tagged_fields(&mut self);
+}
+
+impl CompactBuf for &[u8] {
+    fn get_nullable_string(&mut self) -> Option<String> {
+        let length = self.get_i16();
+        if length < 0 {
+            return None;
+        }
+        let raw = self.copy_to_bytes(length as usize);
+        Some(String::from_utf8_lossy(&raw).to_string())
+    }
+
+    fn get_compact_string(&mut self) -> String {
+        self.get_compact_nullable_string().unwrap_or_default()
+    }
+
+    fn get_compact_nullable_string(&mut self) -> Option<String> {
+        self.get_compact_bytes()
+            .map(|raw| String::from_utf8_lossy(&raw).to_string())
+    }
+
+    fn get_compact_bytes(&mut self) -> Option<Vec<u8>> {
+        let length = self.get_unsigned_varint() as usize;
+        if length == 0 {
+            return None;
+        }
+        Some(self.copy_to_bytes(length - 1).to_vec())
+    }
+
+    fn get_compact_array_length(&mut self) -> usize {
+        // A null array (length 0) is treated as empty
+        (self.get_unsigned_varint() as usize).saturating_sub(1)
+    }
+
+    fn get_compact_nullable_array_length(&mut self) -> Option<usize> {
+        (self.get_unsigned_varint() as usize).checked_sub(1)
+    }
+
+    fn skip_tagged_fields(&mut self) {
+        let tagged_field_count = self.get_unsigned_varint();
+        for _ in 0..tagged_field_count {
+            let _tag = self.get_unsigned_varint();
+            let size = self.get_unsigned_varint() as usize;
+            self.advance(size);
+        }
+    }
+}
+
+// Reads of `Buf` and `CompactBuf` that return None instead of panicking when
+// the input is cut short, for data that wasn't framed by a client request
+pub trait CheckedBuf {
+    fn checked_get_u8(&mut self) -> Option<u8>;
+    fn checked_get_i8(&mut self) -> Option<i8>;
+    fn checked_get_u16(&mut self) -> Option<u16>;
+    fn checked_get_i16(&mut self) -> Option<i16>;
+    fn checked_get_i32(&mut self) -> Option<i32>;
+    fn checked_get_i64(&mut self) -> Option<i64>;
+    fn checked_get_f64(&mut self) -> Option<f64>;
+    fn checked_get_unsigned_varint(&mut self) -> Option<u64>;
+    fn checked_get_nullable_string(&mut self) -> Option<Option<String>>;
+    fn checked_get_compact_string(&mut self) -> Option<String>;
+    fn checked_get_compact_nullable_string(&mut self) -> Option<Option<String>>;
+    fn checked_get_compact_bytes(&mut self) -> Option<Option<Vec<u8>>>;
+    fn checked_get_compact_array_length(&mut self) -> Option<usize>;
+    fn checked_get_compact_nullable_array_length(&mut self) -> Option<Option<usize>>;
+    fn checked_get_uuid(&mut self) -> Option<[u8; 16]>;
+    fn checked_get_tagged_fields(&mut self) -> Option<Vec<(u64, Vec<u8>)>>;
+    fn checked_skip_tagged_fields(&mut self) -> Option<()>;
+}
+
+fn try_take<'a>(cursor: &mut &'a [u8], length: usize) -> Option<&'a [u8]> {
+    if length > cursor.len() {
+        return None;
+    }
+    let (taken, rest) = cursor.split_at(length);
+    *cursor = rest;
+    Some(taken)
+}
+
+impl CheckedBuf for &[u8] {
+    fn checked_get_u8(&mut self) -> Option<u8> {
+        (self.remaining() >= 1).then(|| self.get_u8())
+    }
+
+    fn checked_get_i8(&mut self) -> Option<i8> {
+        (self.remaining() >= 1).then(|| self.get_i8())
+    }
+
+    fn checked_get_u16(&mut self) -> Option<u16> {
+        (self.remaining() >= 2).then(|| self.get_u16())
+    }
+
+    fn checked_get_i16(&mut self) -> Option<i16> {
+        (self.remaining() >= 2).then(|| self.get_i16())
+    }
+
+    fn checked_get_i32(&mut self) -> Option<i32> {
+        (self.remaining() >= 4).then(|| self.get_i32())
+    }
+
+    fn checked_get_i64(&mut self) -> Option<i64> {
+        (self.remaining() >= 8).then(|| self.get_i64())
+    }
+
+    fn checked_get_f64(&mut self) -> Option<f64> {
+        (self.remaining() >= 8).then(|| self.get_f64())
+    }
+
+    fn checked_get_unsigned_varint(&mut self) -> Option<u64> {
+        // At most 10 bytes, the last one without the continuation bit
+        let end = self.iter().take(10).position(|byte| byte & 0x80 == 0)?;
+        let mut varint = try_take(self, end + 1)?;
+        Some(varint.get_unsigned_varint())
+    }
+
+    fn checked_get_nullable_string(&mut self) -> Option<Option<String>> {
+        let Ok(length) = usize::try_from(self.checked_get_i16()?) else {
+            return Some(None);
+        };
+        let raw = try_take(self, length)?;
+        Some(Some(String::from_utf8_lossy(raw).to_string()))
+    }
+
+    fn checked_get_compact_string(&mut self) -> Option<String> {
+        Some(
+            self.checked_get_compact_nullable_string()?
+                .unwrap_or_default(),
+        )
+    }
+
+    fn checked_get_compact_nullable_string(&mut self) -> Option<Option<String>> {
+        Some(
+            self.checked_get_compact_bytes()?
+                .map(|raw| String::from_utf8_lossy(&raw).to_string()),
+        )
+    }
+
+    fn checked_get_compact_bytes(&mut self) -> Option<Option<Vec<u8>>> {
+        let length = usize::try_from(self.checked_get_unsigned_varint()?).ok()?;
+        if length == 0 {
+            return Some(None);
+        }
+        Some(Some(try_take(self, length - 1)?.to_vec()))
+    }
+
+    fn checked_get_compact_array_length(&mut self) -> Option<usize> {
+        Some(
+            self.checked_get_compact_nullable_array_length()?
+                .unwrap_or(0),
+        )
+    }
+
+    fn checked_get_compact_nullable_array_length(&mut self) -> Option<Option<usize>> {
+        let length = usize::try_from(self.checked_get_unsigned_varint()?)
+            .ok()?
+            .checked_sub(1);
+        // Every element takes at least a byte, so a longer array is malformed
+        if length.is_some_and(|length| length > self.len()) {
+            return None;
+        }
+        Some(length)
+    }
+
+    fn checked_get_uuid(&mut self) -> Option<[u8; 16]> {
+        try_take(self, 16)?.try_into().ok()
+    }
+
+    fn checked_get_tagged_fields(&mut self) -> Option<Vec<(u64, Vec<u8>)>> {
+        let tagged_field_count = self.checked_get_unsigned_varint()?;
+        (0..tagged_field_count)
+            .map(|_| {
+                let tag = self.checked_get_unsigned_varint()?;
+                let size = usize::try_from(self.checked_get_unsigned_varint()?).ok()?;
+                Some((tag, try_take(self, size)?.to_vec()))
+            })
+            .collect()
+    }
+
+    fn checked_skip_tagged_fields(&mut self) -> Option<()> {
+        let tagged_field_count = self.checked_get_unsigned_varint()?;
+        for _ in 0..tagged_field_count {
+            let _tag = self.checked_get_unsigned_varint()?;
+            let size = usize::try_from(self.checked_get_unsigned_varint()?).ok()?;
+            try_take(self, size)?;
+        }
+        Some(())
+    }
+}
+
+pub trait CompactBufMut {
+    fn put_string(&mut self, value: &str);
+    fn put_compact_string(&mut self, value: &str);
+    fn put_compact_nullable_string(&mut self, value: Option<&str>);
+    fn put_compact_bytes(&mut self, value: Option<&[u8]>);
+    fn put_compact_array_length(&mut self, length: usize);
+    fn put_empty_tagged_fields(&mut self);
+}
+
+impl CompactBufMut for Vec<u8> {
+    fn put_string(&mut self, value: &str) {
+        self.put_i16(value.len() as i16);
+        self.extend_from_slice(value.as_bytes());
+    }
+
+    fn put_compact_string(&mut self, value: &str) {
+        self.put_compact_bytes(Some(value.as_bytes()));
+    }
+
+    fn put_compact_nullable_string(&mut self, value: Option<&str>) {
+        self.put_compact_bytes(value.map(str::as_bytes));
+    }
+
+    fn put_compact_bytes(&mut self, value: Option<&[u8]>) {
+        match value {
+            Some(value) => {
+                self.put_unsigned_varint(value.len() as u64 + 1);
+                self.extend_from_slice(value);
+            }
+            None => self.put_unsigned_varint(0),
+        }
+    }
+
+    fn put_compact_array_length(&mut self, length: usize) {
+        self.put_unsigned_varint(length as u64 + 1);
+    }
+
+    fn put_empty_tagged_fields(&mut self) {
+        self.put_u8(0);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::config::BrokerConfig;
+use crate::metadata_image::MetadataImage;
+use crate::record_batch::now_ms;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, RwLock};
+
+pub const USER: &str = "user";
+pub const CLIENT_ID: &str = "client-id";
+pub const IP: &str = "ip";
+
+pub const PRODUCER_BYTE_RATE: &str = "producer_byte_rate";
+pub const CONSUMER_BYTE_RATE: &str = "consumer_byte_rate";
+// Percentage of one request handler thread's time
+pub const REQUEST_PERCENTAGE: &str = "request_percentage";
+pub const CONTROLLER_MUTATION_RATE: &str = "controller_mutation_rate";
+pub const CONNECTION_CREATION_RATE: &str = "connection_creation_rate";
+
+// Entity type to entity name, where a None name is the default entity of that type
+pub type QuotaEntity = BTreeMap<String, Option<String>>;
+
+// Checks the entity types of an entity to alter or describe
+pub fn validate_entity(entity: &[(String, Option<String>)]) -> Result<QuotaEntity, String> {
+    let mut validated = QuotaEntity::new();
+    for (entity_type, entity_name) in entity {
+        if ![USER, CLIENT_ID, IP].contains(&entity_type.as_str()) {
+            return Err(format!(
+                "Unhandled client quota entity type: {}",
+                entity_type
+            ));
+        }
+        if validated
+            .insert(entity_type.clone(), entity_name.clone())
+            .is_some()
+        {
+            return Err(format!("Duplicate {} entity type", entity_type));
+        }
+    }
+    if validated.is_empty() {
+        return Err("Invalid empty client quota entity".to_string());
+    }
+    if validated.contains_key(IP) && validated.len() > 1 {
+        return Err("Invalid quota entity combination".to_string());
+    }
+    Ok(validated)
+}
+
+// Checks a quota key is known for the entity and its value has the right type
+pub fn validate_quota(entity: &QuotaEntity, key: &str, value: f64) -> Result<(), String> {
+    let (keys, long_keys): (&[&str], &[&str]) = if entity.contains_key(IP) {
+        (&[CONNECTION_CREATION_RATE], &[CONNECTION_CREATION_RATE])
+    } else {
+        (
+            &[
+                PRODUCER_BYTE_RATE,
+                CONSUMER_BYTE_RATE,
+                REQUEST_PERCENTAGE,
+                CONTROLLER_MUTATION_RATE,
+            ],
+            &[PRODUCER_BYTE_RATE, CONSUMER_BYTE_RATE],
+        )
+    };
+    if !keys.contains(&key) {
+        return Err(format!("Invalid configuration key {}", key));
+    }
+    if long_keys.contains(&key) && value.fract() != 0.0 {
+        return Err(format!("Configuration {} must be a Long value", key));
+    }
+    if value <= 0.0 {
+        return Err(format!("Quota {} must be greater than 0", key));
+    }
+    Ok(())
+}
+
+// Entities whose quotas apply to a client, most specific first
+fn candidate_entities(user: &str, client_id: &str) -> Vec<QuotaEntity> {
+    let entity = |components: &[(&str, Option<&str>)]| -> QuotaEntity {
+        components
+            .iter()
+            .map(|(entity_type, entity_name)| {
+                (entity_type.to_string(), entity_name.map(str::to_string))
+            })
+            .collect()
+    };
+    vec![
+        entity(&[(USER, Some(user)), (CLIENT_ID, Some(client_id))]),
+        entity(&[(USER, Some(user)), (CLIENT_ID, None)]),
+        entity(&[(USER, Some(user))]),
+        entity(&[(USER, None), (CLIENT_ID, Some(client_id))]),
+        entity(&[(USER, None), (CLIENT_ID, None)]),
+        entity(&[(USER, None)]),
+        entity(&[(CLIENT_ID, Some(client_id))]),
+        entity(&[(CLIENT_ID, None)]),
+    ]
+}
+
+// Samples of quota.window.size.seconds, quota.window.num of which are kept
+#[derive(Debug, Clone, Copy)]
+struct QuotaWindow {
+    samples: i64,
+    sample_ms: i64,
+}
+
+// Values recorded per second over a sliding window of samples
+#[derive(Debug, Default)]
+struct Rate {
+    // Start time and sum of each sample
+    samples: VecDeque<(i64, f64)>,
+}
+
+impl Rate {
+    fn purge(&mut self, window: QuotaWindow, now: i64) {
+        while self
+            .samples
+            .front()
+            .is_some_and(|(start, _)| *start <= now - window.samples * window.sample_ms)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    fn record(&mut self, window: QuotaWindow, value: f64, now: i64) {
+        self.purge(window, now);
+        match self.samples.back_mut() {
+            Some((start, sum)) if now < *start + window.sample_ms => *sum += value,
+            _ => self.samples.push_back((now, value)),
+        }
+    }
+
+    // The rate and the length of the window it was measured over, which spans
+    // at least all but one sample so a new client isn't throttled on its first burst
+    fn measure(&mut self, window: QuotaWindow, now: i64) -> (f64, i64) {
+        self.purge(window, now);
+        let oldest = self.samples.front().map_or(now, |(start, _)| *start);
+        let window_ms = (now - oldest).max((window.samples - 1) * window.sample_ms);
+        let sum: f64 = self.samples.iter().map(|(_, sum)| sum).sum();
+        (sum * 1000.0 / window_ms as f64, window_ms)
+    }
+}
+
+// Quota key, and the user and client-id usage is tracked for, which are
+// only set when the quota applying to the client is configured on them
+type RateKey = (String, Option<String>, Option<String>);
+
+// Client quota configs and the usage measured against them
+#[derive(Debug)]
+pub struct ClientQuotas {
+    window: QuotaWindow,
+    configs: RwLock<BTreeMap<QuotaEntity, BTreeMap<String, f64>>>,
+    rates: Mutex<HashMap<RateKey, Rate>>,
+}
+
+impl ClientQuotas {
+    pub fn new(config: &BrokerConfig) -> ClientQuotas {
+        let get = |key, default| {
+            config
+                .get(key)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        ClientQuotas {
+            window: QuotaWindow {
+                samples: get("quota.window.num", 11).max(1),
+                sample_ms: get("quota.window.size.seconds", 1).max(1) * 1000,
+            },
+            configs: RwLock::new(BTreeMap::new()),
+            rates: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn load(&self, metadata: &MetadataImage) {
+        *self.configs.write().unwrap() = metadata.client_quotas().clone();
+    }
+
+    pub fn describe(&self) -> BTreeMap<QuotaEntity, BTreeMap<String, f64>> {
+        self.configs.read().unwrap().clone()
+    }
+
+    // The most specific quota of `key` applying to the client
+    fn quota(&self, key: &str, user: &str, client_id: &str) -> Option<(RateKey, f64)> {
+        let configs = self.configs.read().unwrap();
+        candidate_entities(user, client_id)
+            .into_iter()
+            .find_map(|entity| {
+                let quota = *configs.get(&entity)?.get(key)?;
+                let rate_key = (
+                    key.to_string(),
+                    entity.contains_key(USER).then(|| user.to_string()),
+                    entity
+                        .contains_key(CLIENT_ID)
+                        .then(|| client_id.to_string()),
+                );
+                Some((rate_key, quota))
+            })
+    }
+
+    fn record_at(&self, key: &str, user: &str, client_id: &str, value: f64, now: i64) {
+        if let Some((rate_key, _)) = self.quota(key, user, client_id) {
+            let mut rates = self.rates.lock().unwrap();
+            rates
+                .entry(rate_key)
+                .or_default()
+                .record(self.window, value, now);
+        }
+    }
+
+    pub fn record(&self, key: &str, user: &str, client_id: &str, value: f64) {
+        self.record_at(key, user, client_id, value, now_ms());
+    }
+
+    // How long the client has to back off for its rate to fall back to the
+    // quota, capped at the length of the whole window
+    fn throttle_time_ms_at(&self, key: &str, user: &str, client_id: &str, now: i64) -> i32 {
+        let Some((rate_key, quota)) = self.quota(key, user, client_id) else {
+            return 0;
+        };
+        let mut rates = self.rates.lock().unwrap();
+        let Some(rate) = rates.get_mut(&rate_key) else {
+            return 0;
+        };
+        let (measured, window_ms) = rate.measure(self.window, now);
+        if measured <= quota {
+            return 0;
+        }
+        let throttle_time_ms = (measured - quota) / quota * window_ms as f64;
+        throttle_time_ms.min((self.window.samples * self.window.sample_ms) as f64) as i32
+    }
+
+    pub fn throttle_time_ms(&self, key: &str, user: &str, client_id: &str) -> i32 {
+        self.throttle_time_ms_at(key, user, client_id, now_ms())
+    }
+}
+
+#[test]
+fn test_client_quotas() {
+    let quotas = ClientQuotas::new(&BrokerConfig::default());
+    let entity = |components: &[(&str, Option<&str>)]| {
+        validate_entity(
+            &components
+                .iter()
+                .map(|(entity_type, name)| (entity_type.to_string(), name.map(str::to_string)))
+                .collect::<Vec<_>>(),
+        )
+    };
+    *quotas.configs.write().unwrap() = BTreeMap::from([
+        (
+            entity(&[(USER, None)]).unwrap(),
+            BTreeMap::from([(REQUEST_PERCENTAGE.to_string(), 10.0)]),
+        ),
+        (
+            entity(&[(USER, Some("alice")), (CLIENT_ID, None)]).unwrap(),
+            BTreeMap::from([(PRODUCER_BYTE_RATE.to_string(), 1024.0)]),
+        ),
+    ]);
+
+    // The producer quota is tracked per client id of alice, the default
+    // user's request quota is tracked per user
+    let (rate_key, quota) = quotas.quota(PRODUCER_BYTE_RATE, "alice", "app").unwrap();
+    assert_eq!(
+        rate_key,
+        (
+            PRODUCER_BYTE_RATE.to_string(),
+            Some("alice".to_string()),
+            Some("app".to_string())
+        )
+    );
+    assert_eq!(quota, 1024.0);
+    assert!(quotas.quota(PRODUCER_BYTE_RATE, "bob", "app").is_none());
+    assert_eq!(
+        quotas.quota(REQUEST_PERCENTAGE, "bob", "app").unwrap().0,
+        (
+            REQUEST_PERCENTAGE.to_string(),
+            Some("bob".to_string()),
+            None
+        )
+    );
+
+    // 10 seconds worth of 10% spent at once is 100% over the quota
+    quotas.record_at(REQUEST_PERCENTAGE, "bob", "app", 200.0, 0);
+    assert_eq!(
+        quotas.throttle_time_ms_at(REQUEST_PERCENTAGE, "bob", "app", 0),
+        10_000
+    );
+    assert_eq!(
+        quotas.throttle_time_ms_at(REQUEST_PERCENTAGE, "bob", "app", 20_000),
+        0
+    );
+    assert_eq!(
+        quotas.throttle_time_ms_at(REQUEST_PERCENTAGE, "carol", "app", 0),
+        0
+    );
+
+    assert!(entity(&[(IP, None), (USER, None)]).is_err());
+    assert!(entity(&[(USER, None), (USER, Some("alice"))]).is_err());
+    let user = entity(&[(USER, None)]).unwrap();
+    assert!(validate_quota(&user, PRODUCER_BYTE_RATE, 1.5).is_err());
+    assert!(validate_quota(&user, CONNECTION_CREATION_RATE, 1.0).is_err());
+    assert!(validate_quota(&user, REQUEST_PERCENTAGE, 1.5).is_ok());
+}
@@ -0,0 +1,578 @@
+use crate::compression::{Compression, COMPRESSION_MASK};
+use crate::control_record::ControlRecord;
+use crate::varint::{PutVarint, Varint};
+use bytes::{Buf, BufMut, Bytes};
+
+use std::io::{self, ErrorKind};
+
+pub const TIMESTAMP_TYPE_FLAG: u16 = 0x08;
+pub const TRANSACTIONAL_FLAG: u16 = 0x10;
+pub const CONTROL_FLAG: u16 = 0x20;
+// The base timestamp holds when the compacted batch's tombstones may be removed
+pub const DELETE_HORIZON_FLAG: u16 = 0x40;
+
+// Control record keys: version (0) followed by the record type, the
+// transaction markers being the first two
+pub const ABORT_MARKER: i16 = 0;
+pub const COMMIT_MARKER: i16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampType {
+    CreateTime,
+    // Records take the batch's max timestamp, set by the broker on append
+    LogAppendTime,
+}
+
+// Bytes of a batch up to and including the record count
+const BATCH_OVERHEAD: usize = 61;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CorruptRecord {
+    #[error("Record batch of {size} bytes is truncated to {available}")]
+    Truncated { size: usize, available: usize },
+    #[error("Invalid record batch length {0}")]
+    InvalidBatchLength(i32),
+    #[error("Unsupported record batch magic {0}")]
+    InvalidMagic(u8),
+    #[error("Record batch CRC {stored:#010x} doesn't match its computed CRC {computed:#010x}")]
+    CrcMismatch { stored: u32, computed: u32 },
+    #[error("Unknown record batch compression {0}")]
+    InvalidCompression(u16),
+    #[error("Failed to decompress record batch: {0}")]
+    Decompression(io::Error),
+    #[error("Record {0} of the batch is malformed")]
+    InvalidRecord(usize),
+    #[error("{0} bytes follow the last record of the batch")]
+    TrailingBytes(usize),
+}
+
+impl From<CorruptRecord> for io::Error {
+    fn from(error: CorruptRecord) -> io::Error {
+        io::Error::new(ErrorKind::InvalidData, error)
+    }
+}
+
+// Signed varint that may be cut short or longer than 10 bytes
+fn get_varint(cursor: &mut &[u8]) -> Option<i64> {
+    let end = cursor.iter().take(10).position(|byte| byte & 0x80 == 0)?;
+    let (mut varint, rest) = cursor.split_at(end + 1);
+    *cursor = rest;
+    Some(varint.get_signed_varint())
+}
+
+fn take<'a>(cursor: &mut &'a [u8], length: i64) -> Option<&'a [u8]> {
+    let length = usize::try_from(length)
+        .ok()
+        .filter(|length| *length <= cursor.len())?;
+    let (taken, rest) = cursor.split_at(length);
+    *cursor = rest;
+    Some(taken)
+}
+
+// Length prefixed bytes, where a negative length means null
+fn get_nullable_bytes(cursor: &mut &[u8]) -> Option<Option<Vec<u8>>> {
+    let length = get_varint(cursor)?;
+    if length < 0 {
+        return Some(None);
+    }
+    take(cursor, length).map(|bytes| Some(bytes.to_vec()))
+}
+
+fn put_nullable_bytes(output: &mut Vec<u8>, bytes: Option<&[u8]>) {
+    match bytes {
+        Some(bytes) => {
+            output.put_signed_varint(bytes.len() as i64);
+            output.extend_from_slice(bytes);
+        }
+        None => output.put_signed_varint(-1),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    _length: i64,
+    _attributes: u8,
+    timestamp_delta: i64,
+    offset_delta: i32,
+    _key_length: i64,
+    pub key: Option<Vec<u8>>,
+    _value_length: i64,
+    pub value: Option<Vec<u8>>,
+    pub headers: Vec<(String, Option<Bytes>)>,
+}
+
+impl Record {
+    pub fn new(offset_delta: i32, key: Option<Vec<u8>>, value: Option<Vec<u8>>) -> Record {
+        Record {
+            _length: 0,
+            _attributes: 0,
+            timestamp_delta: 0,
+            offset_delta,
+            _key_length: key.as_ref().map_or(-1, |key| key.len() as i64),
+            key,
+            _value_length: value.as_ref().map_or(-1, |value| value.len() as i64),
+            value,
+            headers: vec![],
+        }
+    }
+
+    // Offset of the record within `record_batch`
+    pub fn offset(&self, record_batch: &RecordBatch) -> i64 {
+        record_batch.base_offset + self.offset_delta as i64
+    }
+
+    // Timestamp of the record within `record_batch`
+    pub fn timestamp(&self, record_batch: &RecordBatch) -> i64 {
+        match record_batch.timestamp_type() {
+            TimestampType::CreateTime => record_batch.base_timestamp + self.timestamp_delta,
+            TimestampType::LogAppendTime => record_batch.max_timestamp,
+        }
+    }
+
+    // Parses a record, bounded by its length, or None when it is malformed
+    fn parse(cursor: &mut &[u8]) -> Option<Record> {
+        let length = get_varint(cursor)?;
+        let mut record = take(cursor, length)?;
+        let attributes = record.first().copied()?;
+        record = &record[1..];
+        let timestamp_delta = get_varint(&mut record)?;
+        let offset_delta = i32::try_from(get_varint(&mut record)?).ok()?;
+        let key = get_nullable_bytes(&mut record)?;
+        let value = get_nullable_bytes(&mut record)?;
+        let header_count = usize::try_from(get_varint(&mut record)?).ok()?;
+        // Each header takes at least 2 bytes
+        let mut headers = Vec::with_capacity(header_count.min(record.len() / 2));
+        for _ in 0..header_count {
+            let header_key = String::from_utf8(get_nullable_bytes(&mut record)??).ok()?;
+            let header_value = get_nullable_bytes(&mut record)?.map(Bytes::from);
+            headers.push((header_key, header_value));
+        }
+        if !record.is_empty() {
+            return None;
+        }
+
+        Some(Record {
+            _length: length,
+            _attributes: attributes,
+            timestamp_delta,
+            offset_delta,
+            _key_length: key.as_ref().map_or(-1, |key| key.len() as i64),
+            key,
+            _value_length: value.as_ref().map_or(-1, |value| value.len() as i64),
+            value,
+            headers,
+        })
+    }
+
+    fn encode(&self, output: &mut Vec<u8>) {
+        let mut body = vec![];
+        body.put_u8(self._attributes);
+        body.put_signed_varint(self.timestamp_delta);
+        body.put_signed_varint(self.offset_delta as i64);
+        put_nullable_bytes(&mut body, self.key.as_deref());
+        put_nullable_bytes(&mut body, self.value.as_deref());
+        body.put_signed_varint(self.headers.len() as i64);
+        for (header_key, header_value) in &self.headers {
+            put_nullable_bytes(&mut body, Some(header_key.as_bytes()));
+            put_nullable_bytes(&mut body, header_value.as_deref());
+        }
+
+        output.put_signed_varint(body.len() as i64);
+        output.extend_from_slice(&body);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordBatch {
+    pub base_offset: i64,
+    _batch_length: i32,
+    pub partition_leader_epoch: i32,
+    _magic_byte: u8,
+    _crc: i32,
+    pub attributes: u16,
+    pub last_offset_delta: i32,
+    pub base_timestamp: i64,
+    pub max_timestamp: i64,
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+    pub base_sequence: i32,
+    _records_length: u32,
+    pub records: Vec<Record>,
+}
+
+impl RecordBatch {
+    pub fn new(
+        attributes: u16,
+        producer_id: i64,
+        producer_epoch: i16,
+        timestamp: i64,
+        records: Vec<Record>,
+    ) -> RecordBatch {
+        RecordBatch {
+            base_offset: 0,
+            _batch_length: 0,
+            partition_leader_epoch: 0,
+            _magic_byte: 2,
+            _crc: 0,
+            attributes,
+            last_offset_delta: records.len() as i32 - 1,
+            base_timestamp: timestamp,
+            max_timestamp: timestamp,
+            producer_id,
+            producer_epoch,
+            base_sequence: -1,
+            _records_length: records.len() as u32,
+            records,
+        }
+    }
+
+    pub fn control(
+        producer_id: i64,
+        producer_epoch: i16,
+        marker: i16,
+        coordinator_epoch: i32,
+    ) -> RecordBatch {
+        let mut key = vec![];
+        key.put_i16(0); // Version
+        key.put_i16(marker);
+        let mut value = vec![];
+        value.put_i16(0); // Version
+        value.put_i32(coordinator_epoch);
+
+        RecordBatch::new(
+            TRANSACTIONAL_FLAG | CONTROL_FLAG,
+            producer_id,
+            producer_epoch,
+            now_ms(),
+            vec![Record::new(0, Some(key), Some(value))],
+        )
+    }
+
+    pub fn timestamp_type(&self) -> TimestampType {
+        if self.attributes & TIMESTAMP_TYPE_FLAG != 0 {
+            TimestampType::LogAppendTime
+        } else {
+            TimestampType::CreateTime
+        }
+    }
+
+    pub fn is_transactional(&self) -> bool {
+        self.attributes & TRANSACTIONAL_FLAG != 0
+    }
+
+    pub fn is_control(&self) -> bool {
+        self.attributes & CONTROL_FLAG != 0
+    }
+
+    // Only log compaction sets the delete horizon
+    pub fn has_delete_horizon(&self) -> bool {
+        self.attributes & DELETE_HORIZON_FLAG != 0
+    }
+
+    pub fn compression(&self) -> Option<Compression> {
+        Compression::from_attributes(self.attributes)
+    }
+
+    // Records are held decompressed, so this only changes how they are encoded
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.attributes = self.attributes & !COMPRESSION_MASK | compression.code();
+    }
+
+    pub fn last_offset(&self) -> i64 {
+        self.base_offset + self.last_offset_delta as i64
+    }
+
+    // Records of a control batch, skipping those too short to decode
+    pub fn control_records(&self) -> Vec<ControlRecord> {
+        if !self.is_control() {
+            return vec![];
+        }
+        self.records
+            .iter()
+            .filter_map(ControlRecord::parse)
+            .collect()
+    }
+
+    // Marker type of a transaction marker batch, read from its single record
+    pub fn control_marker(&self) -> Option<i16> {
+        match self.control_records().first()? {
+            ControlRecord::Abort { .. } => Some(ABORT_MARKER),
+            ControlRecord::Commit { .. } => Some(COMMIT_MARKER),
+            _ => None,
+        }
+    }
+
+    // Bytes taken by the batch at the start of `input`, or None when it was
+    // cut short, e.g. while still being written
+    pub fn size(input: &[u8]) -> Option<usize> {
+        let mut cursor = input.get(8..12)?;
+        let size = 12 + usize::try_from(cursor.get_i32()).ok()?;
+        (size <= input.len()).then_some(size)
+    }
+
+    // Parses the batch at the start of `input`, returning it and the bytes
+    // after it. Its bounds come from the batch length, and its CRC covers
+    // the attributes to the end of the batch.
+    pub fn parse(input: &[u8]) -> Result<(RecordBatch, &[u8]), CorruptRecord> {
+        if input.len() < BATCH_OVERHEAD {
+            return Err(CorruptRecord::Truncated {
+                size: BATCH_OVERHEAD,
+                available: input.len(),
+            });
+        }
+        let mut cursor = input;
+        let base_offset = cursor.get_i64();
+        let batch_length = cursor.get_i32();
+        let size = usize::try_from(batch_length)
+            .ok()
+            .map(|batch_length| batch_length + 12)
+            .filter(|size| *size >= BATCH_OVERHEAD)
+            .ok_or(CorruptRecord::InvalidBatchLength(batch_length))?;
+        if size > input.len() {
+            return Err(CorruptRecord::Truncated {
+                size,
+                available: input.len(),
+            });
+        }
+        let (batch, rest) = input.split_at(size);
+        let mut cursor = &batch[12..];
+        let partition_leader_epoch = cursor.get_i32();
+        let magic_byte = cursor.get_u8();
+        if magic_byte != 2 {
+            return Err(CorruptRecord::InvalidMagic(magic_byte));
+        }
+        let crc = cursor.get_u32();
+        let computed = crc32c::crc32c(cursor);
+        if crc != computed {
+            return Err(CorruptRecord::CrcMismatch {
+                stored: crc,
+                computed,
+            });
+        }
+        let attributes = cursor.get_u16();
+        let last_offset_delta = cursor.get_i32();
+        let base_timestamp = cursor.get_i64();
+        let max_timestamp = cursor.get_i64();
+        let producer_id = cursor.get_i64();
+        let producer_epoch = cursor.get_i16();
+        let base_sequence = cursor.get_i32();
+        let records_length = cursor.get_u32();
+
+        // Only the records themselves are compressed
+        let compression = Compression::from_attributes(attributes).ok_or(
+            CorruptRecord::InvalidCompression(attributes & COMPRESSION_MASK),
+        )?;
+        let decompressed;
+        if compression != Compression::None {
+            decompressed = compression
+                .decompress(cursor)
+                .map_err(CorruptRecord::Decompression)?;
+            cursor = &decompressed;
+        }
+
+        // Every record takes at least 7 bytes, so a bogus count can't make
+        // us allocate more than the batch holds
+        let mut records = Vec::with_capacity((records_length as usize).min(cursor.len() / 7));
+        for _ in 0..records_length {
+            records.push(
+                Record::parse(&mut cursor).ok_or(CorruptRecord::InvalidRecord(records.len()))?,
+            );
+        }
+        if !cursor.is_empty() {
+            return Err(CorruptRecord::TrailingBytes(cursor.len()));
+        }
+
+        let record_batch = RecordBatch {
+            base_offset,
+            _batch_length: batch_length,
+            partition_leader_epoch,
+            _magic_byte: magic_byte,
+            _crc: crc as i32,
+            attributes,
+            last_offset_delta,
+            base_timestamp,
+            max_timestamp,
+            producer_id,
+            producer_epoch,
+            base_sequence,
+            _records_length: records_length,
+            records,
+        };
+
+        Ok((record_batch, rest))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        // Everything covered by the CRC: attributes to the end of the batch
+        let mut body = vec![];
+        body.put_u16(self.attributes);
+        body.put_i32(self.last_offset_delta);
+        body.put_i64(self.base_timestamp);
+        body.put_i64(self.max_timestamp);
+        body.put_i64(self.producer_id);
+        body.put_i16(self.producer_epoch);
+        body.put_i32(self.base_sequence);
+        body.put_u32(self.records.len() as u32);
+        let mut records = vec![];
+        for record in &self.records {
+            record.encode(&mut records);
+        }
+        let compression = self.compression().unwrap_or(Compression::None);
+        // Compressing into memory only fails on a codec bug
+        body.extend(compression.compress(&records).expect("compress records"));
+
+        let mut output = vec![];
+        output.put_i64(self.base_offset);
+        // Partition leader epoch, magic and CRC precede the body
+        output.put_i32(body.len() as i32 + 9);
+        output.put_i32(self.partition_leader_epoch);
+        output.put_u8(2);
+        output.put_u32(crc32c::crc32c(&body));
+        output.extend_from_slice(&body);
+        output
+    }
+}
+
+pub fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+#[test]
+fn test_encode_roundtrip() {
+    let records = vec![
+        Record::new(0, Some(b"key".to_vec()), Some(b"value".to_vec())),
+        Record::new(1, None, Some(vec![])),
+    ];
+    let mut batch = RecordBatch::new(TRANSACTIONAL_FLAG, 7, 2, 1_700_000_000_000, records);
+    batch.base_offset = 42;
+
+    let encoded = batch.encode();
+    let (parsed, rest) = RecordBatch::parse(&encoded).unwrap();
+
+    assert!(rest.is_empty());
+    assert_eq!(parsed._batch_length as usize, encoded.len() - 12);
+    assert_eq!(parsed._crc as u32, crc32c::crc32c(&encoded[21..]));
+    assert_eq!(parsed.base_offset, 42);
+    assert_eq!(parsed.last_offset(), 43);
+    assert_eq!(parsed.producer_id, 7);
+    assert_eq!(parsed.records[0].key.as_deref(), Some(&b"key"[..]));
+    assert_eq!(parsed.records[0].value.as_deref(), Some(&b"value"[..]));
+    assert_eq!(parsed.records[1].key, None);
+}
+
+#[test]
+fn test_control_marker() {
+    let batch = RecordBatch::control(7, 2, COMMIT_MARKER, 0);
+    let (parsed, _) = RecordBatch::parse(&batch.encode()).unwrap();
+    assert_eq!(parsed.control_marker(), Some(COMMIT_MARKER));
+    assert_eq!(parsed.attributes & TRANSACTIONAL_FLAG, TRANSACTIONAL_FLAG);
+}
+
+#[test]
+fn test_parse_corrupt() {
+    let batch = RecordBatch::new(0, -1, -1, 0, vec![Record::new(0, None, Some(vec![1]))]);
+    let encoded = batch.encode();
+    let parse = |input: &[u8]| RecordBatch::parse(input).err();
+
+    assert!(matches!(
+        parse(&encoded[..encoded.len() - 1]),
+        Some(CorruptRecord::Truncated { .. })
+    ));
+    let mut corrupt = encoded.clone();
+    *corrupt.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        parse(&corrupt),
+        Some(CorruptRecord::CrcMismatch { .. })
+    ));
+    let mut corrupt = encoded.clone();
+    corrupt[16] = 1;
+    assert!(matches!(
+        parse(&corrupt),
+        Some(CorruptRecord::InvalidMagic(1))
+    ));
+    let mut corrupt = encoded.clone();
+    corrupt[8..12].copy_from_slice(&20i32.to_be_bytes());
+    assert!(matches!(
+        parse(&corrupt),
+        Some(CorruptRecord::InvalidBatchLength(20))
+    ));
+
+    // A record count beyond the records the batch holds
+    let mut corrupt = encoded.clone();
+    corrupt[57..61].copy_from_slice(&2u32.to_be_bytes());
+    let crc = crc32c::crc32c(&corrupt[21..]);
+    corrupt[17..21].copy_from_slice(&crc.to_be_bytes());
+    assert!(matches!(
+        parse(&corrupt),
+        Some(CorruptRecord::InvalidRecord(1))
+    ));
+
+    // The batch length bounds the batch, whatever follows it
+    let mut input = encoded.clone();
+    input.extend_from_slice(&encoded[..20]);
+    let (parsed, rest) = RecordBatch::parse(&input).unwrap();
+    assert_eq!(parsed.records[0].value.as_deref(), Some(&[1][..]));
+    assert_eq!(rest, &encoded[..20]);
+}
+
+#[test]
+fn test_compressed_roundtrip() {
+    let records = (0..3)
+        .map(|i| Record::new(i, None, Some(vec![b'x'; 1000])))
+        .collect();
+    let mut batch = RecordBatch::new(TRANSACTIONAL_FLAG, 7, 2, 0, records);
+    let uncompressed = batch.encode().len();
+    for compression in [
+        Compression::Gzip,
+        Compression::Snappy,
+        Compression::Lz4,
+        Compression::Zstd,
+    ] {
+        batch.set_compression(compression);
+        let encoded = batch.encode();
+        assert!(encoded.len() < uncompressed);
+        let (parsed, _) = RecordBatch::parse(&encoded).unwrap();
+        assert_eq!(parsed.compression(), Some(compression));
+        assert_eq!(parsed.attributes & TRANSACTIONAL_FLAG, TRANSACTIONAL_FLAG);
+        assert_eq!(parsed.records.len(), 3);
+        assert_eq!(parsed.records[2].value.as_deref(), Some(&[b'x'; 1000][..]));
+    }
+
+    batch.attributes |= COMPRESSION_MASK;
+    let encoded = RecordBatch::new(batch.attributes, 7, 2, 0, vec![]).encode();
+    assert!(matches!(
+        RecordBatch::parse(&encoded),
+        Err(CorruptRecord::InvalidCompression(7))
+    ));
+}
+
+#[test]
+fn test_record_headers_and_deltas() {
+    let mut record = Record::new(300, Some(b"key".to_vec()), Some(b"value".to_vec()));
+    record.timestamp_delta = 100_000;
+    record.headers = vec![
+        ("trace-id".to_string(), Some(Bytes::from_static(b"abc"))),
+        ("empty".to_string(), None),
+    ];
+    let mut batch = RecordBatch::new(0, -1, -1, 1_700_000_000_000, vec![record]);
+    batch.base_offset = 1000;
+    batch.last_offset_delta = 300;
+
+    let encoded = batch.encode();
+    let (parsed, rest) = RecordBatch::parse(&encoded).unwrap();
+    assert!(rest.is_empty());
+    let record = &parsed.records[0];
+    assert_eq!(record.offset(&parsed), 1300);
+    assert_eq!(record.timestamp(&parsed), 1_700_000_100_000);
+    assert_eq!(record.value.as_deref(), Some(&b"value"[..]));
+    assert_eq!(
+        record.headers,
+        vec![
+            ("trace-id".to_string(), Some(Bytes::from_static(b"abc"))),
+            ("empty".to_string(), None),
+        ]
+    );
+}
@@ -0,0 +1,346 @@
+use crate::cluser_metadata::random_uuid;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+
+pub const PLAIN: &str = "PLAIN";
+pub const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+pub const SCRAM_SHA_512: &str = "SCRAM-SHA-512";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScramMechanism {
+    Sha256,
+    Sha512,
+}
+
+impl ScramMechanism {
+    // Mechanism code stored in UserScramCredentialRecords
+    pub fn code(self) -> i8 {
+        match self {
+            ScramMechanism::Sha256 => 1,
+            ScramMechanism::Sha512 => 2,
+        }
+    }
+
+    fn hmac(self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramMechanism::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            ScramMechanism::Sha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+
+    fn hash(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramMechanism::Sha256 => Sha256::digest(data).to_vec(),
+            ScramMechanism::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScramCredential {
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+    pub iterations: i32,
+}
+
+// State kept between the client-first and client-final SCRAM messages
+#[derive(Debug)]
+pub struct ScramExchange {
+    username: String,
+    credential: ScramCredential,
+    gs2_header: String,
+    nonce: String,
+    client_first_bare: String,
+    server_first: String,
+}
+
+#[derive(Debug)]
+pub enum SaslServer {
+    Plain,
+    Scram(ScramMechanism, Option<Box<ScramExchange>>),
+}
+
+#[derive(Debug)]
+pub enum SaslStep {
+    Challenge(Vec<u8>),
+    Complete { username: String, response: Vec<u8> },
+}
+
+// Value of the `key=` attribute in a comma separated SCRAM message
+fn scram_attribute<'a>(attributes: &'a str, key: &str) -> Option<&'a str> {
+    attributes
+        .split(',')
+        .find_map(|attribute| attribute.strip_prefix(key)?.strip_prefix('='))
+}
+
+impl SaslServer {
+    pub fn new(mechanism: &str) -> Option<SaslServer> {
+        match mechanism {
+            PLAIN => Some(SaslServer::Plain),
+            SCRAM_SHA_256 => Some(SaslServer::Scram(ScramMechanism::Sha256, None)),
+            SCRAM_SHA_512 => Some(SaslServer::Scram(ScramMechanism::Sha512, None)),
+            _ => None,
+        }
+    }
+
+    // Handles one client token, failing with the message to send back
+    pub fn evaluate(
+        &mut self,
+        token: &[u8],
+        plain_users: &HashMap<String, String>,
+        scram_credential: impl Fn(&str, ScramMechanism) -> Option<ScramCredential>,
+    ) -> Result<SaslStep, String> {
+        match self {
+            SaslServer::Plain => authenticate_plain(token, plain_users),
+            SaslServer::Scram(mechanism, exchange) => {
+                let message = std::str::from_utf8(token)
+                    .map_err(|_| "Invalid SCRAM message encoding".to_string())?;
+                match exchange.take() {
+                    None => {
+                        let started = start_scram(*mechanism, message, scram_credential)?;
+                        let server_first = started.server_first.clone().into_bytes();
+                        *exchange = Some(Box::new(started));
+                        Ok(SaslStep::Challenge(server_first))
+                    }
+                    Some(started) => finish_scram(*mechanism, &started, message),
+                }
+            }
+        }
+    }
+}
+
+// Compares secrets in a time that doesn't depend on where they differ, as
+// Kafka's MessageDigest.isEqual does. Only the length may leak.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// `[authzid] NUL authcid NUL passwd`
+fn authenticate_plain(
+    token: &[u8],
+    plain_users: &HashMap<String, String>,
+) -> Result<SaslStep, String> {
+    let token = String::from_utf8_lossy(token);
+    let (authorization_id, username, password) = match token.split('\0').collect::<Vec<_>>()[..] {
+        [authorization_id, username, password] => (authorization_id, username, password),
+        _ => return Err("Invalid SASL/PLAIN response: expected 3 tokens".to_string()),
+    };
+    if !authorization_id.is_empty() && authorization_id != username {
+        return Err(
+            "Authentication failed: Client requested an authorization id that is different from username"
+                .to_string(),
+        );
+    }
+    if !plain_users
+        .get(username)
+        .is_some_and(|expected| constant_time_eq(expected.as_bytes(), password.as_bytes()))
+    {
+        return Err("Authentication failed: Invalid username or password".to_string());
+    }
+    Ok(SaslStep::Complete {
+        username: username.to_string(),
+        response: vec![],
+    })
+}
+
+fn start_scram(
+    mechanism: ScramMechanism,
+    client_first: &str,
+    scram_credential: impl Fn(&str, ScramMechanism) -> Option<ScramCredential>,
+) -> Result<ScramExchange, String> {
+    let invalid = || format!("Invalid SCRAM client first message {}", client_first);
+    let mut parts = client_first.splitn(3, ',');
+    let (channel_binding, authorization_id, client_first_bare) =
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(channel_binding), Some(authorization_id), Some(client_first_bare)) => {
+                (channel_binding, authorization_id, client_first_bare)
+            }
+            _ => return Err(invalid()),
+        };
+    if channel_binding != "n" && channel_binding != "y" {
+        return Err("Channel binding is not supported".to_string());
+    }
+    if scram_attribute(client_first_bare, "m").is_some() {
+        return Err("Mandatory SCRAM extensions are not supported".to_string());
+    }
+    let username = scram_attribute(client_first_bare, "n")
+        .ok_or_else(invalid)?
+        .replace("=2C", ",")
+        .replace("=3D", "=");
+    let client_nonce = scram_attribute(client_first_bare, "r").ok_or_else(invalid)?;
+    if let Some(authorization_id) = authorization_id.strip_prefix("a=") {
+        if authorization_id != username {
+            return Err(
+                "Authentication failed: Client requested an authorization id that is different from username"
+                    .to_string(),
+            );
+        }
+    }
+
+    let credential = scram_credential(&username, mechanism).ok_or_else(|| {
+        "Authentication failed during authentication due to invalid credentials with SASL mechanism"
+            .to_string()
+    })?;
+    let server_nonce = random_uuid().map_err(|e| e.to_string())?;
+    let nonce = format!("{}{}", client_nonce, BASE64.encode(server_nonce));
+    let server_first = format!(
+        "r={},s={},i={}",
+        nonce,
+        BASE64.encode(&credential.salt),
+        credential.iterations
+    );
+
+    Ok(ScramExchange {
+        username,
+        credential,
+        gs2_header: format!("{},{},", channel_binding, authorization_id),
+        nonce,
+        client_first_bare: client_first_bare.to_string(),
+        server_first,
+    })
+}
+
+fn finish_scram(
+    mechanism: ScramMechanism,
+    exchange: &ScramExchange,
+    client_final: &str,
+) -> Result<SaslStep, String> {
+    let invalid = || format!("Invalid SCRAM client final message {}", client_final);
+    let (without_proof, proof) = client_final.rsplit_once(",p=").ok_or_else(invalid)?;
+    let proof = BASE64.decode(proof).map_err(|_| invalid())?;
+    if scram_attribute(without_proof, "c") != Some(&BASE64.encode(&exchange.gs2_header)) {
+        return Err("Invalid channel binding".to_string());
+    }
+    if scram_attribute(without_proof, "r") != Some(&exchange.nonce) {
+        return Err("Invalid SCRAM nonce".to_string());
+    }
+
+    let auth_message = format!(
+        "{},{},{}",
+        exchange.client_first_bare, exchange.server_first, without_proof
+    );
+    let client_signature = mechanism.hmac(&exchange.credential.stored_key, auth_message.as_bytes());
+    if proof.len() != client_signature.len() {
+        return Err("Invalid client credentials".to_string());
+    }
+    let client_key: Vec<u8> = proof
+        .iter()
+        .zip(&client_signature)
+        .map(|(proof, signature)| proof ^ signature)
+        .collect();
+    if !constant_time_eq(
+        &mechanism.hash(&client_key),
+        &exchange.credential.stored_key,
+    ) {
+        return Err("Invalid client credentials".to_string());
+    }
+
+    let server_signature = mechanism.hmac(&exchange.credential.server_key, auth_message.as_bytes());
+    Ok(SaslStep::Complete {
+        username: exchange.username.clone(),
+        response: format!("v={}", BASE64.encode(server_signature)).into_bytes(),
+    })
+}
+
+#[test]
+fn test_plain() {
+    let users = HashMap::from([("alice".to_string(), "secret".to_string())]);
+    let mut server = SaslServer::new(PLAIN).unwrap();
+    let no_credential = |_: &str, _| None;
+
+    assert!(matches!(
+        server.evaluate(b"\0alice\0secret", &users, no_credential),
+        Ok(SaslStep::Complete { username, .. }) if username == "alice"
+    ));
+    assert!(server
+        .evaluate(b"\0alice\0wrong", &users, no_credential)
+        .is_err());
+    assert!(server
+        .evaluate(b"bob\0alice\0secret", &users, no_credential)
+        .is_err());
+}
+
+#[test]
+fn test_scram() {
+    let mechanism = ScramMechanism::Sha256;
+    let (salt, iterations) = (b"salt".to_vec(), 4096);
+
+    // Hi() from RFC 5802
+    let mut block = mechanism.hmac(b"secret", &[&salt[..], &[0, 0, 0, 1]].concat());
+    let mut salted_password = block.clone();
+    for _ in 1..iterations {
+        block = mechanism.hmac(b"secret", &block);
+        for (salted, byte) in salted_password.iter_mut().zip(&block) {
+            *salted ^= byte;
+        }
+    }
+    let client_key = mechanism.hmac(&salted_password, b"Client Key");
+    let credential = ScramCredential {
+        salt,
+        stored_key: mechanism.hash(&client_key),
+        server_key: mechanism.hmac(&salted_password, b"Server Key"),
+        iterations,
+    };
+    let lookup = |username: &str, _| (username == "alice").then(|| credential.clone());
+
+    let mut server = SaslServer::new(SCRAM_SHA_256).unwrap();
+    let users = HashMap::new();
+    let client_first_bare = "n=alice,r=clientnonce";
+    let server_first = match server.evaluate(
+        format!("n,,{}", client_first_bare).as_bytes(),
+        &users,
+        lookup,
+    ) {
+        Ok(SaslStep::Challenge(server_first)) => String::from_utf8(server_first).unwrap(),
+        step => panic!("Unexpected step {:?}", step),
+    };
+    assert!(server_first.starts_with("r=clientnonce"));
+    assert!(server_first.ends_with(",s=c2FsdA==,i=4096"));
+
+    let without_proof = format!("c=biws,r={}", scram_attribute(&server_first, "r").unwrap());
+    let auth_message = format!("{},{},{}", client_first_bare, server_first, without_proof);
+    let client_signature = mechanism.hmac(&credential.stored_key, auth_message.as_bytes());
+    let proof: Vec<u8> = client_key
+        .iter()
+        .zip(&client_signature)
+        .map(|(key, signature)| key ^ signature)
+        .collect();
+    let client_final = format!("{},p={}", without_proof, BASE64.encode(proof));
+    let server_signature = mechanism.hmac(&credential.server_key, auth_message.as_bytes());
+    match server.evaluate(client_final.as_bytes(), &users, lookup) {
+        Ok(SaslStep::Complete { username, response }) => {
+            assert_eq!(username, "alice");
+            assert_eq!(
+                response,
+                format!("v={}", BASE64.encode(server_signature)).into_bytes()
+            );
+        }
+        step => panic!("Unexpected step {:?}", step),
+    }
+
+    let mut server = SaslServer::new(SCRAM_SHA_256).unwrap();
+    assert!(server
+        .evaluate(b"n,,n=bob,r=nonce", &users, lookup)
+        .is_err());
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq(b"secret", b"secret"));
+    assert!(constant_time_eq(b"", b""));
+    assert!(!constant_time_eq(b"secret", b"secreT"));
+    assert!(!constant_time_eq(b"secret", b"secret2"));
+}
@@ -0,0 +1,53 @@
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::sasl::SaslStep;
+use crate::session::{Authentication, Session};
+
+use bytes::BufMut;
+
+// Sessions don't expire, so clients never have to re-authenticate
+const SESSION_LIFETIME_MS: i64 = 0;
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &mut Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let auth_bytes = input.get_compact_bytes().unwrap_or_default();
+    input.skip_tagged_fields();
+
+    let result = match &mut session.authentication {
+        Authentication::Authenticate(sasl_server) => sasl_server
+            .evaluate(&auth_bytes, &broker.plain_users, |username, mechanism| {
+                broker.scram_credential(username, mechanism)
+            })
+            .map_err(|e| (error_code::SASL_AUTHENTICATION_FAILED, e)),
+        _ => Err((
+            error_code::ILLEGAL_SASL_STATE,
+            "Unexpected SaslAuthenticate request".to_string(),
+        )),
+    };
+    let (error_code, error_message, auth_bytes) = match result {
+        Ok(SaslStep::Challenge(challenge)) => (error_code::NONE, None, challenge),
+        Ok(SaslStep::Complete { username, response }) => {
+            session.principal = format!("User:{}", username);
+            session.authentication = Authentication::Complete;
+            (error_code::NONE, None, response)
+        }
+        Err((error_code, error_message)) => {
+            // The connection is closed after a failed authentication
+            session.authentication = Authentication::Failed;
+            (error_code, Some(error_message), vec![])
+        }
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i16(error_code);
+    body.put_compact_nullable_string(error_message.as_deref());
+    body.put_compact_bytes(Some(&auth_bytes));
+    body.put_i64(SESSION_LIFETIME_MS);
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
@@ -0,0 +1,40 @@
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response_v0, CompactBuf, CompactBufMut, RequestHeader};
+use crate::sasl::SaslServer;
+use crate::session::{Authentication, Session};
+
+use bytes::BufMut;
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &mut Session) -> Vec<u8> {
+    // Deserialize input, SaslHandshake has no flexible versions
+    let mut input = input;
+    let header = RequestHeader::parse_v1(&mut input);
+    let mechanism = input.get_nullable_string().unwrap_or_default();
+
+    let enabled_mechanisms: Vec<String> = broker
+        .config
+        .sasl_enabled_mechanisms()
+        .into_iter()
+        .filter(|enabled| SaslServer::new(enabled).is_some())
+        .collect();
+    let error_code = match session.authentication {
+        Authentication::Handshake if enabled_mechanisms.contains(&mechanism) => {
+            let sasl_server = SaslServer::new(&mechanism).unwrap();
+            session.authentication = Authentication::Authenticate(sasl_server);
+            error_code::NONE
+        }
+        Authentication::Handshake => error_code::UNSUPPORTED_SASL_MECHANISM,
+        _ => error_code::ILLEGAL_SASL_STATE,
+    };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i16(error_code);
+    body.put_i32(enabled_mechanisms.len() as i32);
+    for enabled in enabled_mechanisms {
+        body.put_string(&enabled);
+    }
+
+    encode_response_v0(header.correlation_id, &body)
+}
@@ -0,0 +1,74 @@
+use crate::sasl::SaslServer;
+
+pub const ANONYMOUS_PRINCIPAL: &str = "User:ANONYMOUS";
+
+#[derive(Debug)]
+pub enum Authentication {
+    // Waiting for a SaslHandshake
+    Handshake,
+    // Waiting for SaslAuthenticate tokens of the negotiated mechanism
+    Authenticate(SaslServer),
+    Complete,
+    Failed,
+}
+
+// Identity of the client on the other end of a connection
+#[derive(Debug)]
+pub struct Session {
+    pub principal: String,
+    pub client_host: String,
+    // Listener the client connected through
+    pub listener_name: String,
+    pub authentication: Authentication,
+    // How long the client is throttled for going over its quotas
+    pub throttle_time_ms: i32,
+}
+
+impl Session {
+    // Connections to SASL listeners have to authenticate first
+    pub fn new(client_host: String, listener_name: String, sasl: bool) -> Session {
+        Session {
+            principal: ANONYMOUS_PRINCIPAL.to_string(),
+            client_host,
+            listener_name,
+            authentication: if sasl {
+                Authentication::Handshake
+            } else {
+                Authentication::Complete
+            },
+            throttle_time_ms: 0,
+        }
+    }
+
+    // Name quotas are configured on, the principal without its `User:` type
+    pub fn user(&self) -> &str {
+        self.principal
+            .split_once(':')
+            .map_or(self.principal.as_str(), |(_, name)| name)
+    }
+
+    // Whether the connection may send a request with this api key yet
+    pub fn allows(&self, api_key: i16) -> bool {
+        match self.authentication {
+            // ApiVersions and SaslHandshake
+            Authentication::Handshake => api_key == 18 || api_key == 17,
+            // SaslAuthenticate
+            Authentication::Authenticate(_) => api_key == 36,
+            Authentication::Complete => true,
+            Authentication::Failed => false,
+        }
+    }
+}
+
+#[test]
+fn test_allows() {
+    let mut session = Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), true);
+    assert!(session.allows(18));
+    assert!(!session.allows(75));
+
+    session.authentication = Authentication::Authenticate(SaslServer::Plain);
+    assert!(session.allows(36));
+    assert!(!session.allows(17));
+
+    assert!(Session::new("127.0.0.1".to_string(), "PLAINTEXT".to_string(), false).allows(75));
+}
@@ -0,0 +1,155 @@
+use regex::Regex;
+
+#[derive(Debug)]
+enum Case {
+    Unchanged,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug)]
+enum Rule {
+    // Uses the distinguished name as is
+    Default,
+    Pattern {
+        regex: Regex,
+        replacement: String,
+        case: Case,
+    },
+}
+
+// Maps certificate distinguished names to principal names with the
+// `RULE:pattern/replacement/[LU]` and `DEFAULT` rules of ssl.principal.mapping.rules
+#[derive(Debug)]
+pub struct SslPrincipalMapper {
+    rules: Vec<Rule>,
+}
+
+// Reads up to the next unescaped '/', returning the part and what follows it
+fn split_part(input: &str) -> Option<(String, &str)> {
+    let mut part = String::new();
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, '/')) => part.push('/'),
+                Some((_, next)) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            '/' => return Some((part, &input[i + 1..])),
+            c => part.push(c),
+        }
+    }
+    None
+}
+
+// Java writes group references as `$1`, which the regex crate would read as `$1...`
+fn replacement_groups(replacement: &str) -> String {
+    let mut converted = String::new();
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '$' && chars.peek().is_some_and(char::is_ascii_digit) {
+            converted.push_str("${");
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                converted.push(digit);
+            }
+            converted.push('}');
+        } else {
+            converted.push(c);
+        }
+    }
+    converted
+}
+
+impl SslPrincipalMapper {
+    pub fn parse(rules: &str) -> Result<SslPrincipalMapper, String> {
+        let mut parsed = vec![];
+        let mut rest = rules;
+        loop {
+            rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(after) = rest.strip_prefix("DEFAULT") {
+                parsed.push(Rule::Default);
+                rest = after;
+                continue;
+            }
+
+            let invalid = || format!("Invalid rule: {}", rest);
+            let rule = rest.strip_prefix("RULE:").ok_or_else(invalid)?;
+            let (pattern, after) = split_part(rule).ok_or_else(invalid)?;
+            let (replacement, after) = split_part(after).ok_or_else(invalid)?;
+            let case = match after.chars().next() {
+                Some('L') => Case::Lower,
+                Some('U') => Case::Upper,
+                _ => Case::Unchanged,
+            };
+            let regex = Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| format!("Invalid rule pattern {}: {}", pattern, e))?;
+            parsed.push(Rule::Pattern {
+                regex,
+                replacement: replacement_groups(&replacement),
+                case,
+            });
+            rest = after.find([',', '\n']).map_or("", |end| &after[end + 1..]);
+        }
+
+        if parsed.is_empty() {
+            parsed.push(Rule::Default);
+        }
+        Ok(SslPrincipalMapper { rules: parsed })
+    }
+
+    // Name given by the first rule matching the whole distinguished name
+    pub fn principal_name(&self, distinguished_name: &str) -> Result<String, String> {
+        for rule in &self.rules {
+            match rule {
+                Rule::Default => return Ok(distinguished_name.to_string()),
+                Rule::Pattern {
+                    regex,
+                    replacement,
+                    case,
+                } if regex.is_match(distinguished_name) => {
+                    let name = regex.replace_all(distinguished_name, replacement.as_str());
+                    return Ok(match case {
+                        Case::Unchanged => name.to_string(),
+                        Case::Lower => name.to_lowercase(),
+                        Case::Upper => name.to_uppercase(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Err(format!("No rules apply to {}", distinguished_name))
+    }
+}
+
+#[test]
+fn test_principal_name() {
+    let mapper = SslPrincipalMapper::parse(
+        "RULE:^CN=(.*?),OU=ServiceUsers.*$/$1/, \
+         RULE:^CN=(.*?),OU=(.*?),O=(.*?),L=(.*?),ST=(.*?),C=(.*?)$/$1@$2/L,\n\
+         RULE:^.*[Cc][Nn]=([a-zA-Z0-9.]*).*$/$1/U, DEFAULT",
+    )
+    .unwrap();
+    let principal_name = |name| mapper.principal_name(name).unwrap();
+
+    assert_eq!(principal_name("CN=Duke,OU=ServiceUsers,O=Org,C=US"), "Duke");
+    assert_eq!(
+        principal_name("CN=Duke,OU=SME,O=mycp,L=Fulton,ST=MD,C=US"),
+        "duke@sme"
+    );
+    assert_eq!(principal_name("cn=duke,ou=sme,dc=mycp,dc=com"), "DUKE");
+    assert_eq!(
+        principal_name("OU=JavaSoft,O=Sun Microsystems,C=US"),
+        "OU=JavaSoft,O=Sun Microsystems,C=US"
+    );
+
+    let mapper = SslPrincipalMapper::parse("RULE:^CN=([^,]*).*$/$1/").unwrap();
+    assert!(mapper.principal_name("OU=nobody").is_err());
+    assert!(SslPrincipalMapper::parse("RULE:^CN=(.*)$").is_err());
+}
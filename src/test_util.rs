@@ -0,0 +1,150 @@
+use crate::broker::Broker;
+use crate::config::BrokerConfig;
+use crate::protocol::CompactBufMut;
+use crate::record_batch::{Record, RecordBatch, TRANSACTIONAL_FLAG};
+
+use bytes::BufMut;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TEMP_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+// Directory of a single test, removed with its contents when dropped, even
+// when the test panics
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        // Tests run concurrently in one process, so the pid alone isn't unique
+        let path = std::env::temp_dir().join(format!(
+            "{}-test-{}-{}",
+            name,
+            std::process::id(),
+            TEMP_DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Batch of a single record, as a non-idempotent producer sends it
+pub fn data_batch() -> RecordBatch {
+    RecordBatch::new(
+        0,
+        -1,
+        -1,
+        0,
+        vec![Record::new(0, None, Some(b"data".to_vec()))],
+    )
+}
+
+pub fn transactional_batch(producer_id: i64) -> RecordBatch {
+    RecordBatch::new(
+        TRANSACTIONAL_FLAG,
+        producer_id,
+        0,
+        0,
+        vec![Record::new(0, None, Some(b"data".to_vec()))],
+    )
+}
+
+// Config of a broker keeping its logs in `log_dir`, followed by `extra`
+// properties lines
+pub fn broker_config(log_dir: &TempDir, extra: &str) -> BrokerConfig {
+    BrokerConfig::parse(&format!("log.dirs={}\n{}", log_dir.path().display(), extra))
+}
+
+// Broker on a fresh log dir, whose metadata log holds each (name, id,
+// partition count) topic with node 1 leading every partition. The dir goes
+// once both are dropped.
+pub fn broker_with_topics(
+    name: &str,
+    extra: &str,
+    topics: &[(&str, [u8; 16], i32)],
+) -> (TempDir, Broker) {
+    let log_dir = TempDir::new(name);
+    let broker = Broker::new(broker_config(&log_dir, extra)).unwrap();
+    let mut records = vec![];
+    for (topic_name, topic_id, partitions) in topics {
+        records.push(topic_record(topic_name, *topic_id));
+        for partition_id in 0..*partitions {
+            records.push(partition_record(*topic_id, partition_id, 1, &[1]));
+        }
+    }
+    broker.append_metadata(records).unwrap();
+    (log_dir, broker)
+}
+
+// TopicRecord v0
+pub fn topic_record(name: &str, topic_id: [u8; 16]) -> Vec<u8> {
+    let mut record = vec![1, 2, 0];
+    record.put_compact_string(name);
+    record.extend_from_slice(&topic_id);
+    record.put_empty_tagged_fields();
+    record
+}
+
+// PartitionRecord v0 with every replica in the ISR
+pub fn partition_record(
+    topic_id: [u8; 16],
+    partition_id: i32,
+    leader_id: i32,
+    replicas: &[i32],
+) -> Vec<u8> {
+    let mut record = vec![1, 3, 0];
+    record.put_i32(partition_id);
+    record.extend_from_slice(&topic_id);
+    for replica_list in [replicas, replicas, &[], &[]] {
+        record.put_compact_array_length(replica_list.len());
+        for replica in replica_list {
+            record.put_i32(*replica);
+        }
+    }
+    record.put_i32(leader_id);
+    record.put_i32(0); // Leader epoch
+    record.put_i32(0); // Partition epoch
+    record.put_empty_tagged_fields();
+    record
+}
+
+// RegisterBrokerRecord v0 of an unfenced broker with a single endpoint on
+// `listener_name`, at port 9090 + its id
+pub fn register_broker_record(broker_id: i32, listener_name: &str) -> Vec<u8> {
+    let mut record = vec![1, 0, 0];
+    record.put_i32(broker_id);
+    record.extend_from_slice(&[broker_id as u8; 16]); // Incarnation id
+    record.put_i64(5); // Broker epoch
+    record.put_compact_array_length(1);
+    record.put_compact_string(listener_name);
+    record.put_compact_string("localhost");
+    record.put_u16(9090 + broker_id as u16);
+    record.put_i16(0); // Security protocol
+    record.put_empty_tagged_fields();
+    record.put_compact_array_length(0); // Features
+    record.put_compact_nullable_string(None); // Rack
+    record.put_u8(0); // Fenced
+    record.put_empty_tagged_fields();
+    record
+}
+
+// FenceBrokerRecord v0 of a broker registered by `register_broker_record`
+pub fn fence_broker_record(broker_id: i32) -> Vec<u8> {
+    let mut record = vec![1, 7, 0];
+    record.put_i32(broker_id);
+    record.put_i64(5); // Broker epoch
+    record.put_empty_tagged_fields();
+    record
+}
@@ -0,0 +1,300 @@
+use crate::config::BrokerConfig;
+use crate::session::ANONYMOUS_PRINCIPAL;
+use crate::ssl_principal_mapper::SslPrincipalMapper;
+
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use std::io::{self, ErrorKind};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+// Attribute names Java writes in RFC 2253 names, anything else uses its OID
+const RFC2253_KEYWORDS: &[(&str, &str)] = &[
+    ("2.5.4.3", "CN"),
+    ("2.5.4.6", "C"),
+    ("2.5.4.7", "L"),
+    ("2.5.4.8", "ST"),
+    ("2.5.4.9", "STREET"),
+    ("2.5.4.10", "O"),
+    ("2.5.4.11", "OU"),
+    ("0.9.2342.19200300.100.1.1", "UID"),
+    ("0.9.2342.19200300.100.1.25", "DC"),
+];
+
+// Longest a client may take over the handshake, so one that connects and
+// stalls doesn't hold its connection thread forever
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn invalid_config(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, message)
+}
+
+fn read_certificates(path: &str) -> io::Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect())
+        .map_err(|e| invalid_config(format!("Invalid certificates in {}: {}", path, e)))
+}
+
+fn escape_rfc2253(value: &str) -> String {
+    let mut escaped = String::new();
+    for (i, c) in value.chars().enumerate() {
+        let leading = i == 0 && (c == '#' || c == ' ');
+        let trailing = i == value.chars().count() - 1 && c == ' ';
+        if leading || trailing || matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Certificate subject in RFC 2253 form, most specific attribute first
+fn subject_name(certificate: &CertificateDer) -> Result<String, String> {
+    let (_, certificate) = X509Certificate::from_der(certificate)
+        .map_err(|e| format!("Invalid client certificate: {}", e))?;
+    let mut rdns: Vec<String> = certificate
+        .subject()
+        .iter_rdn()
+        .map(|rdn| {
+            rdn.iter()
+                .map(|attribute| {
+                    let oid = attribute.attr_type().to_id_string();
+                    let keyword = RFC2253_KEYWORDS
+                        .iter()
+                        .find(|(keyword_oid, _)| *keyword_oid == oid)
+                        .map_or(oid.as_str(), |(_, keyword)| keyword);
+                    let value = attribute.as_str().unwrap_or_default();
+                    format!("{}={}", keyword, escape_rfc2253(value))
+                })
+                .collect::<Vec<_>>()
+                .join("+")
+        })
+        .collect();
+    rdns.reverse();
+    Ok(rdns.join(","))
+}
+
+// Accepts TLS connections on an SSL or SASL_SSL listener
+#[derive(Debug)]
+pub struct TlsAcceptor {
+    config: Arc<ServerConfig>,
+    principal_mapper: SslPrincipalMapper,
+}
+
+impl TlsAcceptor {
+    // PEM keystores hold the certificate chain and the private key in one file,
+    // PEM truststores the CA certificates client certificates are checked against
+    pub fn new(config: &BrokerConfig, listener_name: &str) -> io::Result<TlsAcceptor> {
+        let get = |key| config.listener_get(listener_name, key);
+        for store_type in ["ssl.keystore.type", "ssl.truststore.type"] {
+            if get(store_type).is_some_and(|store_type| store_type != "PEM") {
+                return Err(invalid_config(format!(
+                    "Only PEM is supported for {}",
+                    store_type
+                )));
+            }
+        }
+        let keystore = get("ssl.keystore.location")
+            .ok_or_else(|| invalid_config("ssl.keystore.location is required".to_string()))?;
+        let certificates = read_certificates(keystore)?;
+        let private_key = PrivateKeyDer::from_pem_file(keystore)
+            .map_err(|e| invalid_config(format!("Invalid private key in {}: {}", keystore, e)))?;
+
+        let provider = Arc::new(ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| invalid_config(e.to_string()))?;
+        let builder = match get("ssl.client.auth").unwrap_or("none") {
+            "none" => builder.with_no_client_auth(),
+            client_auth @ ("required" | "requested") => {
+                let truststore = get("ssl.truststore.location").ok_or_else(|| {
+                    invalid_config("ssl.truststore.location is required".to_string())
+                })?;
+                let mut roots = RootCertStore::empty();
+                for certificate in read_certificates(truststore)? {
+                    roots
+                        .add(certificate)
+                        .map_err(|e| invalid_config(e.to_string()))?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = if client_auth == "requested" {
+                    verifier.allow_unauthenticated()
+                } else {
+                    verifier
+                };
+                builder.with_client_cert_verifier(
+                    verifier
+                        .build()
+                        .map_err(|e| invalid_config(e.to_string()))?,
+                )
+            }
+            client_auth => {
+                return Err(invalid_config(format!(
+                    "Invalid ssl.client.auth {}",
+                    client_auth
+                )))
+            }
+        };
+        let server_config = builder
+            .with_single_cert(certificates, private_key)
+            .map_err(|e| invalid_config(e.to_string()))?;
+
+        let principal_mapper =
+            SslPrincipalMapper::parse(get("ssl.principal.mapping.rules").unwrap_or("DEFAULT"))
+                .map_err(invalid_config)?;
+        Ok(TlsAcceptor {
+            config: Arc::new(server_config),
+            principal_mapper,
+        })
+    }
+
+    // Completes the handshake, returning the encrypted stream and the principal
+    // of the client's certificate, or ANONYMOUS when it sent none
+    pub fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> io::Result<(StreamOwned<ServerConnection, TcpStream>, String)> {
+        self.accept_within(stream, HANDSHAKE_TIMEOUT)
+    }
+
+    // Fails once the client sends nothing for `timeout` during the handshake.
+    // The timeout is cleared afterwards, as requests may be far apart.
+    fn accept_within(
+        &self,
+        mut stream: TcpStream,
+        timeout: Duration,
+    ) -> io::Result<(StreamOwned<ServerConnection, TcpStream>, String)> {
+        let mut connection =
+            ServerConnection::new(Arc::clone(&self.config)).map_err(io::Error::other)?;
+        stream.set_read_timeout(Some(timeout))?;
+        while connection.is_handshaking() {
+            connection.complete_io(&mut stream)?;
+        }
+        stream.set_read_timeout(None)?;
+
+        let principal = match connection
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+        {
+            Some(certificate) => {
+                let principal_name = subject_name(certificate)
+                    .and_then(|name| self.principal_mapper.principal_name(&name))
+                    .map_err(|e| io::Error::new(ErrorKind::PermissionDenied, e))?;
+                format!("User:{}", principal_name)
+            }
+            None => ANONYMOUS_PRINCIPAL.to_string(),
+        };
+        Ok((StreamOwned::new(connection, stream), principal))
+    }
+}
+
+#[test]
+fn test_accept() {
+    use rcgen::{
+        BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose,
+        IsCa, KeyPair,
+    };
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConfig, ClientConnection};
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+
+    let server_key = KeyPair::generate().unwrap();
+    let mut server_params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+    server_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    let server_cert = server_params
+        .signed_by(&server_key, &ca_cert, &ca_key)
+        .unwrap();
+
+    let client_key = KeyPair::generate().unwrap();
+    let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    client_params.distinguished_name = DistinguishedName::new();
+    client_params
+        .distinguished_name
+        .push(DnType::OrganizationName, "acme");
+    client_params
+        .distinguished_name
+        .push(DnType::OrganizationalUnitName, "eng");
+    client_params
+        .distinguished_name
+        .push(DnType::CommonName, "Alice");
+    client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    let client_cert = client_params
+        .signed_by(&client_key, &ca_cert, &ca_key)
+        .unwrap();
+    assert_eq!(
+        subject_name(client_cert.der()).unwrap(),
+        "CN=Alice,OU=eng,O=acme"
+    );
+
+    let dir = crate::test_util::TempDir::new("tls");
+    let keystore = dir.path().join("server.pem");
+    let truststore = dir.path().join("ca.pem");
+    fs::write(&keystore, server_cert.pem() + &server_key.serialize_pem()).unwrap();
+    fs::write(&truststore, ca_cert.pem()).unwrap();
+    let config = BrokerConfig::parse(&format!(
+        "listener.name.ssl.ssl.keystore.location={}\n\
+         ssl.truststore.location={}\n\
+         ssl.client.auth=required\n\
+         ssl.principal.mapping.rules=RULE:^CN=([^,]*),.*$/$1/L\n",
+        keystore.display(),
+        truststore.display()
+    ));
+    let acceptor = TlsAcceptor::new(&config, "SSL").unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let mut roots = RootCertStore::empty();
+        roots.add(ca_cert.der().clone()).unwrap();
+        let client_config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(
+                vec![client_cert.der().clone()],
+                PrivateKeyDer::try_from(client_key.serialize_der()).unwrap(),
+            )
+            .unwrap();
+        let connection = ClientConnection::new(
+            Arc::new(client_config),
+            ServerName::try_from("localhost").unwrap(),
+        )
+        .unwrap();
+        let mut stream = StreamOwned::new(connection, TcpStream::connect(address).unwrap());
+        stream.write_all(b"ping").unwrap();
+        stream.flush().unwrap();
+    });
+
+    let (mut stream, principal) = acceptor.accept(listener.accept().unwrap().0).unwrap();
+    let mut message = [0; 4];
+    stream.read_exact(&mut message).unwrap();
+    client.join().unwrap();
+
+    assert_eq!(principal, "User:alice");
+    assert_eq!(&message, b"ping");
+    assert_eq!(stream.sock.read_timeout().unwrap(), None);
+
+    // A client that connects and never starts the handshake
+    let _stalled = TcpStream::connect(address).unwrap();
+    let error = acceptor
+        .accept_within(listener.accept().unwrap().0, Duration::from_millis(50))
+        .unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::WouldBlock | ErrorKind::TimedOut
+    ));
+}
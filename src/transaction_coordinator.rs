@@ -0,0 +1,768 @@
+use crate::compression::Compression;
+use crate::error_code;
+use crate::partition_log::PartitionLog;
+use crate::protocol::{CheckedBuf, CompactBufMut};
+use crate::record_batch::{
+    now_ms, Record, RecordBatch, ABORT_MARKER, COMMIT_MARKER, TRANSACTIONAL_FLAG,
+};
+
+use bytes::BufMut;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const TRANSACTION_STATE_TOPIC: &str = "__transaction_state";
+pub const CONSUMER_OFFSETS_TOPIC: &str = "__consumer_offsets";
+const TRANSACTION_STATE_PARTITIONS: i32 = 50;
+const CONSUMER_OFFSETS_PARTITIONS: i32 = 50;
+const MAX_TRANSACTION_TIMEOUT_MS: i32 = 900_000;
+// Producer ids are reserved in the metadata log this many at a time
+pub const PRODUCER_ID_BLOCK_SIZE: i64 = 1000;
+// Single coordinator, so its epoch never changes
+const COORDINATOR_EPOCH: i32 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionState {
+    Empty,
+    Ongoing,
+    PrepareCommit,
+    PrepareAbort,
+    CompleteCommit,
+    CompleteAbort,
+}
+
+impl TransactionState {
+    fn code(self) -> i8 {
+        match self {
+            TransactionState::Empty => 0,
+            TransactionState::Ongoing => 1,
+            TransactionState::PrepareCommit => 2,
+            TransactionState::PrepareAbort => 3,
+            TransactionState::CompleteCommit => 4,
+            TransactionState::CompleteAbort => 5,
+        }
+    }
+
+    fn from_code(code: i8) -> TransactionState {
+        match code {
+            1 => TransactionState::Ongoing,
+            2 => TransactionState::PrepareCommit,
+            3 => TransactionState::PrepareAbort,
+            4 => TransactionState::CompleteCommit,
+            5 => TransactionState::CompleteAbort,
+            _ => TransactionState::Empty,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionMetadata {
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+    pub timeout_ms: i32,
+    pub state: TransactionState,
+    pub partitions: BTreeSet<(String, i32)>,
+    start_timestamp: i64,
+}
+
+impl TransactionMetadata {
+    // TransactionLogValue v0
+    fn encode(&self) -> Vec<u8> {
+        let mut topics: BTreeMap<&str, Vec<i32>> = BTreeMap::new();
+        for (topic, partition) in &self.partitions {
+            topics.entry(topic).or_default().push(*partition);
+        }
+
+        let mut value = vec![];
+        value.put_i16(0); // Version
+        value.put_i64(self.producer_id);
+        value.put_i16(self.producer_epoch);
+        value.put_i32(self.timeout_ms);
+        value.put_i8(self.state.code());
+        value.put_i32(topics.len() as i32);
+        for (topic, partitions) in topics {
+            value.put_string(topic);
+            value.put_i32(partitions.len() as i32);
+            for partition in partitions {
+                value.put_i32(partition);
+            }
+        }
+        value.put_i64(now_ms());
+        value.put_i64(self.start_timestamp);
+        value
+    }
+
+    // None when the value is cut short
+    fn parse(mut cursor: &[u8]) -> Option<TransactionMetadata> {
+        let _version = cursor.checked_get_i16()?;
+        let producer_id = cursor.checked_get_i64()?;
+        let producer_epoch = cursor.checked_get_i16()?;
+        let timeout_ms = cursor.checked_get_i32()?;
+        let state = TransactionState::from_code(cursor.checked_get_i8()?);
+        let mut partitions = BTreeSet::new();
+        for _ in 0..cursor.checked_get_i32()?.max(0) {
+            let topic = cursor.checked_get_nullable_string()?.unwrap_or_default();
+            for _ in 0..cursor.checked_get_i32()? {
+                partitions.insert((topic.clone(), cursor.checked_get_i32()?));
+            }
+        }
+        let _last_update_timestamp = cursor.checked_get_i64()?;
+        let start_timestamp = cursor.checked_get_i64()?;
+
+        Some(TransactionMetadata {
+            producer_id,
+            producer_epoch,
+            timeout_ms,
+            state,
+            partitions,
+            start_timestamp,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct TxnOffset {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    pub leader_epoch: i32,
+    pub metadata: Option<String>,
+}
+
+// Persists a block of producer ids, given the first id after it
+pub type ReserveBlock<'a> = dyn FnMut(i64) -> io::Result<()> + 'a;
+
+#[derive(Debug)]
+pub struct TransactionCoordinator {
+    log_dir: PathBuf,
+    next_producer_id: i64,
+    // End of the block of producer ids reserved in the metadata log
+    producer_id_block_end: i64,
+    transactions: HashMap<String, TransactionMetadata>,
+}
+
+// Same as Kafka's `Utils.abs(key.hashCode()) % partitions`
+pub fn partition_for(key: &str, partitions: i32) -> i32 {
+    let hash = key.encode_utf16().fold(0i32, |hash, unit| {
+        hash.wrapping_mul(31).wrapping_add(unit as i32)
+    });
+    let hash = if hash == i32::MIN { 0 } else { hash.abs() };
+    hash % partitions
+}
+
+pub fn write_marker(
+    log_dir: &Path,
+    topic: &str,
+    partition: i32,
+    producer_id: i64,
+    producer_epoch: i16,
+    committed: bool,
+    coordinator_epoch: i32,
+) -> i16 {
+    let log = match PartitionLog::open(log_dir, topic, partition) {
+        Some(log) => log,
+        None => return error_code::UNKNOWN_TOPIC_OR_PARTITION,
+    };
+    match log.coordinator_epoch(producer_id) {
+        Ok(Some(last)) if last > coordinator_epoch => {
+            return error_code::TRANSACTION_COORDINATOR_FENCED
+        }
+        Ok(_) => {}
+        Err(_) => return error_code::KAFKA_STORAGE_ERROR,
+    }
+    let marker = if committed {
+        COMMIT_MARKER
+    } else {
+        ABORT_MARKER
+    };
+    let record_batch = RecordBatch::control(producer_id, producer_epoch, marker, coordinator_epoch);
+    match log.append(record_batch) {
+        Ok(_) => error_code::NONE,
+        Err(_) => error_code::KAFKA_STORAGE_ERROR,
+    }
+}
+
+impl TransactionCoordinator {
+    // Rebuilds the coordinator state by replaying the transaction state log.
+    // Ids of the last block reserved before `next_producer_id` may have been
+    // handed out, so the first allocation reserves a new block.
+    pub fn load(log_dir: &Path, next_producer_id: i64) -> io::Result<TransactionCoordinator> {
+        let mut coordinator = TransactionCoordinator {
+            log_dir: log_dir.to_path_buf(),
+            next_producer_id,
+            producer_id_block_end: next_producer_id,
+            transactions: HashMap::new(),
+        };
+
+        for partition in 0..TRANSACTION_STATE_PARTITIONS {
+            let log = match PartitionLog::open(log_dir, TRANSACTION_STATE_TOPIC, partition) {
+                Some(log) => log,
+                None => continue,
+            };
+            for record_batch in log.read_batches()? {
+                for record in &record_batch.records {
+                    let mut key: &[u8] = match &record.key {
+                        Some(key) => key,
+                        None => continue,
+                    };
+                    let Some(Some(transactional_id)) = key
+                        .checked_get_i16()
+                        .and_then(|_version| key.checked_get_nullable_string())
+                    else {
+                        println!(
+                            "Skipping malformed key at offset {} of {}-{}",
+                            record.offset(&record_batch),
+                            TRANSACTION_STATE_TOPIC,
+                            partition
+                        );
+                        continue;
+                    };
+                    match &record.value {
+                        Some(value) => {
+                            let Some(metadata) = TransactionMetadata::parse(value) else {
+                                println!(
+                                    "Skipping malformed state of transaction {}",
+                                    transactional_id
+                                );
+                                continue;
+                            };
+                            coordinator.next_producer_id =
+                                coordinator.next_producer_id.max(metadata.producer_id + 1);
+                            coordinator.transactions.insert(transactional_id, metadata);
+                        }
+                        None => {
+                            coordinator.transactions.remove(&transactional_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Finish transactions whose markers were not all written before shutdown
+        let pending: Vec<(String, TransactionMetadata)> = coordinator
+            .transactions
+            .iter()
+            .filter(|(_, metadata)| {
+                matches!(
+                    metadata.state,
+                    TransactionState::PrepareCommit | TransactionState::PrepareAbort
+                )
+            })
+            .map(|(transactional_id, metadata)| (transactional_id.clone(), metadata.clone()))
+            .collect();
+        for (transactional_id, mut metadata) in pending {
+            let _ = coordinator.write_markers(&transactional_id, &mut metadata);
+        }
+
+        Ok(coordinator)
+    }
+
+    // Takes the next producer id, first reserving a new block through
+    // `reserve_block` with the end of the block when the current one is used up
+    fn allocate_producer_id(&mut self, reserve_block: &mut ReserveBlock) -> Result<i64, i16> {
+        if self.next_producer_id >= self.producer_id_block_end {
+            let block_end = self.next_producer_id + PRODUCER_ID_BLOCK_SIZE;
+            reserve_block(block_end).map_err(|_| error_code::COORDINATOR_NOT_AVAILABLE)?;
+            self.producer_id_block_end = block_end;
+        }
+        let producer_id = self.next_producer_id;
+        self.next_producer_id += 1;
+        Ok(producer_id)
+    }
+
+    // Persists the new state to the transaction log before making it visible
+    fn update(
+        &mut self,
+        transactional_id: &str,
+        metadata: &TransactionMetadata,
+    ) -> Result<(), i16> {
+        let mut key = vec![];
+        key.put_i16(0); // Version
+        key.put_string(transactional_id);
+        let record = Record::new(0, Some(key), Some(metadata.encode()));
+        let record_batch = RecordBatch::new(0, -1, -1, now_ms(), vec![record]);
+
+        let partition = partition_for(transactional_id, TRANSACTION_STATE_PARTITIONS);
+        PartitionLog::create(&self.log_dir, TRANSACTION_STATE_TOPIC, partition)
+            .and_then(|log| log.append(record_batch))
+            .map_err(|_| error_code::COORDINATOR_NOT_AVAILABLE)?;
+
+        self.transactions
+            .insert(transactional_id.to_string(), metadata.clone());
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+    ) -> Result<TransactionMetadata, i16> {
+        let metadata = match self.transactions.get(transactional_id) {
+            Some(metadata) => metadata,
+            None => return Err(error_code::INVALID_PRODUCER_ID_MAPPING),
+        };
+        if metadata.producer_id != producer_id {
+            return Err(error_code::INVALID_PRODUCER_ID_MAPPING);
+        }
+        if metadata.producer_epoch != producer_epoch {
+            return Err(error_code::PRODUCER_FENCED);
+        }
+        Ok(metadata.clone())
+    }
+
+    pub fn init_producer_id(
+        &mut self,
+        transactional_id: Option<&str>,
+        timeout_ms: i32,
+        producer_id: i64,
+        producer_epoch: i16,
+        reserve_block: &mut ReserveBlock,
+    ) -> Result<(i64, i16), i16> {
+        // Idempotent producers only need a fresh id
+        let transactional_id = match transactional_id {
+            Some(transactional_id) => transactional_id,
+            None => return Ok((self.allocate_producer_id(reserve_block)?, 0)),
+        };
+        if timeout_ms <= 0 || timeout_ms > MAX_TRANSACTION_TIMEOUT_MS {
+            return Err(error_code::INVALID_TRANSACTION_TIMEOUT);
+        }
+
+        let mut metadata = match self.transactions.get(transactional_id).cloned() {
+            None => TransactionMetadata {
+                producer_id: self.allocate_producer_id(reserve_block)?,
+                producer_epoch: 0,
+                timeout_ms,
+                state: TransactionState::Empty,
+                partitions: BTreeSet::new(),
+                start_timestamp: -1,
+            },
+            Some(mut metadata) => {
+                if producer_id != -1
+                    && (producer_id != metadata.producer_id
+                        || producer_epoch != metadata.producer_epoch)
+                {
+                    return Err(error_code::PRODUCER_FENCED);
+                }
+                if matches!(
+                    metadata.state,
+                    TransactionState::PrepareCommit | TransactionState::PrepareAbort
+                ) {
+                    return Err(error_code::CONCURRENT_TRANSACTIONS);
+                }
+
+                // Bumping the epoch fences off any older producer instance
+                if metadata.producer_epoch >= i16::MAX - 1 {
+                    metadata.producer_id = self.allocate_producer_id(reserve_block)?;
+                    metadata.producer_epoch = 0;
+                } else {
+                    metadata.producer_epoch += 1;
+                }
+                if metadata.state == TransactionState::Ongoing {
+                    self.end_ongoing(transactional_id, &mut metadata, false)?;
+                }
+                metadata
+            }
+        };
+
+        metadata.timeout_ms = timeout_ms;
+        metadata.state = TransactionState::Empty;
+        metadata.partitions.clear();
+        self.update(transactional_id, &metadata)?;
+        Ok((metadata.producer_id, metadata.producer_epoch))
+    }
+
+    pub fn add_partitions(
+        &mut self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+        partitions: &[(String, i32)],
+    ) -> Result<(), i16> {
+        let mut metadata = self.validate(transactional_id, producer_id, producer_epoch)?;
+        match metadata.state {
+            TransactionState::PrepareCommit | TransactionState::PrepareAbort => {
+                return Err(error_code::CONCURRENT_TRANSACTIONS)
+            }
+            TransactionState::Ongoing => {
+                if partitions
+                    .iter()
+                    .all(|partition| metadata.partitions.contains(partition))
+                {
+                    return Ok(());
+                }
+            }
+            _ => metadata.start_timestamp = now_ms(),
+        }
+
+        metadata.partitions.extend(partitions.iter().cloned());
+        metadata.state = TransactionState::Ongoing;
+        self.update(transactional_id, &metadata)
+    }
+
+    pub fn add_offsets(
+        &mut self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+        group_id: &str,
+    ) -> Result<(), i16> {
+        let partition = partition_for(group_id, CONSUMER_OFFSETS_PARTITIONS);
+        self.add_partitions(
+            transactional_id,
+            producer_id,
+            producer_epoch,
+            &[(CONSUMER_OFFSETS_TOPIC.to_string(), partition)],
+        )
+    }
+
+    // Offsets are written as transactional records, so they only become
+    // visible once the commit marker lands in the offsets partition. The
+    // batch is written with the offsets topic's `compression`.
+    pub fn commit_offsets(
+        &mut self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+        group_id: &str,
+        offsets: &[TxnOffset],
+        compression: Compression,
+    ) -> Result<(), i16> {
+        let metadata = self.validate(transactional_id, producer_id, producer_epoch)?;
+        let partition = partition_for(group_id, CONSUMER_OFFSETS_PARTITIONS);
+        if metadata.state != TransactionState::Ongoing
+            || !metadata
+                .partitions
+                .contains(&(CONSUMER_OFFSETS_TOPIC.to_string(), partition))
+        {
+            return Err(error_code::INVALID_TXN_STATE);
+        }
+
+        let timestamp = now_ms();
+        let records = offsets
+            .iter()
+            .enumerate()
+            .map(|(offset_delta, offset)| {
+                // OffsetCommitKey v1
+                let mut key = vec![];
+                key.put_i16(1);
+                key.put_string(group_id);
+                key.put_string(&offset.topic);
+                key.put_i32(offset.partition);
+                // OffsetCommitValue v3
+                let mut value = vec![];
+                value.put_i16(3);
+                value.put_i64(offset.offset);
+                value.put_i32(offset.leader_epoch);
+                value.put_string(offset.metadata.as_deref().unwrap_or(""));
+                value.put_i64(timestamp);
+                Record::new(offset_delta as i32, Some(key), Some(value))
+            })
+            .collect();
+        let mut record_batch = RecordBatch::new(
+            TRANSACTIONAL_FLAG,
+            producer_id,
+            producer_epoch,
+            timestamp,
+            records,
+        );
+        record_batch.set_compression(compression);
+
+        PartitionLog::create(&self.log_dir, CONSUMER_OFFSETS_TOPIC, partition)
+            .and_then(|log| log.append(record_batch))
+            .map(|_| ())
+            .map_err(|_| error_code::KAFKA_STORAGE_ERROR)
+    }
+
+    pub fn end_transaction(
+        &mut self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+        committed: bool,
+    ) -> Result<(), i16> {
+        let mut metadata = self.validate(transactional_id, producer_id, producer_epoch)?;
+        match (metadata.state, committed) {
+            (TransactionState::Ongoing, _) => {
+                self.end_ongoing(transactional_id, &mut metadata, committed)
+            }
+            // Markers failed part way through, retry them
+            (TransactionState::PrepareCommit, true) | (TransactionState::PrepareAbort, false) => {
+                self.write_markers(transactional_id, &mut metadata)
+            }
+            // Retried request for a transaction that already finished
+            (TransactionState::CompleteCommit, true) | (TransactionState::CompleteAbort, false) => {
+                Ok(())
+            }
+            _ => Err(error_code::INVALID_TXN_STATE),
+        }
+    }
+
+    // Aborts the transactions still ongoing past their timeout, so a hung
+    // producer can't hold back the last stable offset of its partitions. The
+    // epoch is bumped first, fencing the producer should it come back.
+    // Returns the transactional ids aborted.
+    pub fn abort_timed_out(&mut self, now: i64) -> Vec<String> {
+        let timed_out: Vec<(String, TransactionMetadata)> = self
+            .transactions
+            .iter()
+            .filter(|(_, metadata)| {
+                metadata.state == TransactionState::Ongoing
+                    && metadata.start_timestamp + metadata.timeout_ms as i64 <= now
+            })
+            .map(|(transactional_id, metadata)| (transactional_id.clone(), metadata.clone()))
+            .collect();
+
+        let mut aborted = vec![];
+        for (transactional_id, mut metadata) in timed_out {
+            // An exhausted epoch is left for the next InitProducerId to replace
+            if metadata.producer_epoch < i16::MAX - 1 {
+                metadata.producer_epoch += 1;
+            }
+            match self.end_ongoing(&transactional_id, &mut metadata, false) {
+                Ok(()) => aborted.push(transactional_id),
+                Err(error_code) => println!(
+                    "Error {} aborting timed out transaction {}",
+                    error_code, transactional_id
+                ),
+            }
+        }
+        aborted
+    }
+
+    fn end_ongoing(
+        &mut self,
+        transactional_id: &str,
+        metadata: &mut TransactionMetadata,
+        committed: bool,
+    ) -> Result<(), i16> {
+        metadata.state = if committed {
+            TransactionState::PrepareCommit
+        } else {
+            TransactionState::PrepareAbort
+        };
+        self.update(transactional_id, metadata)?;
+        self.write_markers(transactional_id, metadata)
+    }
+
+    fn write_markers(
+        &mut self,
+        transactional_id: &str,
+        metadata: &mut TransactionMetadata,
+    ) -> Result<(), i16> {
+        let committed = metadata.state == TransactionState::PrepareCommit;
+        for (topic, partition) in &metadata.partitions {
+            let error_code = write_marker(
+                &self.log_dir,
+                topic,
+                *partition,
+                metadata.producer_id,
+                metadata.producer_epoch,
+                committed,
+                COORDINATOR_EPOCH,
+            );
+            // A partition without a log never received data for this transaction
+            if error_code == error_code::KAFKA_STORAGE_ERROR {
+                return Err(error_code::COORDINATOR_NOT_AVAILABLE);
+            }
+        }
+
+        metadata.state = if committed {
+            TransactionState::CompleteCommit
+        } else {
+            TransactionState::CompleteAbort
+        };
+        metadata.partitions.clear();
+        self.update(transactional_id, metadata)
+    }
+}
+
+#[test]
+fn test_partition_for_matches_java_hash_code() {
+    // "my-group".hashCode() == -1_906_497_762 in Java
+    assert_eq!(partition_for("my-group", 50), 12);
+    assert_eq!(partition_for("", 50), 0);
+}
+
+#[test]
+fn test_commit_writes_markers_and_survives_reload() {
+    use crate::test_util::TempDir;
+
+    let log_dir = TempDir::new("txn-coordinator");
+    let log_dir = log_dir.path();
+    PartitionLog::create(log_dir, "events", 0).unwrap();
+
+    let mut coordinator = TransactionCoordinator::load(log_dir, 0).unwrap();
+    let mut blocks = vec![];
+    let (producer_id, producer_epoch) = coordinator
+        .init_producer_id(Some("txn"), 60_000, -1, -1, &mut |block_end| {
+            blocks.push(block_end);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(blocks, vec![PRODUCER_ID_BLOCK_SIZE]);
+    coordinator
+        .add_partitions(
+            "txn",
+            producer_id,
+            producer_epoch,
+            &[("events".to_string(), 0)],
+        )
+        .unwrap();
+    assert_eq!(
+        coordinator.end_transaction("txn", producer_id, producer_epoch + 1, true),
+        Err(error_code::PRODUCER_FENCED)
+    );
+    coordinator
+        .end_transaction("txn", producer_id, producer_epoch, true)
+        .unwrap();
+
+    let events = PartitionLog::open(log_dir, "events", 0).unwrap();
+    let record_batches = events.read_batches().unwrap();
+    assert_eq!(record_batches.len(), 1);
+    assert_eq!(record_batches[0].control_marker(), Some(COMMIT_MARKER));
+
+    let reloaded = TransactionCoordinator::load(log_dir, 0).unwrap();
+    let metadata = &reloaded.transactions["txn"];
+    assert_eq!(metadata.state, TransactionState::CompleteCommit);
+    assert_eq!(metadata.producer_id, producer_id);
+    assert_eq!(reloaded.next_producer_id, producer_id + 1);
+}
+
+#[test]
+fn test_load_skips_malformed_records() {
+    use crate::test_util::TempDir;
+
+    let log_dir = TempDir::new("txn-malformed");
+    let log_dir = log_dir.path();
+    let mut coordinator = TransactionCoordinator::load(log_dir, 0).unwrap();
+    coordinator
+        .init_producer_id(Some("txn"), 60_000, -1, -1, &mut |_| Ok(()))
+        .unwrap();
+
+    // A key cut short and a value cut short, after the good record
+    let partition = partition_for("txn", TRANSACTION_STATE_PARTITIONS);
+    let log = PartitionLog::open(log_dir, TRANSACTION_STATE_TOPIC, partition).unwrap();
+    let mut key = vec![];
+    key.put_i16(0);
+    key.put_string("txn");
+    let records = vec![
+        Record::new(0, Some(vec![0, 0, 0, 9]), Some(vec![])),
+        Record::new(1, Some(key), Some(vec![0, 0, 0])),
+    ];
+    log.append(RecordBatch::new(0, -1, -1, now_ms(), records))
+        .unwrap();
+
+    let reloaded = TransactionCoordinator::load(log_dir, 0).unwrap();
+    assert_eq!(reloaded.transactions["txn"].state, TransactionState::Empty);
+}
+
+#[test]
+fn test_committed_offsets_take_the_topic_compression() {
+    use crate::test_util::TempDir;
+
+    let log_dir = TempDir::new("txn-offsets-compression");
+    let mut coordinator = TransactionCoordinator::load(log_dir.path(), 0).unwrap();
+    let (producer_id, producer_epoch) = coordinator
+        .init_producer_id(Some("txn"), 60_000, -1, -1, &mut |_| Ok(()))
+        .unwrap();
+    coordinator
+        .add_offsets("txn", producer_id, producer_epoch, "group")
+        .unwrap();
+    let offset = TxnOffset {
+        topic: "events".to_string(),
+        partition: 0,
+        offset: 42,
+        leader_epoch: -1,
+        metadata: None,
+    };
+    coordinator
+        .commit_offsets(
+            "txn",
+            producer_id,
+            producer_epoch,
+            "group",
+            &[offset],
+            Compression::Zstd,
+        )
+        .unwrap();
+
+    let partition = partition_for("group", CONSUMER_OFFSETS_PARTITIONS);
+    let offsets = PartitionLog::open(log_dir.path(), CONSUMER_OFFSETS_TOPIC, partition).unwrap();
+    let record_batches = offsets.read_batches().unwrap();
+    assert_eq!(record_batches[0].compression(), Some(Compression::Zstd));
+    assert_eq!(record_batches[0].records.len(), 1);
+}
+
+#[test]
+fn test_timed_out_transaction_is_aborted() {
+    use crate::test_util::{transactional_batch, TempDir};
+
+    let log_dir = TempDir::new("txn-timeout");
+    let log_dir = log_dir.path();
+    let events = PartitionLog::create(log_dir, "events", 0).unwrap();
+
+    let mut coordinator = TransactionCoordinator::load(log_dir, 0).unwrap();
+    let (producer_id, producer_epoch) = coordinator
+        .init_producer_id(Some("txn"), 60_000, -1, -1, &mut |_| Ok(()))
+        .unwrap();
+    coordinator
+        .add_partitions(
+            "txn",
+            producer_id,
+            producer_epoch,
+            &[("events".to_string(), 0)],
+        )
+        .unwrap();
+    events.append(transactional_batch(producer_id)).unwrap();
+    assert_eq!(events.last_stable_offset().unwrap(), 0);
+
+    let start_timestamp = coordinator.transactions["txn"].start_timestamp;
+    assert!(coordinator
+        .abort_timed_out(start_timestamp + 59_999)
+        .is_empty());
+    assert_eq!(
+        coordinator.abort_timed_out(start_timestamp + 60_000),
+        vec!["txn".to_string()]
+    );
+    assert_eq!(events.last_stable_offset().unwrap(), 2);
+    assert_eq!(
+        events.read_batches().unwrap()[1].control_marker(),
+        Some(ABORT_MARKER)
+    );
+    assert_eq!(
+        coordinator.end_transaction("txn", producer_id, producer_epoch, true),
+        Err(error_code::PRODUCER_FENCED)
+    );
+}
+
+#[test]
+fn test_markers_from_an_older_coordinator_are_fenced() {
+    use crate::test_util::{transactional_batch, TempDir};
+
+    let log_dir = TempDir::new("txn-marker-fencing");
+    let log_dir = log_dir.path();
+    let events = PartitionLog::create(log_dir, "events", 0).unwrap();
+    let marker = |producer_id, coordinator_epoch| {
+        write_marker(
+            log_dir,
+            "events",
+            0,
+            producer_id,
+            0,
+            true,
+            coordinator_epoch,
+        )
+    };
+
+    events.append(transactional_batch(1)).unwrap();
+    assert_eq!(marker(1, 5), error_code::NONE);
+    assert_eq!(events.coordinator_epoch(1).unwrap(), Some(5));
+    events.append(transactional_batch(1)).unwrap();
+    assert_eq!(marker(1, 4), error_code::TRANSACTION_COORDINATOR_FENCED);
+    assert_eq!(marker(1, 5), error_code::NONE);
+    // Each producer's markers are fenced on their own
+    assert_eq!(events.coordinator_epoch(2).unwrap(), None);
+    assert_eq!(marker(2, 0), error_code::NONE);
+    assert_eq!(events.log_end_offset().unwrap(), 5);
+}
@@ -0,0 +1,111 @@
+use crate::acl::{AclOperation, ResourceType};
+use crate::broker::Broker;
+use crate::compression::Compression;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+use crate::transaction_coordinator::{TxnOffset, CONSUMER_OFFSETS_TOPIC};
+
+use bytes::{Buf, BufMut};
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let transactional_id = input.get_compact_string();
+    let group_id = input.get_compact_string();
+    let producer_id = input.get_i64();
+    let producer_epoch = input.get_i16();
+    let _generation_id = input.get_i32();
+    let _member_id = input.get_compact_string();
+    let _group_instance_id = input.get_compact_nullable_string();
+    let topic_count = input.get_compact_array_length();
+    let mut offsets = vec![];
+    for _ in 0..topic_count {
+        let topic = input.get_compact_string();
+        let partition_count = input.get_compact_array_length();
+        for _ in 0..partition_count {
+            let partition = input.get_i32();
+            let offset = input.get_i64();
+            let leader_epoch = input.get_i32();
+            let metadata = input.get_compact_nullable_string();
+            input.skip_tagged_fields();
+            offsets.push(TxnOffset {
+                topic: topic.clone(),
+                partition,
+                offset,
+                leader_epoch,
+                metadata,
+            });
+        }
+        input.skip_tagged_fields();
+    }
+    input.skip_tagged_fields();
+
+    // Offsets of unreadable topics fail on their own, the rest are committed
+    let (authorized, unauthorized): (Vec<TxnOffset>, Vec<TxnOffset>) =
+        offsets.into_iter().partition(|offset| {
+            broker.authorize(
+                session,
+                AclOperation::Read,
+                ResourceType::Topic,
+                &offset.topic,
+            )
+        });
+    let error_code = if !broker.authorize(
+        session,
+        AclOperation::Write,
+        ResourceType::TransactionalId,
+        &transactional_id,
+    ) {
+        error_code::TRANSACTIONAL_ID_AUTHORIZATION_FAILED
+    } else if !broker.authorize(session, AclOperation::Read, ResourceType::Group, &group_id) {
+        error_code::GROUP_AUTHORIZATION_FAILED
+    } else {
+        // The coordinator builds the batch uncompressed, which the offsets
+        // topic's compression.type may override
+        let compression = broker.compression_for(CONSUMER_OFFSETS_TOPIC, Compression::None);
+        let result = broker.transactions.lock().unwrap().commit_offsets(
+            &transactional_id,
+            producer_id,
+            producer_epoch,
+            &group_id,
+            &authorized,
+            compression,
+        );
+        result.err().unwrap_or(error_code::NONE)
+    };
+    let error_codes = authorized.iter().map(|_| error_code).chain(
+        unauthorized
+            .iter()
+            .map(|_| error_code::TOPIC_AUTHORIZATION_FAILED),
+    );
+
+    // Serialize result
+    let mut topics: Vec<(&str, Vec<(i32, i16)>)> = vec![];
+    for (offset, error_code) in authorized.iter().chain(&unauthorized).zip(error_codes) {
+        match topics.last_mut() {
+            Some((name, partitions)) if *name == offset.topic => {
+                partitions.push((offset.partition, error_code))
+            }
+            _ => topics.push((&offset.topic, vec![(offset.partition, error_code)])),
+        }
+    }
+
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_compact_array_length(topics.len());
+    for (name, partitions) in topics {
+        body.put_compact_string(name);
+        body.put_compact_array_length(partitions.len());
+        for (partition, error_code) in partitions {
+            body.put_i32(partition);
+            body.put_i16(error_code);
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
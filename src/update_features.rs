@@ -0,0 +1,139 @@
+use crate::acl::{AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::cluser_metadata::FeatureLevelRecord;
+use crate::error_code;
+use crate::feature::{self, SAFE_DOWNGRADE, UPGRADE};
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+
+use bytes::{Buf, BufMut};
+
+#[derive(Debug)]
+struct FeatureUpdate {
+    feature: String,
+    max_version_level: i16,
+    upgrade_type: i8,
+}
+
+type FeatureResults = Vec<(String, Result<(), (i16, String)>)>;
+
+// Updates are applied all or nothing, as in Kafka: when one is rejected the
+// others report no error but aren't applied either
+fn update_features(
+    updates: &[FeatureUpdate],
+    validate_only: bool,
+    broker: &Broker,
+    session: &Session,
+) -> Result<FeatureResults, (i16, String)> {
+    if !broker.authorize(
+        session,
+        AclOperation::Alter,
+        ResourceType::Cluster,
+        CLUSTER_NAME,
+    ) {
+        return Err((
+            error_code::CLUSTER_AUTHORIZATION_FAILED,
+            "Cluster authorization failed".to_string(),
+        ));
+    }
+    if updates.is_empty() {
+        return Err((
+            error_code::INVALID_REQUEST,
+            "Feature updates can not be null or empty.".to_string(),
+        ));
+    }
+    for (i, update) in updates.iter().enumerate() {
+        if updates[..i]
+            .iter()
+            .any(|previous| previous.feature == update.feature)
+        {
+            return Err((
+                error_code::INVALID_REQUEST,
+                format!("Duplicate feature {} in the request", update.feature),
+            ));
+        }
+    }
+
+    let metadata = broker.metadata();
+    let finalized = metadata.finalized_features();
+    let results: FeatureResults = updates
+        .iter()
+        .map(|update| {
+            let result = feature::validate_update(
+                finalized,
+                &update.feature,
+                update.max_version_level,
+                update.upgrade_type,
+            );
+            (update.feature.clone(), result)
+        })
+        .collect();
+
+    if !validate_only && results.iter().all(|(_, result)| result.is_ok()) {
+        let records = updates
+            .iter()
+            .map(|update| FeatureLevelRecord::new(&update.feature, update.max_version_level))
+            .map(|record| record.encode())
+            .collect();
+        broker
+            .append_metadata(records)
+            .map_err(|e| (error_code::KAFKA_STORAGE_ERROR, e.to_string()))?;
+    }
+    Ok(results)
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let _timeout_ms = input.get_i32();
+    let updates_length = input.get_compact_array_length();
+    let updates: Vec<FeatureUpdate> = (0..updates_length)
+        .map(|_| {
+            let feature = input.get_compact_string();
+            let max_version_level = input.get_i16();
+            // Version 0 only had a flag allowing downgrades
+            let upgrade_type = if header.api_version >= 1 {
+                input.get_i8()
+            } else if input.get_u8() != 0 {
+                SAFE_DOWNGRADE
+            } else {
+                UPGRADE
+            };
+            input.skip_tagged_fields();
+            FeatureUpdate {
+                feature,
+                max_version_level,
+                upgrade_type,
+            }
+        })
+        .collect();
+    let validate_only = header.api_version >= 1 && input.get_u8() != 0;
+    input.skip_tagged_fields();
+
+    let (error_code, error_message, results) =
+        match update_features(&updates, validate_only, broker, session) {
+            Ok(results) => (error_code::NONE, None, results),
+            Err((error_code, error_message)) => (error_code, Some(error_message), vec![]),
+        };
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_i32(session.throttle_time_ms); // Throttle time
+    body.put_i16(error_code);
+    body.put_compact_nullable_string(error_message.as_deref());
+    body.put_compact_array_length(results.len());
+    for (feature, result) in results {
+        let (error_code, error_message) = match result {
+            Ok(()) => (error_code::NONE, None),
+            Err((error_code, error_message)) => (error_code, Some(error_message)),
+        };
+        body.put_compact_string(&feature);
+        body.put_i16(error_code);
+        body.put_compact_nullable_string(error_message.as_deref());
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}
@@ -1,23 +1,48 @@
-use bytes::Buf;
+use bytes::{Buf, BufMut};
 
 pub trait Varint {
     fn get_signed_varint(&mut self) -> i64;
+    fn get_unsigned_varint(&mut self) -> u64;
 }
 
 impl Varint for &[u8] {
     fn get_signed_varint(&mut self) -> i64 {
+        let result = self.get_unsigned_varint();
+        (result >> 1) as i64 ^ -((result & 1) as i64)
+    }
+
+    fn get_unsigned_varint(&mut self) -> u64 {
         let mut result = 0;
         let mut shift = 0;
         loop {
             let byte = self.get_u8();
-            let value = (byte & 0b0111_1111) as i64;
+            let value = (byte & 0b0111_1111) as u64;
             result |= value << shift;
             shift += 7;
             if byte & 0b1000_0000 == 0 {
                 break;
             }
         }
-        (result >> 1) ^ -(result & 1)
+        result
+    }
+}
+
+pub trait PutVarint {
+    fn put_signed_varint(&mut self, value: i64);
+    fn put_unsigned_varint(&mut self, value: u64);
+}
+
+impl PutVarint for Vec<u8> {
+    fn put_signed_varint(&mut self, value: i64) {
+        self.put_unsigned_varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn put_unsigned_varint(&mut self, mut value: u64) {
+        while value >= 0b1000_0000 {
+            self.put_u8((value as u8 & 0b0111_1111) | 0b1000_0000);
+            value >>= 7;
+        }
+        self.put_u8(value as u8);
     }
 }
 
@@ -36,3 +61,14 @@ fn test_decode_minus_one() {
     assert_eq!(expected_output, input.get_signed_varint());
     assert!(input.is_empty());
 }
+
+#[test]
+fn test_encode_roundtrip() {
+    for value in [0, 1, -1, 63, -64, 64, 300, -300, i32::MAX as i64, i64::MIN] {
+        let mut output = vec![];
+        output.put_signed_varint(value);
+        let mut input: &[u8] = &output;
+        assert_eq!(value, input.get_signed_varint());
+        assert!(input.is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::acl::{AclOperation, ResourceType, CLUSTER_NAME};
+use crate::broker::Broker;
+use crate::error_code;
+use crate::protocol::{encode_response, CompactBuf, CompactBufMut, RequestHeader};
+use crate::session::Session;
+use crate::transaction_coordinator::write_marker;
+
+use bytes::{Buf, BufMut};
+
+struct TxnMarker {
+    producer_id: i64,
+    producer_epoch: i16,
+    committed: bool,
+    topics: Vec<(String, Vec<i32>)>,
+    coordinator_epoch: i32,
+}
+
+pub fn handle_request(input: &[u8], broker: &Broker, session: &Session) -> Vec<u8> {
+    // Deserialize input
+    let mut input = input;
+    let header = RequestHeader::parse(&mut input);
+    let marker_count = input.get_compact_array_length();
+    let mut markers = vec![];
+    for _ in 0..marker_count {
+        let producer_id = input.get_i64();
+        let producer_epoch = input.get_i16();
+        let committed = input.get_u8() != 0;
+        let topic_count = input.get_compact_array_length();
+        let mut topics = vec![];
+        for _ in 0..topic_count {
+            let name = input.get_compact_string();
+            let partition_count = input.get_compact_array_length();
+            let partitions = (0..partition_count).map(|_| input.get_i32()).collect();
+            input.skip_tagged_fields();
+            topics.push((name, partitions));
+        }
+        let coordinator_epoch = input.get_i32();
+        input.skip_tagged_fields();
+        markers.push(TxnMarker {
+            producer_id,
+            producer_epoch,
+            committed,
+            topics,
+            coordinator_epoch,
+        });
+    }
+    input.skip_tagged_fields();
+
+    // Only other brokers may write markers
+    let authorized = broker.authorize(
+        session,
+        AclOperation::ClusterAction,
+        ResourceType::Cluster,
+        CLUSTER_NAME,
+    );
+
+    // Hold the coordinator lock so markers never interleave with its own appends
+    let log_dir = broker.config.metadata_log_dir();
+    let _transactions = broker.transactions.lock().unwrap();
+
+    // Serialize result
+    let mut body = vec![];
+    body.put_compact_array_length(markers.len());
+    for marker in markers {
+        body.put_i64(marker.producer_id);
+        body.put_compact_array_length(marker.topics.len());
+        for (name, partitions) in marker.topics {
+            body.put_compact_string(&name);
+            body.put_compact_array_length(partitions.len());
+            for partition in partitions {
+                let error_code = if authorized {
+                    write_marker(
+                        &log_dir,
+                        &name,
+                        partition,
+                        marker.producer_id,
+                        marker.producer_epoch,
+                        marker.committed,
+                        marker.coordinator_epoch,
+                    )
+                } else {
+                    error_code::CLUSTER_AUTHORIZATION_FAILED
+                };
+                body.put_i32(partition);
+                body.put_i16(error_code);
+                body.put_empty_tagged_fields();
+            }
+            body.put_empty_tagged_fields();
+        }
+        body.put_empty_tagged_fields();
+    }
+    body.put_empty_tagged_fields();
+
+    encode_response(header.correlation_id, &body)
+}